@@ -0,0 +1,64 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 比较串行与并行（rayon）计算 GNU 哈希的耗时：`cargo bench --features rayon`
+
+use accessor::Encode;
+use elf_trait::hash::{gnu_hash, precompute_name_hashes};
+use elf_trait::symbol::DynSymView;
+use elf_trait::{Class, Strtab, StrtabBuilder};
+use std::time::{Duration, Instant};
+
+const COUNT: usize = 200_000;
+const RUNS: usize = 10;
+
+/// 预热一次后重复运行 RUNS 次，返回结果与其中最短的耗时
+fn measure<R>(mut f: impl FnMut() -> R) -> (R, Duration) {
+    let result = f();
+    let time = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            drop(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+    (result, time)
+}
+
+fn main() {
+    let mut strtab = StrtabBuilder::new();
+    let mut bytes = vec![0u8; COUNT * 24];
+    for (i, sym) in bytes.chunks_mut(24).enumerate() {
+        let name = strtab.add(&format!("_ZN9benchmark6symbol{}E", i));
+        sym[..4].copy_from_slice(&(name as u32).to_le_bytes());
+    }
+    let symtab = DynSymView::new(&bytes, Class::Elf64, Encode::Le);
+    let names = || symtab.iter().map(|sym| sym.st_name as usize);
+
+    let (serial, serial_time) = measure(|| {
+        names()
+            .map(|name| gnu_hash(strtab.get(name).unwrap_or("").as_bytes()))
+            .collect::<Vec<_>>()
+    });
+    let (parallel, parallel_time) = measure(|| precompute_name_hashes(names(), &strtab));
+
+    assert_eq!(serial, parallel);
+    println!("{} symbols, best of {} runs", symtab.len(), RUNS);
+    println!("serial:   {:?}", serial_time);
+    println!("parallel: {:?}", parallel_time);
+}
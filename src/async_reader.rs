@@ -0,0 +1,258 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 基于 tokio 的异步读取，只有 I/O 是异步的，字段的解码仍然是同步的。
+//!
+//! [`from_async_reader`] 会 await 两次读取：ident 与 Elf Header；
+//! 表项数量或 e_shstrndx 使用扩展编号时还会读取 0 号 section header。
+//! 之后只有 `read_*` 系列方法会 seek 并 await 读取，其余方法都只访问已读入内存的头部。
+//! 表的范围按 [`ParseLimits`] 检查，异常的头部在读取前就会以 `io::ErrorKind::InvalidData` 返回。
+
+use crate::codec::Codec;
+use crate::elf::{table_range, HeaderCounts};
+use crate::owned::{OwnedEhdr, OwnedShdr};
+use crate::{Class, ElfError, ParseLimits};
+use accessor::Encode;
+use std::io;
+use std::ops::Range;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, SeekFrom};
+
+const EI_NIDENT: usize = 16;
+
+/// 已读入 Elf Header 的异步 Elf 文件，表与 section 数据需要通过 `read_*` 方法按需读取。
+#[derive(Debug, Clone)]
+pub struct AsyncElf {
+    /// 包含 ident 在内的完整 Elf Header
+    header: Vec<u8>,
+    ehdr: OwnedEhdr,
+    codec: Codec,
+    counts: HeaderCounts,
+    limits: ParseLimits,
+}
+
+/// 以默认的 [`ParseLimits`] 读取 ident 与 Elf Header，reader 会从头开始读取。
+pub async fn from_async_reader<R>(r: &mut R) -> io::Result<AsyncElf>
+where
+    R: AsyncRead + AsyncSeek + Unpin,
+{
+    from_async_reader_with_limits(r, &ParseLimits::default()).await
+}
+
+/// 读取 ident 与 Elf Header，之后的表按 limits 限制
+pub async fn from_async_reader_with_limits<R>(
+    r: &mut R,
+    limits: &ParseLimits,
+) -> io::Result<AsyncElf>
+where
+    R: AsyncRead + AsyncSeek + Unpin,
+{
+    let mut header = vec![0u8; EI_NIDENT];
+    r.seek(SeekFrom::Start(0)).await?;
+    r.read_exact(&mut header).await?;
+    let codec = OwnedEhdr::codec(&header).map_err(invalid)?;
+    header.resize(codec.class.ehdr_size(), 0);
+    r.read_exact(&mut header[EI_NIDENT..]).await?;
+    let ehdr = OwnedEhdr::parse(codec, &header).map_err(invalid)?;
+    let counts = if HeaderCounts::extended(&ehdr) {
+        let mut first = vec![0u8; codec.class.shdr_size()];
+        r.seek(SeekFrom::Start(ehdr.e_shoff)).await?;
+        r.read_exact(&mut first).await?;
+        let first = OwnedShdr::parse(codec, &first, 0).map_err(invalid)?;
+        HeaderCounts::resolve(&ehdr, &first).map_err(invalid)?
+    } else {
+        HeaderCounts::raw(&ehdr)
+    };
+    Ok(AsyncElf {
+        header,
+        ehdr,
+        codec,
+        counts,
+        limits: *limits,
+    })
+}
+
+fn invalid(err: ElfError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+impl AsyncElf {
+    pub fn ident(&self) -> &[u8] {
+        &self.header[..EI_NIDENT]
+    }
+
+    /// 完整的 Elf Header（含 ident）
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// 解码后的 Elf Header
+    pub fn ehdr(&self) -> &OwnedEhdr {
+        &self.ehdr
+    }
+
+    pub fn class(&self) -> Class {
+        self.codec.class
+    }
+
+    pub fn encode(&self) -> Encode {
+        self.codec.encode
+    }
+
+    pub fn is_64(&self) -> bool {
+        self.class() == Class::Elf64
+    }
+
+    /// section 字符串表的索引，e_shstrndx 为 SHN_XINDEX 时为 0 号 section 的 sh_link
+    pub fn shstrndx(&self) -> usize {
+        self.counts.shstrndx
+    }
+
+    /// off 处共 num 项的表的范围。
+    ///
+    /// entsize 只能为 0 或 Class 对应的大小，num 按 limit 限制，因此读取的字节数总是有界的。
+    fn table(
+        &self,
+        what: &'static str,
+        (off, entsize, num): (u64, u16, usize),
+        size: usize,
+        limit: usize,
+    ) -> io::Result<Range<usize>> {
+        if entsize != 0 && entsize as usize != size {
+            return Err(invalid(ElfError::Malformed(
+                "unexpected header table entry size",
+            )));
+        }
+        let num = self.limits.cap(what, num, limit).map_err(invalid)?;
+        table_range(off, entsize, num, size).map_err(invalid)
+    }
+
+    /// Program Header Table 在文件中的范围
+    pub fn pht_range(&self) -> io::Result<Range<usize>> {
+        let e = &self.ehdr;
+        let size = self.class().phdr_size();
+        let fields = (e.e_phoff, e.e_phentsize, self.counts.phnum);
+        self.table("segments", fields, size, self.limits.max_segments)
+    }
+
+    /// Section Header Table 在文件中的范围
+    pub fn sht_range(&self) -> io::Result<Range<usize>> {
+        let e = &self.ehdr;
+        let size = self.class().shdr_size();
+        let fields = (e.e_shoff, e.e_shentsize, self.counts.shnum);
+        self.table("sections", fields, size, self.limits.max_sections)
+    }
+
+    /// seek 到 range.start 并读取 range 内的数据（await）。
+    ///
+    /// 会按 range 的长度分配缓冲区，range 来自不可信的文件时调用者需要自行检查其大小。
+    pub async fn read_range<R>(&self, r: &mut R, range: Range<usize>) -> io::Result<Vec<u8>>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+    {
+        let mut data = vec![0u8; range.len()];
+        r.seek(SeekFrom::Start(range.start as u64)).await?;
+        r.read_exact(&mut data).await?;
+        Ok(data)
+    }
+
+    /// 读取 Program Header Table 的原始数据（await）
+    pub async fn read_pht<R>(&self, r: &mut R) -> io::Result<Vec<u8>>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+    {
+        self.read_range(r, self.pht_range()?).await
+    }
+
+    /// 读取 Section Header Table 的原始数据（await）
+    pub async fn read_sht<R>(&self, r: &mut R) -> io::Result<Vec<u8>>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+    {
+        self.read_range(r, self.sht_range()?).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::sample_elf64;
+    use std::future::Future;
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    /// Cursor 上的读取总是立即完成，轮询到 Ready 即可，不需要运行时
+    fn block_on<F: Future>(f: F) -> F::Output {
+        struct Noop;
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut f = Box::pin(f);
+        loop {
+            if let Poll::Ready(out) = f.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    #[test]
+    fn header_and_tables() {
+        let bytes = sample_elf64();
+        let mut r = Cursor::new(bytes.clone());
+        let elf = block_on(from_async_reader(&mut r)).unwrap();
+        assert!(elf.is_64());
+        assert_eq!(elf.ehdr().e_shnum, 3);
+        assert_eq!(elf.pht_range().unwrap(), 64..120);
+        assert_eq!(block_on(elf.read_pht(&mut r)).unwrap(), &bytes[64..120]);
+        assert_eq!(
+            block_on(elf.read_sht(&mut r)).unwrap(),
+            &bytes[0x100..0x1c0]
+        );
+
+        let mut bad = bytes.clone();
+        bad[0] = 0;
+        let err = block_on(from_async_reader(&mut Cursor::new(bad))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn hostile_header() {
+        let mut bytes = sample_elf64();
+        bytes[40..48].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        let elf = block_on(from_async_reader(&mut Cursor::new(bytes.clone()))).unwrap();
+        assert_eq!(
+            elf.sht_range().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        // e_shentsize 为 0xffff 时不会按其分配缓冲区
+        bytes[58..60].copy_from_slice(&u16::MAX.to_le_bytes());
+        let elf = block_on(from_async_reader(&mut Cursor::new(bytes.clone()))).unwrap();
+        assert!(elf.sht_range().is_err());
+
+        let limits = ParseLimits {
+            max_segments: 0,
+            ..Default::default()
+        };
+        let mut r = Cursor::new(bytes);
+        let elf = block_on(from_async_reader_with_limits(&mut r, &limits)).unwrap();
+        let err = block_on(elf.read_pht(&mut r)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 嵌入在文件中的校验和的重新计算与验证。
+//!
+//! Elf 本身没有校验和字段，但基于 Elf 的格式（或自定义 note）常会嵌入一个，
+//! 修改文件后需要按照 [`ChecksumSpec`] 描述的位置与范围重新计算。
+
+use crate::codec::Codec;
+use crate::{Class, ElfError};
+use accessor::Encode;
+use std::ops::Range;
+
+/// 用户提供的校验和算法
+pub type ChecksumFn = Box<dyn Fn(&[u8]) -> u64>;
+
+/// 计算校验和的算法
+pub enum ChecksumAlgo {
+    /// IEEE 802.3 CRC32（与 zlib、.gnu_debuglink 相同）
+    Crc32,
+    /// 用户提供的算法，结果按字段宽度截断后写入
+    Custom(ChecksumFn),
+}
+
+impl ChecksumAlgo {
+    fn compute(&self, data: &[u8]) -> u64 {
+        match self {
+            ChecksumAlgo::Crc32 => crc32(data) as u64,
+            ChecksumAlgo::Custom(f) => f(data),
+        }
+    }
+}
+
+/// 描述校验和存放在哪里以及覆盖哪些数据
+pub struct ChecksumSpec {
+    pub algo: ChecksumAlgo,
+    /// 校验和字段所在范围，长度不超过 8 字节
+    pub field_range: Range<usize>,
+    /// 参与计算的范围，可以包含校验和字段本身，计算时该字段视为 0
+    pub covered_range: Range<usize>,
+    /// 校验和字段的字节序
+    pub encode: Encode,
+}
+
+impl ChecksumSpec {
+    fn check(&self, buf: &[u8]) -> Result<(), ElfError> {
+        for range in &[&self.field_range, &self.covered_range] {
+            if range.start > range.end || range.end > buf.len() {
+                return Err(ElfError::OutOfBounds((*range).clone()));
+            }
+        }
+        if self.field_range.len() > 8 {
+            return Err(ElfError::Unsupported("checksum field wider than 8 bytes"));
+        }
+        Ok(())
+    }
+
+    /// 将校验和字段视为 0 后计算覆盖范围的校验和，结果截断为字段宽度
+    fn compute(&self, buf: &[u8]) -> u64 {
+        let mut covered = buf[self.covered_range.clone()].to_vec();
+        let start = self.field_range.start.max(self.covered_range.start);
+        let end = self.field_range.end.min(self.covered_range.end);
+        if start < end {
+            let base = self.covered_range.start;
+            covered[start - base..end - base]
+                .iter_mut()
+                .for_each(|b| *b = 0);
+        }
+        let value = self.algo.compute(&covered);
+        match self.field_range.len() {
+            8 => value,
+            len => value & ((1u64 << (len * 8)) - 1),
+        }
+    }
+}
+
+/// 按 spec 重新计算校验和并写回，返回写入的值
+pub fn recompute_checksum(buf: &mut [u8], spec: &ChecksumSpec) -> Result<u64, ElfError> {
+    spec.check(buf)?;
+    buf[spec.field_range.clone()]
+        .iter_mut()
+        .for_each(|b| *b = 0);
+    let value = spec.compute(buf);
+    Codec::new(Class::Elf64, spec.encode).put_uint(&mut buf[spec.field_range.clone()], value);
+    Ok(value)
+}
+
+/// 验证 buf 中存放的校验和是否与计算结果一致
+pub fn verify_checksum(buf: &[u8], spec: &ChecksumSpec) -> Result<bool, ElfError> {
+    spec.check(buf)?;
+    let stored = Codec::new(Class::Elf64, spec.encode).uint(&buf[spec.field_range.clone()]);
+    Ok(stored == spec.compute(buf))
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+/// IEEE 802.3 CRC32
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn recompute_then_verify() {
+        let mut buf = b"header:????:payload".to_vec();
+        let spec = ChecksumSpec {
+            algo: ChecksumAlgo::Crc32,
+            field_range: 7..11,
+            covered_range: 0..buf.len(),
+            encode: Encode::Le,
+        };
+        recompute_checksum(&mut buf, &spec).unwrap();
+        assert!(verify_checksum(&buf, &spec).unwrap());
+        buf[15] ^= 1;
+        assert!(!verify_checksum(&buf, &spec).unwrap());
+    }
+
+    #[test]
+    fn custom_algo() {
+        let mut buf = vec![1u8, 2, 3, 0, 0];
+        let spec = ChecksumSpec {
+            algo: ChecksumAlgo::Custom(Box::new(|d| d.iter().map(|&b| b as u64).sum())),
+            field_range: 3..5,
+            covered_range: 0..3,
+            encode: Encode::Be,
+        };
+        assert_eq!(recompute_checksum(&mut buf, &spec).unwrap(), 6);
+        assert_eq!(&buf[3..], &[0, 6]);
+        assert!(verify_checksum(&buf, &spec).unwrap());
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 位置与宽度随 Class 变化的字段。
+//!
+//! `Field` 的范围与 `BytesType` 在编译期固定，同一个字段类型无法同时描述 ELF32 与 ELF64 的布局。
+//! [`ClassField`] 在运行时根据 Class 给出字段的范围，值统一以 u64 表示，
+//! 由 [`EhdrView`](crate::EhdrView) 等 view 的 `get_class`/`with_class` 读写。
+
+use crate::Class;
+use std::ops::Range;
+
+/// 由若干命名字段组成的结构，用于列出各字段的位置（如十六进制编辑器的字段标注）
+pub trait Layout {
+    /// 按偏移顺序列出各字段的名称与相对范围
+    fn fields(class: Class) -> Vec<(&'static str, Range<usize>)>;
+}
+
+/// 范围由 Class 决定的整数字段
+pub trait ClassField {
+    fn range_of(class: Class) -> Range<usize>;
+}
+
+macro_rules! class_field {
+    ($($(#[$doc:meta])* $name:ident: ($off32:expr, $len32:expr), ($off64:expr, $len64:expr);)*) => {$(
+        $(#[$doc])*
+        pub struct $name;
+        impl ClassField for $name {
+            fn range_of(class: Class) -> Range<usize> {
+                match class {
+                    Class::Elf32 => $off32..$off32 + $len32,
+                    Class::Elf64 => $off64..$off64 + $len64,
+                }
+            }
+        }
+    )*};
+}
+
+class_field! {
+    /// e_type
+    EType: (16, 2), (16, 2);
+    /// e_machine
+    EMachine: (18, 2), (18, 2);
+    /// e_version
+    EVersion: (20, 4), (20, 4);
+    /// e_entry
+    EEntry: (24, 4), (24, 8);
+    /// e_phoff
+    EPhoff: (28, 4), (32, 8);
+    /// e_shoff
+    EShoff: (32, 4), (40, 8);
+    /// e_flags
+    EFlags: (36, 4), (48, 4);
+    /// e_ehsize
+    EEhsize: (40, 2), (52, 2);
+    /// e_phentsize
+    EPhentsize: (42, 2), (54, 2);
+    /// e_phnum
+    EPhnum: (44, 2), (56, 2);
+    /// e_shentsize
+    EShentsize: (46, 2), (58, 2);
+    /// e_shnum
+    EShnum: (48, 2), (60, 2);
+    /// e_shstrndx
+    EShstrndx: (50, 2), (62, 2);
+}
+
+/// Elf Header 的布局
+pub struct EhdrLayout;
+
+impl Layout for EhdrLayout {
+    fn fields(class: Class) -> Vec<(&'static str, Range<usize>)> {
+        vec![
+            ("e_ident", 0..16),
+            ("e_type", EType::range_of(class)),
+            ("e_machine", EMachine::range_of(class)),
+            ("e_version", EVersion::range_of(class)),
+            ("e_entry", EEntry::range_of(class)),
+            ("e_phoff", EPhoff::range_of(class)),
+            ("e_shoff", EShoff::range_of(class)),
+            ("e_flags", EFlags::range_of(class)),
+            ("e_ehsize", EEhsize::range_of(class)),
+            ("e_phentsize", EPhentsize::range_of(class)),
+            ("e_phnum", EPhnum::range_of(class)),
+            ("e_shentsize", EShentsize::range_of(class)),
+            ("e_shnum", EShnum::range_of(class)),
+            ("e_shstrndx", EShstrndx::range_of(class)),
+        ]
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 按 Class 与 Encode 顺序读写原始字节，供 owned 类型的解析与序列化使用。
+
+use crate::util::clamped_slice;
+use crate::{field, Class, ElfError};
+use accessor::Encode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Codec {
+    pub class: Class,
+    pub encode: Encode,
+}
+
+impl Codec {
+    pub fn new(class: Class, encode: Encode) -> Self {
+        Codec { class, encode }
+    }
+
+    /// 解码任意长度（不超过 8 字节）的无符号整数
+    pub fn uint(&self, bytes: &[u8]) -> u64 {
+        match self.encode {
+            Encode::Le => field::from_le_bytes_sized(bytes, bytes.len()),
+            Encode::Be => field::from_be_bytes_sized(bytes, bytes.len()),
+        }
+    }
+
+    /// 将 val 的低 out.len() 个字节编码到 out
+    pub fn put_uint(&self, out: &mut [u8], val: u64) {
+        let len = out.len();
+        for (i, b) in out.iter_mut().enumerate() {
+            let shift = match self.encode {
+                Encode::Le => i,
+                Encode::Be => len - 1 - i,
+            };
+            *b = (val >> (shift * 8)) as u8;
+        }
+    }
+
+    /// 读取 buf 中 off 处长度为 len 的整数
+    pub fn read(&self, buf: &[u8], off: usize, len: usize) -> Result<u64, ElfError> {
+        let range = off..off.saturating_add(len);
+        clamped_slice(buf, &range)
+            .map(|b| self.uint(b))
+            .ok_or(ElfError::OutOfBounds(range))
+    }
+
+    pub fn reader<'a>(&self, buf: &'a [u8], pos: usize) -> Reader<'a> {
+        Reader {
+            codec: *self,
+            buf,
+            pos,
+        }
+    }
+
+    pub fn writer<'a>(&self, buf: &'a mut [u8], pos: usize) -> Writer<'a> {
+        Writer {
+            codec: *self,
+            buf,
+            pos,
+        }
+    }
+}
+
+/// 顺序读取字段
+pub(crate) struct Reader<'a> {
+    codec: Codec,
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn uint(&mut self, len: usize) -> Result<u64, ElfError> {
+        let val = self.codec.read(self.buf, self.pos, len)?;
+        self.pos += len;
+        Ok(val)
+    }
+    pub fn u16(&mut self) -> Result<u16, ElfError> {
+        self.uint(2).map(|v| v as u16)
+    }
+    pub fn u32(&mut self) -> Result<u32, ElfError> {
+        self.uint(4).map(|v| v as u32)
+    }
+    /// 长度由 Class 决定的字段
+    pub fn word(&mut self) -> Result<u64, ElfError> {
+        self.uint(self.codec.class.word_size())
+    }
+}
+
+/// 顺序写入字段，越界时 panic，调用者需先准备好足够大的缓冲区
+pub(crate) struct Writer<'a> {
+    codec: Codec,
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn uint(&mut self, len: usize, val: u64) {
+        self.codec
+            .put_uint(&mut self.buf[self.pos..self.pos + len], val);
+        self.pos += len;
+    }
+    pub fn u16(&mut self, val: u16) {
+        self.uint(2, val as u64)
+    }
+    pub fn u32(&mut self, val: u32) {
+        self.uint(4, val as u64)
+    }
+    pub fn word(&mut self, val: u64) {
+        self.uint(self.codec.class.word_size(), val)
+    }
+}
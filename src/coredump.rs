@@ -0,0 +1,183 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! ET_CORE 文件（core dump）的访问。
+//!
+//! core dump 通常只有 Program Header Table，没有 section，
+//! 进程信息保存在 PT_NOTE segment 中，内存内容保存在 PT_LOAD segment 中。
+
+use crate::note::{NoteEntry, NoteIter};
+use crate::segments::LoadSegment;
+use crate::util::clamped_slice;
+use crate::{ElfError, ObjectType, OwnedElf, SegmentType};
+use accessor::Encode;
+
+/// 在没有 section 的 core dump 上使用 section 相关操作时 `ElfError::Unsupported` 的内容
+pub(crate) const NO_SECTIONS: &str = "section headers not available in core files";
+
+/// 只通过 Program Header 访问 core dump 的 view
+#[derive(Debug, Clone, Copy)]
+pub struct CoreFile<'a> {
+    elf: &'a OwnedElf,
+    encode: Encode,
+}
+
+impl<'a> CoreFile<'a> {
+    /// e_type 不是 ET_CORE 时返回 `ElfError::Unsupported`，ident 无效时返回对应的错误
+    pub fn new(elf: &'a OwnedElf) -> Result<Self, ElfError> {
+        if !elf.is_core() {
+            return Err(ElfError::Unsupported("not a core file"));
+        }
+        let encode = elf.encode()?;
+        Ok(CoreFile { elf, encode })
+    }
+
+    /// 依次遍历所有 PT_NOTE segment 中的 note 项（如 NT_PRSTATUS、NT_FILE）
+    pub fn notes(&self) -> impl Iterator<Item = NoteEntry<'a>> + 'a {
+        let (elf, encode) = (self.elf, self.encode);
+        elf.phdrs
+            .iter()
+            .filter(|phdr| SegmentType::from(phdr.p_type) == SegmentType::Note)
+            .filter_map(move |phdr| {
+                let data = clamped_slice(&elf.data, &phdr.file_range())?;
+                Some(NoteIter::new(data, encode, phdr.p_align as usize))
+            })
+            .flatten()
+    }
+
+    /// 按 Program Header 的顺序遍历 PT_LOAD segment，即进程的内存映射。
+    ///
+    /// 未转储的映射（如只读的代码段）p_filesz 为 0，file_data 为空。
+    pub fn memory(&self) -> impl Iterator<Item = LoadSegment<'a>> + 'a {
+        let elf = self.elf;
+        elf.phdrs
+            .iter()
+            .filter(|phdr| SegmentType::from(phdr.p_type) == SegmentType::Load)
+            .filter_map(move |phdr| {
+                Some(LoadSegment {
+                    vaddr: phdr.p_vaddr,
+                    file_data: clamped_slice(&elf.data, &phdr.file_range())?,
+                    mem_size: phdr.p_memsz,
+                    flags: phdr.p_flags,
+                })
+            })
+    }
+
+    /// 读取从 vaddr 开始、最多 len 字节的转储内存，vaddr 不在任何已转储的映射中时返回 None
+    pub fn read_memory(&self, vaddr: u64, len: usize) -> Option<&'a [u8]> {
+        self.memory().find_map(|seg| {
+            let delta = vaddr.checked_sub(seg.vaddr)? as usize;
+            let rest = seg.file_data.get(delta..).filter(|rest| !rest.is_empty())?;
+            Some(&rest[..len.min(rest.len())])
+        })
+    }
+}
+
+impl OwnedElf {
+    /// e_type 是否为 ET_CORE
+    pub fn is_core(&self) -> bool {
+        ObjectType::from(self.ehdr.e_type) == ObjectType::Core
+    }
+
+    /// 需要 Section Header Table 的操作在没有 section 的 core dump 上返回的错误
+    pub(crate) fn require_sections(&self) -> Result<(), ElfError> {
+        if self.shdrs.is_empty() && self.is_core() {
+            Err(ElfError::Unsupported(NO_SECTIONS))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::note::Note;
+    use crate::owned::*;
+    use crate::test_util::*;
+    use crate::Ehdr;
+    use accessor::Setter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn notes_and_memory() {
+        let mut data = vec![0u8; 0x100];
+        for (i, word) in [5u32, 4, 1].iter().enumerate() {
+            data[0x40 + i * 4..0x44 + i * 4].copy_from_slice(&word.to_le_bytes());
+        }
+        data[0x4c..0x51].copy_from_slice(b"CORE\0");
+        data[0x54..0x58].copy_from_slice(&[1, 2, 3, 4]);
+        data[0x80..0x88].copy_from_slice(b"stackmem");
+        let phdr = |p_type, off, size, vaddr| OwnedPhdr {
+            p_type,
+            p_offset: off,
+            p_filesz: size,
+            p_memsz: size,
+            p_vaddr: vaddr,
+            p_align: 4,
+            ..Default::default()
+        };
+        let bytes = OwnedElf {
+            ehdr: OwnedEhdr {
+                ident: elf64_ident(),
+                e_type: 4,
+                e_phoff: 0xc0,
+                e_phnum: 2,
+                ..Default::default()
+            },
+            phdrs: vec![phdr(4, 0x40, 0x18, 0), phdr(1, 0x80, 8, 0x7ff0_0000)],
+            shdrs: Vec::new(),
+            data,
+        }
+        .to_bytes()
+        .unwrap();
+        let elf = OwnedElf::parse(&bytes).unwrap();
+
+        let core = CoreFile::new(&elf).unwrap();
+        let notes: Vec<_> = core.notes().collect();
+        assert_eq!(notes.len(), 1);
+        assert_eq!((notes[0].name(), notes[0].n_type()), (&b"CORE"[..], 1));
+        assert_eq!(notes[0].desc(), [1, 2, 3, 4]);
+        assert_eq!(core.memory().count(), 1);
+        assert_eq!(core.read_memory(0x7ff0_0005, 16), Some(&b"mem"[..]));
+        assert_eq!(core.read_memory(0x7ff0_0008, 1), None);
+
+        let unsupported = ElfError::Unsupported(NO_SECTIONS);
+        assert_eq!(elf.symbols(0), Err(unsupported.clone()));
+        assert_eq!(elf.section_data(0), Err(unsupported.clone()));
+        assert_eq!(elf.section_name(0), Err(unsupported));
+    }
+
+    #[test]
+    fn trait_level_guard() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 64]));
+        let ehdr = Ehdr64(Obj {
+            buf: buf.clone(),
+            base: 0,
+            encode: Encode::Le,
+        });
+        let sht = table(&buf, 0..0, Shdr64);
+        ehdr.with::<EType>(ObjectType::Core);
+        assert_eq!(
+            ehdr.require_sections(&sht),
+            Err(ElfError::Unsupported(NO_SECTIONS))
+        );
+        ehdr.with::<EType>(ObjectType::Exec);
+        assert_eq!(ehdr.require_sections(&sht), Ok(()));
+    }
+}
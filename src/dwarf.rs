@@ -0,0 +1,284 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 定位 DWARF 调试信息所在的 section，所有结果均为对原始数据的切片，不做拷贝。
+
+use crate::checksum::crc32;
+use crate::codec::Codec;
+use crate::sections::section_data_by_name;
+use crate::util::{checked_align_up, clamped_slice};
+use crate::{Class, Shdr, ShdrTab, Strtab};
+
+/// 主目标文件中的 DWARF section
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DwarfSections<'a> {
+    pub debug_info: Option<&'a [u8]>,
+    pub debug_abbrev: Option<&'a [u8]>,
+    pub debug_str: Option<&'a [u8]>,
+    pub debug_line: Option<&'a [u8]>,
+    /// DWARF 5 中行号程序引用的文件名与目录名
+    pub debug_line_str: Option<&'a [u8]>,
+    pub debug_ranges: Option<&'a [u8]>,
+    pub debug_aranges: Option<&'a [u8]>,
+    pub debug_frame: Option<&'a [u8]>,
+    /// split-DWARF 中 .dwo 通过 DW_AT_addr_base 引用主文件的 .debug_addr
+    pub debug_addr: Option<&'a [u8]>,
+}
+
+/// split-DWARF（`-gsplit-dwarf`）产生的 `.dwo` section
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DwoSections<'a> {
+    pub debug_info: Option<&'a [u8]>,
+    pub debug_abbrev: Option<&'a [u8]>,
+    pub debug_str: Option<&'a [u8]>,
+    pub debug_str_offsets: Option<&'a [u8]>,
+}
+
+impl<'a> DwoSections<'a> {
+    /// 没有任何 `.dwo` section 时返回 true
+    pub fn is_empty(&self) -> bool {
+        self.debug_info.is_none()
+            && self.debug_abbrev.is_none()
+            && self.debug_str.is_none()
+            && self.debug_str_offsets.is_none()
+    }
+}
+
+/// 按名称遍历 section，对每个数据在 buf 范围内的 section 调用 f
+fn for_each_named<'a, T, F>(sht: &impl ShdrTab<T>, shstrtab: &impl Strtab, buf: &'a [u8], mut f: F)
+where
+    T: Shdr,
+    F: FnMut(&str, &'a [u8]),
+{
+    for shdr in sht.iter() {
+        let name = match shstrtab.get(shdr.get::<T::Name>()) {
+            Some(name) => name,
+            None => continue,
+        };
+        if let Some(data) = clamped_slice(buf, &shdr.file_range()) {
+            f(name, data);
+        }
+    }
+}
+
+/// 定位主目标文件中的 DWARF section，`shstrtab` 为 section 名称所在的字符串表。
+pub fn dwarf_sections<'a, T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    shstrtab: &impl Strtab,
+    buf: &'a [u8],
+) -> DwarfSections<'a> {
+    let mut secs = DwarfSections::default();
+    for_each_named(sht, shstrtab, buf, |name, data| {
+        let slot = match name {
+            ".debug_info" => &mut secs.debug_info,
+            ".debug_abbrev" => &mut secs.debug_abbrev,
+            ".debug_str" => &mut secs.debug_str,
+            ".debug_line" => &mut secs.debug_line,
+            ".debug_line_str" => &mut secs.debug_line_str,
+            ".debug_ranges" => &mut secs.debug_ranges,
+            ".debug_aranges" => &mut secs.debug_aranges,
+            ".debug_frame" => &mut secs.debug_frame,
+            ".debug_addr" => &mut secs.debug_addr,
+            _ => return,
+        };
+        *slot = Some(data);
+    });
+    secs
+}
+
+/// 定位 `.debug_*.dwo` section，既可用于主目标文件，也可用于单独的 `.dwo` 文件。
+pub fn dwo_sections<'a, T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    shstrtab: &impl Strtab,
+    buf: &'a [u8],
+) -> DwoSections<'a> {
+    let mut secs = DwoSections::default();
+    for_each_named(sht, shstrtab, buf, |name, data| {
+        let slot = match name {
+            ".debug_info.dwo" => &mut secs.debug_info,
+            ".debug_abbrev.dwo" => &mut secs.debug_abbrev,
+            ".debug_str.dwo" => &mut secs.debug_str,
+            ".debug_str_offsets.dwo" => &mut secs.debug_str_offsets,
+            _ => return,
+        };
+        *slot = Some(data);
+    });
+    secs
+}
+
+/// `.gnu_debuglink` 的内容：分离出的调试文件名及其 CRC32
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugLink<'a> {
+    pub filename: &'a str,
+    pub crc: u32,
+}
+
+impl<'a> DebugLink<'a> {
+    /// 调试文件的内容是否与记录的 CRC32 一致
+    pub fn verify(&self, debug_file: &[u8]) -> bool {
+        crc32(debug_file) == self.crc
+    }
+}
+
+/// `.gnu_debugaltlink` 的内容：dwz 生成的公共调试文件名及其 build-id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugAltLink<'a> {
+    pub filename: &'a str,
+    pub build_id: &'a [u8],
+}
+
+/// 将 data 拆分为以 NUL 结尾的文件名与其后的字节
+fn split_filename(data: &[u8]) -> Option<(&str, usize)> {
+    let end = data.iter().position(|&b| b == 0)?;
+    Some((std::str::from_utf8(&data[..end]).ok()?, end + 1))
+}
+
+/// 解析 `.gnu_debuglink`，CRC32 位于文件名之后下一个 4 字节对齐处，字节序与文件相同
+pub fn debuglink<'a, T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    shstrtab: &impl Strtab,
+    buf: &'a [u8],
+) -> Option<DebugLink<'a>> {
+    let data = section_data_by_name(sht, shstrtab, buf, ".gnu_debuglink")?;
+    let (filename, name_end) = split_filename(data)?;
+    let crc_off = checked_align_up(name_end, 4)?;
+    let crc = Codec::new(Class::Elf32, sht.table().encode())
+        .read(data, crc_off, 4)
+        .ok()? as u32;
+    Some(DebugLink { filename, crc })
+}
+
+/// 解析 `.gnu_debugaltlink`，build-id 紧跟在文件名的 NUL 之后，没有填充
+pub fn debugaltlink<'a, T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    shstrtab: &impl Strtab,
+    buf: &'a [u8],
+) -> Option<DebugAltLink<'a>> {
+    let data = section_data_by_name(sht, shstrtab, buf, ".gnu_debugaltlink")?;
+    let (filename, name_end) = split_filename(data)?;
+    Some(DebugAltLink {
+        filename,
+        build_id: &data[name_end..],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{StrtabBuilder, StrtabView};
+    use accessor::Setter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn debuglink_with_padding() {
+        // "app.debug\0" 共 10 字节，CRC 位于对齐后的 12 处
+        let mut link = b"app.debug\0\0\0".to_vec();
+        link.extend_from_slice(&0xdead_beefu32.to_le_bytes());
+        let mut alt = b"common.debug\0".to_vec();
+        alt.extend_from_slice(&[0xab; 20]);
+        let data_off = 3 * 64;
+        let mut bytes = vec![0u8; data_off];
+        bytes.extend_from_slice(&link);
+        bytes.extend_from_slice(&alt);
+
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 64]));
+        let sht = table(&buf, 0..3 * 64, Shdr64);
+        sht[1]
+            .with::<ShName>(1)
+            .with::<ShOffset>(data_off as u64)
+            .with::<ShSize>(link.len() as u64);
+        sht[2]
+            .with::<ShName>(16)
+            .with::<ShOffset>((data_off + link.len()) as u64)
+            .with::<ShSize>(alt.len() as u64);
+        let shstrtab = StrtabView::new(b"\0.gnu_debuglink\0.gnu_debugaltlink\0");
+
+        let link = debuglink(&sht, &shstrtab, &bytes).unwrap();
+        assert_eq!(link.filename, "app.debug");
+        assert_eq!(link.crc, 0xdead_beef);
+        assert!(DebugLink {
+            crc: crc32(b"debug"),
+            ..link
+        }
+        .verify(b"debug"));
+
+        let alt = debugaltlink(&sht, &shstrtab, &bytes).unwrap();
+        assert_eq!(alt.filename, "common.debug");
+        assert_eq!(alt.build_id, &[0xab; 20][..]);
+
+        // 名称偏移超出 shstrtab 的 section 被跳过
+        sht[0].with::<ShName>(0x1000);
+        assert!(dwarf_sections(&sht, &shstrtab, &bytes).debug_info.is_none());
+        assert!(debuglink(&sht, &shstrtab, &bytes).is_some());
+    }
+
+    #[test]
+    fn locate_every_section() {
+        let names = [
+            ".debug_info",
+            ".debug_abbrev",
+            ".debug_str",
+            ".debug_line",
+            ".debug_line_str",
+            ".debug_ranges",
+            ".debug_aranges",
+            ".debug_frame",
+            ".debug_addr",
+            ".debug_info.dwo",
+            ".debug_abbrev.dwo",
+            ".debug_str.dwo",
+            ".debug_str_offsets.dwo",
+            ".text",
+        ];
+        let mut shstrtab = StrtabBuilder::new();
+        let buf = Rc::new(RefCell::new(vec![0u8; names.len() * 64]));
+        let sht = table(&buf, 0..names.len() * 64, Shdr64);
+        for (i, name) in names.iter().enumerate() {
+            sht[i]
+                .with::<ShName>(shstrtab.add(name))
+                .with::<ShOffset>(i as u64)
+                .with::<ShSize>(1);
+        }
+        // 第 i 个 section 的内容为一个字节 i
+        let bytes: Vec<u8> = (0..names.len() as u8).collect();
+        let data = |i: usize| Some(&bytes[i..i + 1]);
+
+        let secs = dwarf_sections(&sht, &shstrtab, &bytes);
+        assert_eq!(secs.debug_info, data(0));
+        assert_eq!(secs.debug_abbrev, data(1));
+        assert_eq!(secs.debug_str, data(2));
+        assert_eq!(secs.debug_line, data(3));
+        assert_eq!(secs.debug_line_str, data(4));
+        assert_eq!(secs.debug_ranges, data(5));
+        assert_eq!(secs.debug_aranges, data(6));
+        assert_eq!(secs.debug_frame, data(7));
+        assert_eq!(secs.debug_addr, data(8));
+
+        let dwo = dwo_sections(&sht, &shstrtab, &bytes);
+        assert!(!dwo.is_empty());
+        assert_eq!(dwo.debug_info, data(9));
+        assert_eq!(dwo.debug_abbrev, data(10));
+        assert_eq!(dwo.debug_str, data(11));
+        assert_eq!(dwo.debug_str_offsets, data(12));
+
+        // 只有主文件 section 时 .dwo 部分为空
+        let main_only = table(&buf, 0..9 * 64, Shdr64);
+        assert!(dwo_sections(&main_only, &shstrtab, &bytes).is_empty());
+    }
+}
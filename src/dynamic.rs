@@ -0,0 +1,307 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Dynamic section 相关的辅助函数
+
+use crate::codec::Codec;
+use crate::segments::vaddr_to_offset;
+use crate::util::clamped_slice;
+use crate::{Class, Dyn, DynamicTab, ParseLimits, Phdr, PhdrTab, SegmentType, Strtab};
+use accessor::Encode;
+
+pub const DT_NULL: i64 = 0;
+pub const DT_NEEDED: i64 = 1;
+pub const DT_HASH: i64 = 4;
+pub const DT_STRTAB: i64 = 5;
+pub const DT_SYMTAB: i64 = 6;
+pub const DT_SONAME: i64 = 14;
+pub const DT_RPATH: i64 = 15;
+pub const DT_RUNPATH: i64 = 29;
+pub const DT_FLAGS: i64 = 30;
+pub const DT_GNU_HASH: i64 = 0x6fff_fef5;
+
+elf_flags! {
+    /// DT_FLAGS 项的值
+    pub struct DynamicFlags: u64 {
+        const ORIGIN = 0x1;
+        const SYMBOLIC = 0x2;
+        const TEXTREL = 0x4;
+        const BIND_NOW = 0x8;
+        const STATIC_TLS = 0x10;
+    }
+}
+
+/// 与依赖库相关的 dynamic 项，字符串均已通过 .dynstr 解析
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DynamicSummary {
+    /// 按出现顺序排列的 DT_NEEDED
+    pub needed: Vec<String>,
+    pub soname: Option<String>,
+    pub rpath: Option<String>,
+    pub runpath: Option<String>,
+}
+
+/// 符号哈希表的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashKind {
+    /// DT_GNU_HASH，见 [`GnuHash`](crate::hash::GnuHash)
+    Gnu,
+    /// DT_HASH，见 [`SysvHash`](crate::hash::SysvHash)
+    Sysv,
+}
+
+/// 遍历 DT_NULL 之前的所有 (tag, val)
+fn entries<'a, T: Dyn + 'a>(
+    dyn_tab: &'a impl DynamicTab<T>,
+) -> impl Iterator<Item = (i64, u64)> + 'a {
+    dyn_tab
+        .iter()
+        .map(|d| (d.get::<T::Tag>(), d.get::<T::Val>()))
+        .take_while(|&(tag, _)| tag != DT_NULL)
+}
+
+/// 按顺序列出所有 DT_NEEDED 所指的库名，无法解析的偏移会被跳过。
+///
+/// 没有 dynamic section 的文件传入空表即可，此时返回空的 Vec。
+pub fn needed_libraries<T: Dyn>(dyn_tab: &impl DynamicTab<T>, dynstr: &impl Strtab) -> Vec<String> {
+    dynamic_summary(dyn_tab, dynstr).needed
+}
+
+/// 一次遍历收集 DT_NEEDED、DT_SONAME、DT_RPATH 与 DT_RUNPATH
+pub fn dynamic_summary<T: Dyn>(
+    dyn_tab: &impl DynamicTab<T>,
+    dynstr: &impl Strtab,
+) -> DynamicSummary {
+    summarize(entries(dyn_tab), dynstr)
+}
+
+fn summarize(entries: impl Iterator<Item = (i64, u64)>, dynstr: &impl Strtab) -> DynamicSummary {
+    let mut summary = DynamicSummary::default();
+    for (tag, val) in entries {
+        let slot = match tag {
+            DT_NEEDED => None,
+            DT_SONAME => Some(&mut summary.soname),
+            DT_RPATH => Some(&mut summary.rpath),
+            DT_RUNPATH => Some(&mut summary.runpath),
+            _ => continue,
+        };
+        // 只解析需要的 tag，其他项的 val 可能是地址或大小而不是字符串偏移
+        let name = match dynstr.get(val as usize) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        match slot {
+            Some(slot) => *slot = Some(name),
+            None => summary.needed.push(name),
+        }
+    }
+    summary
+}
+
+/// 通过 DT_GNU_HASH 或 DT_HASH 得到哈希表的格式与虚拟地址，两者都存在时优先使用 DT_GNU_HASH。
+///
+/// 适用于没有 Section Header 的内存镜像，文件中的位置见 [`hash_table_offset`]。
+pub fn hash_table_addr<T: Dyn>(dyn_tab: &impl DynamicTab<T>) -> Option<(HashKind, u64)> {
+    let mut sysv = None;
+    for (tag, val) in entries(dyn_tab) {
+        match tag {
+            DT_GNU_HASH => return Some((HashKind::Gnu, val)),
+            DT_HASH => sysv = Some((HashKind::Sysv, val)),
+            _ => {}
+        }
+    }
+    sysv
+}
+
+/// 与 [`hash_table_addr`] 相同，但通过 PT_LOAD segment 将虚拟地址转换为文件偏移
+pub fn hash_table_offset<T: Dyn, P: Phdr>(
+    dyn_tab: &impl DynamicTab<T>,
+    pht: &impl PhdrTab<P>,
+) -> Option<(HashKind, u64)> {
+    let (kind, vaddr) = hash_table_addr(dyn_tab)?;
+    Some((kind, vaddr_to_offset(pht, vaddr)?))
+}
+
+/// 直接引用 PT_DYNAMIC 内容的 dynamic 数组，用于没有 Section Header 的文件
+#[derive(Debug, Clone, Copy)]
+pub struct DynamicView<'a> {
+    data: &'a [u8],
+    codec: Codec,
+    limit: usize,
+}
+
+impl<'a> DynamicView<'a> {
+    pub fn new(data: &'a [u8], class: Class, encode: Encode) -> Self {
+        DynamicView {
+            data,
+            codec: Codec::new(class, encode),
+            limit: ParseLimits::default().max_dynamic,
+        }
+    }
+
+    /// 按 limits 中的 max_dynamic 限制遍历的项数。
+    ///
+    /// 遍历本身不返回错误，因此超出上限的项总是被截断，与 `truncate` 无关。
+    pub fn with_limits(mut self, limits: &ParseLimits) -> Self {
+        self.limit = limits.max_dynamic;
+        self
+    }
+
+    /// 遍历 DT_NULL 之前的所有 (tag, val)，遇到截断的项时停止
+    pub fn entries(&self) -> impl Iterator<Item = (i64, u64)> + 'a {
+        let (data, c) = (self.data, self.codec);
+        let entsize = 2 * c.class.word_size();
+        (0..(data.len() / entsize).min(self.limit))
+            .map_while(move |i| {
+                let mut r = c.reader(data, i * entsize);
+                let tag = match c.class {
+                    Class::Elf32 => r.word().ok()? as u32 as i32 as i64,
+                    Class::Elf64 => r.word().ok()? as i64,
+                };
+                Some((tag, r.word().ok()?))
+            })
+            .take_while(|&(tag, _)| tag != DT_NULL)
+    }
+
+    /// 与 [`needed_libraries`] 相同
+    pub fn needed_libraries(&self, dynstr: &impl Strtab) -> Vec<String> {
+        self.summary(dynstr).needed
+    }
+
+    /// 与 [`dynamic_summary`] 相同
+    pub fn summary(&self, dynstr: &impl Strtab) -> DynamicSummary {
+        summarize(self.entries(), dynstr)
+    }
+}
+
+/// 通过 PT_DYNAMIC 定位 dynamic 数组，没有 PT_DYNAMIC 或其范围超出 buf 时返回 None。
+///
+/// dynamic 项的大小取决于 Class，而 Program Header 本身不包含这一信息，因此需要显式传入。
+pub fn dynamic_from_phdrs<'a, T: Phdr>(
+    pht: &impl PhdrTab<T>,
+    buf: &'a [u8],
+    class: Class,
+    encode: Encode,
+) -> Option<DynamicView<'a>> {
+    let phdr = pht
+        .iter()
+        .find(|phdr| phdr.get::<T::Type>() == SegmentType::Dynamic)?;
+    Some(DynamicView::new(
+        clamped_slice(buf, &phdr.get::<T::SegRange>())?,
+        class,
+        encode,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+    use accessor::Setter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn hash_from_dynamic() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 4 * 16 + 56]));
+        let dyn_tab = table(&buf, 0..4 * 16, Dyn64);
+        dyn_tab[0].with::<DTag>(DT_HASH).with::<DVal>(0x40_0100);
+        dyn_tab[1].with::<DTag>(DT_GNU_HASH).with::<DVal>(0x40_0200);
+        let pht = table(&buf, 4 * 16..4 * 16 + 56, Phdr64);
+        pht[0]
+            .with::<PType>(SegmentType::Load)
+            .with::<PVaddr>(0x40_0000)
+            .with::<POffset>(0)
+            .with::<PFilesz>(0x1000);
+
+        assert_eq!(hash_table_addr(&dyn_tab), Some((HashKind::Gnu, 0x40_0200)));
+        assert_eq!(
+            hash_table_offset(&dyn_tab, &pht),
+            Some((HashKind::Gnu, 0x200))
+        );
+
+        dyn_tab[1].with::<DTag>(DT_NULL);
+        assert_eq!(
+            hash_table_offset(&dyn_tab, &pht),
+            Some((HashKind::Sysv, 0x100))
+        );
+        dyn_tab[0].with::<DVal>(0x50_0000);
+        assert_eq!(hash_table_offset(&dyn_tab, &pht), None);
+    }
+
+    #[test]
+    fn needed_from_pt_dynamic() {
+        let dynstr = crate::StrtabView::new(b"\0libc.so.6\0libm.so.6\0");
+        let mut bytes = vec![0u8; 56];
+        for (tag, val) in [
+            (DT_NEEDED, 1),
+            (DT_NEEDED, 11),
+            (DT_NULL, 0),
+            (DT_NEEDED, 1),
+        ]
+        .iter()
+        {
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&(*val as u64).to_le_bytes());
+        }
+        let buf = Rc::new(RefCell::new(bytes[..56].to_vec()));
+        let pht = table(&buf, 0..56, Phdr64);
+        pht[0]
+            .with::<PType>(SegmentType::Dynamic)
+            .with::<POffset>(56)
+            .with::<PFilesz>(4 * 16);
+
+        let dynamic = dynamic_from_phdrs(&pht, &bytes, Class::Elf64, Encode::Le).unwrap();
+        assert_eq!(dynamic.entries().count(), 2);
+        assert_eq!(
+            dynamic.needed_libraries(&dynstr),
+            ["libc.so.6", "libm.so.6"]
+        );
+
+        pht[0].with::<PType>(SegmentType::Load);
+        assert!(dynamic_from_phdrs(&pht, &bytes, Class::Elf64, Encode::Le).is_none());
+    }
+
+    #[test]
+    fn summary_from_dynamic() {
+        let dynstr = crate::StrtabView::new(b"\0libc.so.6\0libfoo.so.1\0/opt/lib\0$ORIGIN\0");
+        let buf = Rc::new(RefCell::new(vec![0u8; 7 * 16]));
+        let dyn_tab = table(&buf, 0..7 * 16, Dyn64);
+        let items = [
+            (DT_NEEDED, 1),
+            (DT_HASH, 0x40_0100),
+            (DT_SONAME, 11),
+            (DT_RPATH, 23),
+            (DT_RUNPATH, 32),
+            (DT_NEEDED, 0x1000),
+        ];
+        for (i, &(tag, val)) in items.iter().enumerate() {
+            dyn_tab[i].with::<DTag>(tag).with::<DVal>(val);
+        }
+
+        let summary = dynamic_summary(&dyn_tab, &dynstr);
+        assert_eq!(summary.needed, ["libc.so.6"]);
+        assert_eq!(summary.soname.as_deref(), Some("libfoo.so.1"));
+        assert_eq!(summary.rpath.as_deref(), Some("/opt/lib"));
+        assert_eq!(summary.runpath.as_deref(), Some("$ORIGIN"));
+
+        // 没有 dynamic section 时传入空表
+        let empty = table(&buf, 0..0, Dyn64);
+        assert_eq!(dynamic_summary(&empty, &dynstr), DynamicSummary::default());
+        assert!(needed_libraries(&empty, &dynstr).is_empty());
+    }
+}
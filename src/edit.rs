@@ -0,0 +1,267 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 对 [`OwnedElf`] 的增量修改，修改后文件布局仍然有效。
+
+use crate::header::SHN_LORESERVE;
+use crate::owned::{OwnedElf, OwnedShdr};
+use crate::sections::SectionFlags;
+use crate::util::{align_up, clamped_slice, field_align_up};
+use crate::{shift, ElfError, SectionType, StrtabBuilder};
+
+impl OwnedElf {
+    /// 与 [`shift_all_after`](crate::shift_all_after) 相同，但同时作用于 section、segment、
+    /// e_phoff 与 e_shoff：所有偏移不小于 threshold 的位置都加上 delta。
+    ///
+    /// 溢出时返回 `ElfError::Overflow`，此时不做任何修改。
+    pub fn shift_all_after(&mut self, threshold: usize, delta: i64) -> Result<(), ElfError> {
+        let threshold = threshold as u64;
+        let bump = |off: u64| {
+            if off >= threshold {
+                shift(off, delta)
+            } else {
+                Ok(off)
+            }
+        };
+        let e_phoff = bump(self.ehdr.e_phoff)?;
+        let e_shoff = bump(self.ehdr.e_shoff)?;
+        let sh_offsets = self
+            .shdrs
+            .iter()
+            .map(|shdr| bump(shdr.sh_offset))
+            .collect::<Result<Vec<_>, _>>()?;
+        let p_offsets = self
+            .phdrs
+            .iter()
+            .map(|phdr| bump(phdr.p_offset))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.ehdr.e_phoff = e_phoff;
+        self.ehdr.e_shoff = e_shoff;
+        for (shdr, off) in self.shdrs.iter_mut().zip(sh_offsets) {
+            shdr.sh_offset = off;
+        }
+        for (phdr, off) in self.phdrs.iter_mut().zip(p_offsets) {
+            phdr.p_offset = off;
+        }
+        Ok(())
+    }
+
+    /// 将 s 加入第 section_idx 个字符串表，返回其偏移，即新的 st_name/sh_name 值。
+    ///
+    /// 字符串已存在时直接返回原偏移，不做修改；否则在字符串表末尾追加，
+    /// 并通过 [`OwnedElf::shift_all_after`] 移动其后的所有数据。移动量会向上对齐到其后各 section
+    /// 与 segment 的最大对齐，以保持它们的对齐不变。
+    ///
+    /// 成功追加后，之前得到的所有范围（如 [`OwnedElf::section_data`] 的结果、`file_range`）都会失效。
+    /// 字符串表位于某个 segment 中时（如可执行文件的 `.dynstr`），追加会破坏虚拟地址布局，
+    /// 返回 `ElfError::Unsupported`；其后的对齐值不是 2 的幂或过大时返回 `ElfError::Malformed`。
+    pub fn add_string(&mut self, section_idx: usize, s: &str) -> Result<usize, ElfError> {
+        self.require_sections()?;
+        let shdr = *self
+            .shdrs
+            .get(section_idx)
+            .ok_or(ElfError::OutOfBounds(section_idx..section_idx + 1))?;
+        if shdr.sh_type != SectionType::Strtab.raw() {
+            return Err(ElfError::Unsupported("add_string on non-strtab section"));
+        }
+        let range = shdr.file_range();
+        let old = clamped_slice(&self.data, &range)
+            .ok_or_else(|| ElfError::OutOfBounds(range.clone()))?;
+        let old_len = old.len();
+        let mut builder = StrtabBuilder::from_bytes(old);
+        let offset = builder.add(s);
+        let bytes = builder.into_bytes();
+        if bytes.len() == old_len {
+            return Ok(offset);
+        }
+        if self
+            .phdrs
+            .iter()
+            .any(|phdr| phdr.file_range().start < range.end && range.end <= phdr.file_range().end)
+        {
+            return Err(ElfError::Unsupported(
+                "add_string on strtab inside a segment",
+            ));
+        }
+
+        let added = bytes.len() - old_len;
+        let align = self
+            .shdrs
+            .iter()
+            .filter(|other| other.sh_offset >= range.end as u64)
+            .map(|other| other.sh_addralign)
+            .chain(
+                self.phdrs
+                    .iter()
+                    .filter(|phdr| phdr.p_offset >= range.end as u64)
+                    .map(|phdr| phdr.p_align),
+            )
+            .max()
+            .unwrap_or(1)
+            .max(self.class()?.word_size() as u64);
+        let delta = field_align_up(added, align)?;
+        self.shift_all_after(range.end, delta as i64)?;
+
+        let mut insert = bytes[old_len..].to_vec();
+        insert.resize(delta, 0);
+        let tail = self.data.split_off(range.end.min(self.data.len()));
+        self.data.extend_from_slice(&insert);
+        self.data.extend_from_slice(&tail);
+        // 空的字符串表的起始位置等于 threshold，也会被移动
+        self.shdrs[section_idx].sh_offset = shdr.sh_offset;
+        self.shdrs[section_idx].sh_size += added as u64;
+        Ok(offset)
+    }
+
+    /// 在文件末尾追加一个 section，返回其索引。
+    ///
+    /// 名称通过 [`OwnedElf::add_string`] 加入 e_shstrndx 所指的字符串表，数据追加在文件末尾，
+    /// Section Header Table 移动到新数据之后并修正 e_shoff 与 e_shnum。
+    /// 结果通过 [`OwnedElf::to_bytes`] 得到；与 `add_string` 相同，之前得到的范围都会失效。
+    /// SHT_NOBITS 没有文件中的数据，不能通过这种方式追加，返回 `ElfError::Unsupported`。
+    pub fn append_section(
+        &mut self,
+        name: &str,
+        sh_type: SectionType,
+        flags: SectionFlags,
+        data: &[u8],
+    ) -> Result<usize, ElfError> {
+        self.require_sections()?;
+        if sh_type == SectionType::Nobits {
+            return Err(ElfError::Unsupported("append_section with SHT_NOBITS"));
+        }
+        if self.shdrs.len() >= SHN_LORESERVE as usize {
+            return Err(ElfError::Unsupported("append_section beyond SHN_LORESERVE"));
+        }
+        let sh_name = self.add_string(self.shstrndx(), name)?;
+
+        // 原来的 Section Header Table 位于文件末尾时直接覆盖，否则保留原处的字节
+        let class = self.class()?;
+        let word = class.word_size();
+        let sht_start = self.ehdr.e_shoff as usize;
+        if !self.shdrs.is_empty()
+            && sht_start + self.shdrs.len() * class.shdr_size() >= self.data.len()
+        {
+            self.data.truncate(sht_start);
+        }
+        let sh_offset = self.data.len();
+        self.data.extend_from_slice(data);
+        let sht_start = align_up(self.data.len(), word);
+        self.data.resize(sht_start, 0);
+
+        self.shdrs.push(OwnedShdr {
+            sh_name: sh_name as u32,
+            sh_type: sh_type.raw(),
+            sh_flags: flags.bits(),
+            sh_offset: sh_offset as u64,
+            sh_size: data.len() as u64,
+            sh_addralign: 1,
+            ..Default::default()
+        });
+        self.ehdr.e_shoff = sht_start as u64;
+        self.ehdr.e_shnum = self.shdrs.len() as u16;
+        if self.ehdr.e_shentsize == 0 {
+            self.ehdr.e_shentsize = class.shdr_size() as u16;
+        }
+        Ok(self.shdrs.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::owned::*;
+    use crate::test_util::elf64_ident;
+
+    /// .strtab 同时作为 section 名称字符串表的 ELF64 文件
+    fn sample() -> OwnedElf {
+        let mut data = vec![0u8; 0x50];
+        data[0x40..0x45].copy_from_slice(b"\0foo\0");
+        data[0x48..0x4c].copy_from_slice(&[1, 2, 3, 4]);
+        let shdr = |sh_type, off, size, align| OwnedShdr {
+            sh_type,
+            sh_offset: off,
+            sh_size: size,
+            sh_addralign: align,
+            ..Default::default()
+        };
+        let bytes = OwnedElf {
+            ehdr: OwnedEhdr {
+                ident: elf64_ident(),
+                e_shoff: 0x50,
+                e_shnum: 3,
+                e_shstrndx: 1,
+                ..Default::default()
+            },
+            phdrs: Vec::new(),
+            shdrs: vec![
+                OwnedShdr::default(),
+                shdr(3, 0x40, 5, 1),
+                shdr(1, 0x48, 4, 4),
+            ],
+            data,
+        }
+        .to_bytes()
+        .unwrap();
+        OwnedElf::parse(&bytes).unwrap()
+    }
+
+    #[test]
+    fn add_symbol_name() {
+        let mut elf = sample();
+
+        assert_eq!(elf.add_string(1, "foo"), Ok(1));
+        assert_eq!(elf.add_string(1, "bar"), Ok(5));
+        assert_eq!(elf.shdrs[1].sh_size, 9);
+        assert_eq!(elf.shdrs[2].sh_offset, 0x50);
+        assert_eq!(elf.ehdr.e_shoff, 0x58);
+
+        let elf = OwnedElf::parse(&elf.to_bytes().unwrap()).unwrap();
+        assert_eq!(elf.section_data(1), Ok(&b"\0foo\0bar\0"[..]));
+        assert_eq!(elf.section_data(2), Ok(&[1, 2, 3, 4][..]));
+        assert!(OwnedElf::parse(&elf.to_bytes().unwrap()).is_ok());
+
+        // 之后的 section 的对齐值无效时不做任何修改
+        let mut elf = sample();
+        elf.shdrs[2].sh_addralign = 24;
+        let before = elf.clone();
+        assert!(matches!(
+            elf.add_string(1, "bar"),
+            Err(ElfError::Malformed(_))
+        ));
+        assert_eq!(elf.to_bytes().unwrap(), before.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn append_and_reparse() {
+        let mut elf = sample();
+        let flags = SectionFlags::ALLOC;
+        let idx = elf
+            .append_section(".sig", SectionType::Progbits, flags, b"signature")
+            .unwrap();
+        assert_eq!(idx, 3);
+
+        let elf = OwnedElf::parse(&elf.to_bytes().unwrap()).unwrap();
+        assert_eq!(elf.ehdr.e_shnum, 4);
+        assert_eq!(elf.ehdr.e_shoff % 8, 0);
+        assert_eq!(elf.section_name(3), Ok(".sig"));
+        assert_eq!(elf.section_data(3), Ok(&b"signature"[..]));
+        assert_eq!(elf.shdrs[3].sh_flags, SectionFlags::ALLOC.bits());
+        assert_eq!(elf.section_name(1), Ok(""));
+        assert_eq!(elf.section_data(2), Ok(&[1, 2, 3, 4][..]));
+    }
+}
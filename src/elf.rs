@@ -0,0 +1,633 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 不持有文件数据的 Elf 容器，只保存解码后的 header 与两张 header table。
+
+use crate::codec::Codec;
+use crate::header::{PN_XNUM, SHN_XINDEX};
+use crate::machine::class_matches_machine;
+use crate::owned::{OwnedEhdr, OwnedElf, OwnedPhdr, OwnedShdr};
+use crate::{Class, ElfError, Machine, ObjectType};
+use accessor::Encode;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+/// 解析时各类表项数量的上限，避免损坏或恶意构造的文件声明过多表项而耗费大量时间与内存。
+///
+/// 声明的数量超过上限时，`truncate` 为 false 则返回 `ElfError::LimitExceeded`，
+/// 为 true 则只处理前 limit 项。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    pub max_sections: usize,
+    pub max_segments: usize,
+    pub max_symbols: usize,
+    pub max_dynamic: usize,
+    pub truncate: bool,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_sections: 1 << 20,
+            max_segments: 1 << 16,
+            max_symbols: 1 << 24,
+            max_dynamic: 1 << 20,
+            truncate: false,
+        }
+    }
+}
+
+impl ParseLimits {
+    /// 声明了 count 项、上限为 limit 时实际应处理的数量
+    pub fn cap(&self, what: &'static str, count: usize, limit: usize) -> Result<usize, ElfError> {
+        match count <= limit {
+            true => Ok(count),
+            false if self.truncate => Ok(limit),
+            false => Err(ElfError::LimitExceeded { what, count, limit }),
+        }
+    }
+}
+
+/// 解析后的 Elf header 与 header table，数据本身仍由调用者持有。
+///
+/// 修改缓冲区后可调用 [`Elf::reparse_tables`] 刷新，各部分的失效粒度为：
+/// - Elf Header（包括 ident）总是重新读取；
+/// - Program Header Table 仅在 e_phoff、e_phentsize、e_phnum 或 Class/Encode 变化时重新读取；
+/// - Section Header Table 仅在 e_shoff、e_shentsize、e_shnum 或 Class/Encode 变化时重新读取。
+///
+/// 只修改了表项本身而没有修改 Elf Header 时，表不会被刷新，此时应重新调用 [`Elf::parse`]。
+/// 刷新时沿用解析时的 [`ParseLimits`]。
+/// section 与 segment 的数据不属于容器的状态，不受影响。
+#[derive(Debug, Clone)]
+pub struct Elf {
+    ehdr: OwnedEhdr,
+    codec: Codec,
+    limits: ParseLimits,
+    counts: HeaderCounts,
+    pht_range: Range<usize>,
+    sht_range: Range<usize>,
+    phdrs: Vec<OwnedPhdr>,
+    shdrs: Vec<OwnedShdr>,
+}
+
+/// 解码扩展编号后的 e_phnum、e_shnum 与 e_shstrndx
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct HeaderCounts {
+    pub phnum: usize,
+    pub shnum: usize,
+    pub shstrndx: usize,
+}
+
+impl HeaderCounts {
+    /// 不解码扩展编号，直接取 Elf Header 中的值
+    pub(crate) fn raw(ehdr: &OwnedEhdr) -> Self {
+        HeaderCounts {
+            phnum: ehdr.e_phnum as usize,
+            shnum: ehdr.e_shnum as usize,
+            shstrndx: ehdr.e_shstrndx as usize,
+        }
+    }
+
+    /// e_shoff 不为 0，且 e_shnum 为 0、e_phnum 为 PN_XNUM 或 e_shstrndx 为 SHN_XINDEX，
+    /// 即需要 0 号 section header 才能得到实际的值
+    pub(crate) fn extended(ehdr: &OwnedEhdr) -> bool {
+        ehdr.e_shoff != 0
+            && (ehdr.e_shnum == 0 || ehdr.e_phnum == PN_XNUM || ehdr.e_shstrndx == SHN_XINDEX)
+    }
+
+    /// 使用扩展编号的值分别取自 0 号 section header 的 sh_size、sh_info 与 sh_link
+    pub(crate) fn resolve(ehdr: &OwnedEhdr, first: &OwnedShdr) -> Result<Self, ElfError> {
+        let mut counts = Self::raw(ehdr);
+        if ehdr.e_shnum == 0 {
+            counts.shnum = usize::try_from(first.sh_size).map_err(|_| ElfError::Overflow)?;
+        }
+        if ehdr.e_phnum == PN_XNUM {
+            counts.phnum = first.sh_info as usize;
+        }
+        if ehdr.e_shstrndx == SHN_XINDEX {
+            counts.shstrndx = first.sh_link as usize;
+        }
+        Ok(counts)
+    }
+
+    /// 从 buf 中读取 Elf Header 声明的值，见 [`HeaderCounts::extended`]。
+    ///
+    /// 需要读取 0 号 section header 而其超出 buf 时返回 `ElfError::OutOfBounds`
+    pub(crate) fn read(c: Codec, ehdr: &OwnedEhdr, buf: &[u8]) -> Result<Self, ElfError> {
+        if !Self::extended(ehdr) {
+            return Ok(Self::raw(ehdr));
+        }
+        let off = usize::try_from(ehdr.e_shoff).map_err(|_| ElfError::Overflow)?;
+        Self::resolve(ehdr, &OwnedShdr::parse(c, buf, off)?)
+    }
+}
+
+/// 起始于 off、共 num 项、每项 entsize 字节（为 0 时取 default）的表的范围
+pub(crate) fn table_range(
+    off: u64,
+    entsize: u16,
+    num: usize,
+    default: usize,
+) -> Result<Range<usize>, ElfError> {
+    let entsize = match entsize {
+        0 => default,
+        size => size as usize,
+    };
+    // 32 位平台上直接转换会截断过大的偏移
+    let start = usize::try_from(off).map_err(|_| ElfError::Overflow)?;
+    let end = num
+        .checked_mul(entsize)
+        .and_then(|len| start.checked_add(len))
+        .ok_or(ElfError::Overflow)?;
+    Ok(start..end)
+}
+
+/// 从 range 所指、声明了 declared 项的表中读取前 num 项，
+/// 先检查这些表项都在 buf 中，避免对不存在的表项逐个尝试
+pub(crate) fn read_table<T>(
+    buf: &[u8],
+    range: &Range<usize>,
+    declared: usize,
+    num: usize,
+    parse: impl Fn(usize) -> Result<T, ElfError>,
+) -> Result<Vec<T>, ElfError> {
+    let entsize = range.len().checked_div(declared).unwrap_or(0);
+    let end = range.start + num * entsize;
+    if num > 0 && end > buf.len() {
+        return Err(ElfError::OutOfBounds(range.start..end));
+    }
+    (0..num).map(|i| parse(range.start + i * entsize)).collect()
+}
+
+/// ehdr 声明的、共 phnum 项的 Program Header Table 的范围
+pub(crate) fn pht_range(
+    c: Codec,
+    ehdr: &OwnedEhdr,
+    phnum: usize,
+) -> Result<Range<usize>, ElfError> {
+    table_range(ehdr.e_phoff, ehdr.e_phentsize, phnum, c.class.phdr_size())
+}
+
+/// ehdr 声明的、共 shnum 项的 Section Header Table 的范围
+pub(crate) fn sht_range(
+    c: Codec,
+    ehdr: &OwnedEhdr,
+    shnum: usize,
+) -> Result<Range<usize>, ElfError> {
+    table_range(ehdr.e_shoff, ehdr.e_shentsize, shnum, c.class.shdr_size())
+}
+
+/// 读取 ehdr 声明的 program header，数量按 limits 限制
+pub(crate) fn read_phdrs(
+    c: Codec,
+    ehdr: &OwnedEhdr,
+    counts: &HeaderCounts,
+    buf: &[u8],
+    limits: &ParseLimits,
+) -> Result<Vec<OwnedPhdr>, ElfError> {
+    let range = pht_range(c, ehdr, counts.phnum)?;
+    let num = limits.cap("segments", counts.phnum, limits.max_segments)?;
+    read_table(buf, &range, counts.phnum, num, |off| {
+        OwnedPhdr::parse(c, buf, off)
+    })
+}
+
+/// 读取 ehdr 声明的 section header，数量按 limits 限制
+pub(crate) fn read_shdrs(
+    c: Codec,
+    ehdr: &OwnedEhdr,
+    counts: &HeaderCounts,
+    buf: &[u8],
+    limits: &ParseLimits,
+) -> Result<Vec<OwnedShdr>, ElfError> {
+    let range = sht_range(c, ehdr, counts.shnum)?;
+    let num = limits.cap("sections", counts.shnum, limits.max_sections)?;
+    read_table(buf, &range, counts.shnum, num, |off| {
+        OwnedShdr::parse(c, buf, off)
+    })
+}
+
+impl Elf {
+    /// 以默认的 [`ParseLimits`] 解析
+    pub fn parse(buf: &[u8]) -> Result<Self, ElfError> {
+        Self::parse_with_limits(buf, &ParseLimits::default())
+    }
+
+    /// 解析 buf，program header 与 section header 的数量按 limits 限制。
+    ///
+    /// 数量或 e_shstrndx 使用扩展编号时从 0 号 section header 中读取实际的值。
+    pub fn parse_with_limits(buf: &[u8], limits: &ParseLimits) -> Result<Self, ElfError> {
+        let codec = OwnedEhdr::codec(buf)?;
+        let ehdr = OwnedEhdr::parse(codec, buf)?;
+        let counts = HeaderCounts::read(codec, &ehdr, buf)?;
+        Ok(Elf {
+            ehdr,
+            codec,
+            limits: *limits,
+            counts,
+            pht_range: pht_range(codec, &ehdr, counts.phnum)?,
+            sht_range: sht_range(codec, &ehdr, counts.shnum)?,
+            phdrs: read_phdrs(codec, &ehdr, &counts, buf, limits)?,
+            shdrs: read_shdrs(codec, &ehdr, &counts, buf, limits)?,
+        })
+    }
+
+    /// 重新读取 Elf Header，并只刷新范围发生变化的 header table，失效粒度见 [`Elf`]。
+    ///
+    /// 出错时容器保持原状。
+    pub fn reparse_tables(&mut self, buf: &[u8]) -> Result<(), ElfError> {
+        let codec = OwnedEhdr::codec(buf)?;
+        let ehdr = OwnedEhdr::parse(codec, buf)?;
+        let counts = HeaderCounts::read(codec, &ehdr, buf)?;
+        let mut next = Elf {
+            ehdr,
+            codec,
+            limits: self.limits,
+            counts,
+            pht_range: pht_range(codec, &ehdr, counts.phnum)?,
+            sht_range: sht_range(codec, &ehdr, counts.shnum)?,
+            phdrs: Vec::new(),
+            shdrs: Vec::new(),
+        };
+        // 比较 header 字段而不是字节范围：56×2 与 112×1 的范围相同，但表项不同
+        let (old, new) = (&self.ehdr, &next.ehdr);
+        let codec_changed = codec != self.codec;
+        let phdrs = if codec_changed
+            || (old.e_phoff, old.e_phentsize, self.counts.phnum)
+                != (new.e_phoff, new.e_phentsize, counts.phnum)
+        {
+            Some(read_phdrs(codec, new, &counts, buf, &next.limits)?)
+        } else {
+            None
+        };
+        let shdrs = if codec_changed
+            || (old.e_shoff, old.e_shentsize, self.counts.shnum)
+                != (new.e_shoff, new.e_shentsize, counts.shnum)
+        {
+            Some(read_shdrs(codec, new, &counts, buf, &next.limits)?)
+        } else {
+            None
+        };
+        next.phdrs = phdrs.unwrap_or_else(|| std::mem::take(&mut self.phdrs));
+        next.shdrs = shdrs.unwrap_or_else(|| std::mem::take(&mut self.shdrs));
+        *self = next;
+        Ok(())
+    }
+
+    pub fn ehdr(&self) -> &OwnedEhdr {
+        &self.ehdr
+    }
+    pub fn class(&self) -> Class {
+        self.codec.class
+    }
+    pub fn encode(&self) -> Encode {
+        self.codec.encode
+    }
+    /// section 字符串表的索引，e_shstrndx 为 SHN_XINDEX 时为 0 号 section 的 sh_link
+    pub fn shstrndx(&self) -> usize {
+        self.counts.shstrndx
+    }
+    /// Program Header Table 在文件中的范围
+    pub fn pht_range(&self) -> Range<usize> {
+        self.pht_range.clone()
+    }
+    /// Section Header Table 在文件中的范围
+    pub fn sht_range(&self) -> Range<usize> {
+        self.sht_range.clone()
+    }
+    pub fn phdrs(&self) -> &[OwnedPhdr] {
+        &self.phdrs
+    }
+    pub fn shdrs(&self) -> &[OwnedShdr] {
+        &self.shdrs
+    }
+}
+
+/// [`parse_lenient`] 的结果，只包含缓冲区中完整存在的部分，不应当作完整的解析结果使用。
+#[derive(Debug, Clone, Default)]
+pub struct PartialElf {
+    /// Elf Header 不完整或 ident 无效时为 None，此时其余部分均为空
+    pub ehdr: Option<OwnedEhdr>,
+    /// 完整存在的 program header，为 Program Header Table 的前缀
+    pub phdrs: Vec<OwnedPhdr>,
+    /// 完整存在的 section header，为 Section Header Table 的前缀
+    pub shdrs: Vec<OwnedShdr>,
+    /// 超出缓冲区而被丢弃的 program header 数量
+    pub missing_phdrs: usize,
+    /// 超出缓冲区而被丢弃的 section header 数量
+    pub missing_shdrs: usize,
+    /// header 完整存在、但数据超出缓冲区的 section 在 `shdrs` 中的索引
+    pub truncated_sections: Vec<usize>,
+}
+
+impl PartialElf {
+    /// 所有 header 与 section 数据是否都完整存在
+    pub fn is_complete(&self) -> bool {
+        self.ehdr.is_some()
+            && self.missing_phdrs == 0
+            && self.missing_shdrs == 0
+            && self.truncated_sections.is_empty()
+    }
+}
+
+/// 按表项依次解析，遇到第一个超出 buf 的表项时停止，返回已解析的表项与被丢弃的数量
+fn parse_prefix<T>(
+    num: usize,
+    range: Result<Range<usize>, ElfError>,
+    parse: impl Fn(usize) -> Result<T, ElfError>,
+) -> (Vec<T>, usize) {
+    let range = match range {
+        Ok(range) => range,
+        Err(_) => return (Vec::new(), num),
+    };
+    let entsize = range.len().checked_div(num).unwrap_or(0);
+    let entries: Vec<T> = (0..num)
+        .map_while(|i| parse(range.start + i * entsize).ok())
+        .collect();
+    let missing = num - entries.len();
+    (entries, missing)
+}
+
+/// 宽松地解析可能被截断的文件（如崩溃现场或抓包中只有前若干字节的文件）。
+///
+/// 与返回错误不同，超出 buf 的表项会被丢弃并计数，结果中记录了缺失的内容，
+/// 调用者应通过 [`PartialElf::is_complete`] 判断是否得到了完整的文件。
+pub fn parse_lenient(bytes: &[u8]) -> PartialElf {
+    let codec = match OwnedEhdr::codec(bytes) {
+        Ok(codec) => codec,
+        Err(_) => return PartialElf::default(),
+    };
+    let ehdr = match OwnedEhdr::parse(codec, bytes) {
+        Ok(ehdr) => ehdr,
+        Err(_) => return PartialElf::default(),
+    };
+    // 0 号 section header 被截断时无法解码扩展编号，只能按 Elf Header 中的值处理
+    let counts =
+        HeaderCounts::read(codec, &ehdr, bytes).unwrap_or_else(|_| HeaderCounts::raw(&ehdr));
+    let (phdrs, missing_phdrs) =
+        parse_prefix(counts.phnum, pht_range(codec, &ehdr, counts.phnum), |off| {
+            OwnedPhdr::parse(codec, bytes, off)
+        });
+    let (shdrs, missing_shdrs) =
+        parse_prefix(counts.shnum, sht_range(codec, &ehdr, counts.shnum), |off| {
+            OwnedShdr::parse(codec, bytes, off)
+        });
+    let truncated_sections = shdrs
+        .iter()
+        .enumerate()
+        .filter(|(_, shdr)| shdr.file_range().end > bytes.len())
+        .map(|(idx, _)| idx)
+        .collect();
+    PartialElf {
+        ehdr: Some(ehdr),
+        phdrs,
+        shdrs,
+        missing_phdrs,
+        missing_shdrs,
+        truncated_sections,
+    }
+}
+
+/// [`parse_best_effort`] 遇到的异常，对应的字段保持原值，其余部分照常解析
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// e_machine 为 EM_NONE 或未知的值
+    UnknownMachine(u16),
+    /// e_type 为 ET_NONE 或 [`ObjectType::is_known`] 之外的值
+    UnknownType(u16),
+    /// EI_VERSION 或 e_version 不是 EV_CURRENT
+    UnknownVersion(u32),
+    /// EI_CLASS 与 e_machine 的指针宽度不一致，见 [`class_matches_machine`](crate::machine::class_matches_machine)
+    ClassMachineMismatch,
+    /// Program Header Table 无法读取，结果中没有 program header
+    BadProgramHeaders(ElfError),
+    /// Section Header Table 无法读取，结果中没有 section header
+    BadSectionHeaders(ElfError),
+    /// section 字符串表的索引（解码扩展编号后）超出 Section Header Table，section 名称不可用
+    BadShstrndx(usize),
+}
+
+/// 解析文件并报告 Elf Header 中的未知值（如构建中途的目标文件的 e_machine 为 EM_NONE）。
+///
+/// [`Elf::parse`] 与 [`OwnedElf::parse`] 不检查 e_machine、e_type 与版本号，
+/// 但 header table 无法读取时会返回错误。这里只有 ident 与 Elf Header 本身无法读取时返回错误；
+/// 上述字段的未知值以及无法读取的 header table 都被记录为 [`ParseWarning`]，结果仍可用于查看 section 与符号。
+/// header table 按 limits 限制，与 [`OwnedElf::parse_with_limits`] 相同。
+/// 与 [`parse_lenient`] 处理截断的文件不同，这里容忍的是字段的取值；两者都不修改文件。
+pub fn parse_best_effort(
+    bytes: &[u8],
+    limits: &ParseLimits,
+) -> Result<(OwnedElf, Vec<ParseWarning>), ElfError> {
+    let c = OwnedEhdr::codec(bytes)?;
+    let ehdr = OwnedEhdr::parse(c, bytes)?;
+    let mut warnings = Vec::new();
+    let machine = Machine::from(ehdr.e_machine);
+    if matches!(machine, Machine::None | Machine::Other(_)) {
+        warnings.push(ParseWarning::UnknownMachine(ehdr.e_machine));
+    } else if !class_matches_machine(c.class, machine) {
+        warnings.push(ParseWarning::ClassMachineMismatch);
+    }
+    match ObjectType::from(ehdr.e_type) {
+        ObjectType::None => warnings.push(ParseWarning::UnknownType(ehdr.e_type)),
+        ty if !ty.is_known() => warnings.push(ParseWarning::UnknownType(ehdr.e_type)),
+        _ => {}
+    }
+    for version in [ehdr.ident[6] as u32, ehdr.e_version].iter() {
+        if *version != 1 {
+            warnings.push(ParseWarning::UnknownVersion(*version));
+        }
+    }
+
+    let counts = HeaderCounts::read(c, &ehdr, bytes).unwrap_or_else(|err| {
+        warnings.push(ParseWarning::BadSectionHeaders(err));
+        HeaderCounts::raw(&ehdr)
+    });
+    let phdrs = read_phdrs(c, &ehdr, &counts, bytes, limits).unwrap_or_else(|err| {
+        warnings.push(ParseWarning::BadProgramHeaders(err));
+        Vec::new()
+    });
+    let shdrs = read_shdrs(c, &ehdr, &counts, bytes, limits).unwrap_or_else(|err| {
+        warnings.push(ParseWarning::BadSectionHeaders(err));
+        Vec::new()
+    });
+    if !shdrs.is_empty() && counts.shstrndx >= shdrs.len() {
+        warnings.push(ParseWarning::BadShstrndx(counts.shstrndx));
+    }
+    let elf = OwnedElf {
+        ehdr,
+        phdrs,
+        shdrs,
+        data: bytes.to_vec(),
+    };
+    Ok((elf, warnings))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::sample_elf64;
+
+    #[test]
+    fn reparse_after_header_edit() {
+        let mut buf = sample_elf64();
+        let mut elf = Elf::parse(&buf).unwrap();
+        assert_eq!(elf.sht_range(), 0x100..0x100 + 3 * 64);
+        assert_eq!(elf.shdrs().len(), 3);
+
+        // e_shnum 改为 2，sht 随之刷新，pht 保持不变
+        buf[60] = 2;
+        elf.reparse_tables(&buf).unwrap();
+        assert_eq!(elf.sht_range(), 0x100..0x100 + 2 * 64);
+        assert_eq!(elf.shdrs().len(), 2);
+        assert_eq!(elf.phdrs()[0].p_type, 1);
+
+        // 只修改表项而不修改 header 时不会刷新
+        buf[64] = 2;
+        elf.reparse_tables(&buf).unwrap();
+        assert_eq!(elf.phdrs()[0].p_type, 1);
+
+        // 出错时保持原状
+        buf[60] = 0xff;
+        assert!(elf.reparse_tables(&buf).is_err());
+        assert_eq!(elf.shdrs().len(), 2);
+        assert_eq!(elf.phdrs().len(), 1);
+    }
+
+    #[test]
+    fn reparse_same_range_new_entsize() {
+        let mut buf = sample_elf64();
+        buf[54..58].copy_from_slice(&[56, 0, 2, 0]);
+        let mut elf = Elf::parse(&buf).unwrap();
+        assert_eq!(elf.phdrs().len(), 2);
+
+        // 112×1 与 56×2 的范围相同，但表项数不同
+        buf[54..58].copy_from_slice(&[112, 0, 1, 0]);
+        elf.reparse_tables(&buf).unwrap();
+        assert_eq!(elf.pht_range(), 64..64 + 112);
+        assert_eq!(elf.phdrs().len(), 1);
+        assert_eq!(elf.shdrs().len(), 3);
+    }
+
+    #[test]
+    fn lenient_on_truncated() {
+        let mut buf = sample_elf64();
+        // section 2 的数据紧跟在 Section Header Table 之后
+        let end = buf.len();
+        buf[0x100 + 2 * 64 + 24..][..2].copy_from_slice(&(end as u16).to_le_bytes());
+        buf[0x100 + 2 * 64 + 32] = 0x10;
+        buf.resize(end + 0x10, 0);
+        assert!(parse_lenient(&buf).is_complete());
+
+        let partial = parse_lenient(&buf[..end + 8]);
+        assert!(!partial.is_complete());
+        assert_eq!(partial.shdrs.len(), 3);
+        assert_eq!(partial.truncated_sections, [2]);
+
+        let partial = parse_lenient(&buf[..0x100 + 2 * 64 + 10]);
+        assert_eq!(partial.phdrs.len(), 1);
+        assert_eq!(partial.missing_phdrs, 0);
+        assert_eq!(partial.shdrs.len(), 2);
+        assert_eq!(partial.missing_shdrs, 1);
+        assert!(partial.truncated_sections.is_empty());
+        assert!(Elf::parse(&buf[..0x100 + 2 * 64 + 10]).is_err());
+
+        assert!(parse_lenient(&buf[..40]).ehdr.is_none());
+    }
+
+    #[test]
+    fn limits_on_huge_counts() {
+        let mut buf = sample_elf64();
+        buf[60..62].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(matches!(
+            Elf::parse(&buf),
+            Err(ElfError::OutOfBounds(r)) if r.end == 0x100 + 0xffff * 64
+        ));
+        assert!(OwnedElf::parse(&buf).is_err());
+
+        let limits = ParseLimits {
+            max_sections: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            Elf::parse_with_limits(&buf, &limits).err(),
+            Some(ElfError::LimitExceeded {
+                what: "sections",
+                count: 0xffff,
+                limit: 2
+            })
+        );
+        let limits = ParseLimits {
+            truncate: true,
+            ..limits
+        };
+        assert_eq!(
+            Elf::parse_with_limits(&buf, &limits).unwrap().shdrs().len(),
+            2
+        );
+        let elf = OwnedElf::parse_with_limits(&buf, &limits).unwrap();
+        assert_eq!(elf.shdrs.len(), 2);
+        assert_eq!(elf.phdrs.len(), 1);
+    }
+
+    #[test]
+    fn best_effort_em_none() {
+        let limits = ParseLimits::default();
+        let mut buf = sample_elf64();
+        buf[16] = 1;
+        buf[20] = 1;
+        let (elf, warnings) = parse_best_effort(&buf, &limits).unwrap();
+        assert_eq!(warnings, [ParseWarning::UnknownMachine(0)]);
+        assert_eq!(elf.shdrs.len(), 3);
+        assert_eq!(elf.phdrs[0].p_type, 1);
+
+        // OS 保留区间内的 e_type 与 check_object_type 一样视为已知，处理器保留区间则不是
+        buf[16..18].copy_from_slice(&0xfe10u16.to_le_bytes());
+        assert_eq!(parse_best_effort(&buf, &limits).unwrap().1.len(), 1);
+        buf[16..18].copy_from_slice(&0xff00u16.to_le_bytes());
+        assert_eq!(
+            parse_best_effort(&buf, &limits).unwrap().1[1],
+            ParseWarning::UnknownType(0xff00)
+        );
+        buf[16..18].copy_from_slice(&1u16.to_le_bytes());
+
+        // Program Header Table 超出文件时只丢弃 program header
+        buf[32..40].copy_from_slice(&0x1_0000u64.to_le_bytes());
+        let (elf, warnings) = parse_best_effort(&buf, &limits).unwrap();
+        assert!(matches!(
+            warnings[1],
+            ParseWarning::BadProgramHeaders(ElfError::OutOfBounds(_))
+        ));
+        assert!(elf.phdrs.is_empty());
+        assert_eq!(elf.shdrs.len(), 3);
+        assert!(parse_best_effort(&buf[..40], &limits).is_err());
+
+        // 与 OwnedElf::parse_with_limits 使用相同的上限
+        let limits = ParseLimits {
+            max_sections: 2,
+            ..Default::default()
+        };
+        let (elf, warnings) = parse_best_effort(&sample_elf64(), &limits).unwrap();
+        assert!(matches!(
+            warnings.last(),
+            Some(ParseWarning::BadSectionHeaders(
+                ElfError::LimitExceeded { .. }
+            ))
+        ));
+        assert!(elf.shdrs.is_empty());
+        assert_eq!(elf.phdrs.len(), 1);
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::ops::Range;
+
+/// 解析或修改 Elf 文件时出现的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElfError {
+    /// 魔数不是 `\x7fELF`
+    BadMagic,
+    /// EI_CLASS 的值无效
+    BadClass(u8),
+    /// EI_DATA 的值无效
+    BadEncode(u8),
+    /// 需要访问的范围超出了缓冲区
+    OutOfBounds(Range<usize>),
+    /// 当前文件不支持该操作
+    Unsupported(&'static str),
+    /// 偏移或长度计算溢出
+    Overflow,
+    /// 数据格式错误
+    Malformed(&'static str),
+    /// 声明的表项数量超过了 [`ParseLimits`](crate::ParseLimits) 中的上限
+    LimitExceeded {
+        what: &'static str,
+        count: usize,
+        limit: usize,
+    },
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfError::BadMagic => write!(f, "bad elf magic"),
+            ElfError::BadClass(v) => write!(f, "bad elf class: {}", v),
+            ElfError::BadEncode(v) => write!(f, "bad elf data encoding: {}", v),
+            ElfError::OutOfBounds(r) => write!(f, "range {:?} out of bounds", r),
+            ElfError::Unsupported(what) => write!(f, "unsupported: {}", what),
+            ElfError::Overflow => write!(f, "arithmetic overflow"),
+            ElfError::Malformed(what) => write!(f, "malformed data: {}", what),
+            ElfError::LimitExceeded { what, count, limit } => {
+                write!(f, "{} {} exceeds limit {}", count, what, limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ElfError {}
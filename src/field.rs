@@ -0,0 +1,393 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 字段相关的扩展，`Field` 本身定义在 accessor 中。
+
+/// 重新导出，供 [`impl_field_size!`](crate::impl_field_size) 在其他 crate 中使用
+pub use accessor::Field;
+
+use crate::class_field::{ClassField, Layout};
+use crate::{Class, ElfError, ObjectType};
+use std::ops::Range;
+
+/// 编译期可知的字段宽度。
+///
+/// 通用的表与布局检查代码可以直接使用 `SIZE` 计算偏移，无需调用 `range()`。
+/// 应通过 [`impl_field_size!`](crate::impl_field_size) 实现，以便在编译期检查 `SIZE` 与 `BytesType` 的长度一致。
+pub trait FieldSize: Field {
+    const SIZE: usize;
+}
+
+/// 为字段实现 [`FieldSize`]，`SIZE` 与 `BytesType` 的字节长度不一致时编译失败。
+/// # 用法
+/// ```compile_fail
+/// // 本代码只是示例，并不能编译
+/// impl_field_size!(ShType => 4, ShFlags => 8);
+/// ```
+#[macro_export]
+macro_rules! impl_field_size {
+    ($($ty:ty => $size:expr),* $(,)?) => {$(
+        impl $crate::field::FieldSize for $ty {
+            const SIZE: usize = $size;
+        }
+        const _: () = assert!(
+            $size == ::core::mem::size_of::<<$ty as $crate::field::Field>::BytesType>(),
+            "FieldSize::SIZE 与 BytesType 的长度不一致"
+        );
+    )*};
+}
+
+/// 按字段的逻辑值比较两个对象。
+///
+/// 两个对象各自按自己的 Encode 解码，因此可以比较大小端不同的文件。
+pub trait FieldEq: accessor::Getter {
+    fn field_eq<T: Field>(&self, other: &impl accessor::Getter) -> bool
+    where
+        T::FieldType: PartialEq,
+    {
+        self.get::<T>() == other.get::<T>()
+    }
+}
+
+impl<G: accessor::Getter> FieldEq for G {}
+
+/// 将字段直接编码到调用者提供的缓冲区，适合将许多字段写入同一个输出缓冲区的场景。
+///
+/// out 的长度至少为字段宽度，否则返回 `ElfError::OutOfBounds` 且不做修改；超出字段宽度的字节保持不变。
+pub trait FieldWrite: Field {
+    fn write_le_bytes(val: Self::FieldType, out: &mut [u8]) -> Result<(), ElfError> {
+        copy_into(Self::to_le_bytes(val).as_ref(), out)
+    }
+    fn write_be_bytes(val: Self::FieldType, out: &mut [u8]) -> Result<(), ElfError> {
+        copy_into(Self::to_be_bytes(val).as_ref(), out)
+    }
+}
+
+impl<T: Field> FieldWrite for T {}
+
+/// 写入前检查字段值的约束，默认不做检查。
+///
+/// 与 `Setter::with` 不同，[`CheckedSetter::with_checked`] 遇到不满足约束的值时返回错误且不做修改，
+/// 避免编辑后得到明显损坏的文件。常用的约束见 [`check_alignment`] 等函数。
+pub trait FieldValidate: Field {
+    fn validate(_value: &Self::FieldType) -> Result<(), ElfError> {
+        Ok(())
+    }
+}
+
+/// 写入前调用 [`FieldValidate::validate`] 的 Setter
+pub trait CheckedSetter: accessor::Setter {
+    fn with_checked<T: FieldValidate + accessor::Mutable>(
+        &self,
+        value: T::FieldType,
+    ) -> Result<&Self, ElfError> {
+        T::validate(&value)?;
+        Ok(self.with::<T>(value))
+    }
+}
+
+impl<S: accessor::Setter> CheckedSetter for S {}
+
+/// sh_addralign、p_align 等对齐字段只能为 0 或 2 的幂
+pub fn check_alignment(value: u64) -> Result<(), ElfError> {
+    if value == 0 || value.is_power_of_two() {
+        Ok(())
+    } else {
+        Err(ElfError::Malformed("alignment is not a power of two"))
+    }
+}
+
+/// e_type 只能为规范中列出的类型或 OS 保留区间内的值，处理器保留区间见 [`ObjectType::is_known`]
+pub fn check_object_type(value: ObjectType) -> Result<(), ElfError> {
+    if value.is_known() {
+        Ok(())
+    } else {
+        Err(ElfError::Malformed("unknown e_type"))
+    }
+}
+
+/// EI_VERSION 与 e_version 不能为 EV_NONE
+pub fn check_version(value: u32) -> Result<(), ElfError> {
+    match value {
+        0 => Err(ElfError::Malformed("version is EV_NONE")),
+        _ => Ok(()),
+    }
+}
+
+fn copy_into(bytes: &[u8], out: &mut [u8]) -> Result<(), ElfError> {
+    out.get_mut(..bytes.len())
+        .ok_or(ElfError::OutOfBounds(0..bytes.len()))?
+        .copy_from_slice(bytes);
+    Ok(())
+}
+
+/// 知道自身在缓冲区中位置的对象，可以在不读取字段的情况下得到字段的绝对范围
+pub trait Located {
+    /// 对象在缓冲区中的范围
+    fn get_range(&self) -> Range<usize>;
+
+    /// 字段 T 在缓冲区中的绝对范围
+    fn field_range<T: Field>(&self) -> Range<usize> {
+        offset_by(T::range(), self.get_range().start)
+    }
+
+    /// 位置随 Class 变化的字段 T 在缓冲区中的绝对范围
+    fn class_field_range<T: ClassField>(&self, class: Class) -> Range<usize> {
+        offset_by(T::range_of(class), self.get_range().start)
+    }
+
+    /// 按布局 L 列出所有字段的名称与绝对范围
+    fn field_map<L: Layout>(&self, class: Class) -> Vec<(&'static str, Range<usize>)> {
+        let base = self.get_range().start;
+        L::fields(class)
+            .into_iter()
+            .map(|(name, range)| (name, offset_by(range, base)))
+            .collect()
+    }
+}
+
+fn offset_by(range: Range<usize>, base: usize) -> Range<usize> {
+    base + range.start..base + range.end
+}
+
+/// 将 val 的前 width 个字节按小端组装为 u64，高位补零，用于 3 字节等非标准宽度的字段。
+///
+/// width 超过 8 或 val 的长度时 panic。
+pub fn from_le_bytes_sized(val: &[u8], width: usize) -> u64 {
+    assert!(width <= 8, "字段宽度 {} 超过 8 字节", width);
+    val[..width]
+        .iter()
+        .rev()
+        .fold(0, |acc, &b| (acc << 8) | b as u64)
+}
+
+/// 大端版本的 [`from_le_bytes_sized`]
+pub fn from_be_bytes_sized(val: &[u8], width: usize) -> u64 {
+    assert!(width <= 8, "字段宽度 {} 超过 8 字节", width);
+    val[..width].iter().fold(0, |acc, &b| (acc << 8) | b as u64)
+}
+
+/// 将 width 字节宽的值的最高位视为符号位，扩展为 i64
+fn sign_extend(val: u64, width: usize) -> i64 {
+    if width == 0 {
+        return 0;
+    }
+    let shift = 64 - 8 * width as u32;
+    ((val << shift) as i64) >> shift
+}
+
+/// 符号扩展版本的 [`from_le_bytes_sized`]
+pub fn from_le_bytes_sized_signed(val: &[u8], width: usize) -> i64 {
+    sign_extend(from_le_bytes_sized(val, width), width)
+}
+
+/// 符号扩展版本的 [`from_be_bytes_sized`]
+pub fn from_be_bytes_sized_signed(val: &[u8], width: usize) -> i64 {
+    sign_extend(from_be_bytes_sized(val, width), width)
+}
+
+/// 将 val 的低 width 个字节按小端输出，超出部分被截断。width 超过 8 时 panic。
+pub fn to_le_bytes_sized(val: u64, width: usize) -> Vec<u8> {
+    assert!(width <= 8, "字段宽度 {} 超过 8 字节", width);
+    val.to_le_bytes()[..width].to_vec()
+}
+
+/// 大端版本的 [`to_le_bytes_sized`]
+pub fn to_be_bytes_sized(val: u64, width: usize) -> Vec<u8> {
+    assert!(width <= 8, "字段宽度 {} 超过 8 字节", width);
+    val.to_be_bytes()[8 - width..].to_vec()
+}
+
+/// 不分配内存的 [`to_le_bytes_sized`]，写入 out 的前 width 个字节。
+///
+/// out 短于 width 时返回 `ElfError::OutOfBounds` 且不做修改，width 超过 8 时 panic。
+pub fn write_le_bytes_sized(val: u64, width: usize, out: &mut [u8]) -> Result<(), ElfError> {
+    assert!(width <= 8, "字段宽度 {} 超过 8 字节", width);
+    copy_into(&val.to_le_bytes()[..width], out)
+}
+
+/// 大端版本的 [`write_le_bytes_sized`]
+pub fn write_be_bytes_sized(val: u64, width: usize, out: &mut [u8]) -> Result<(), ElfError> {
+    assert!(width <= 8, "字段宽度 {} 超过 8 字节", width);
+    copy_into(&val.to_be_bytes()[8 - width..], out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Half;
+    impl Field for Half {
+        type FieldType = u16;
+        type BytesType = [u8; 2];
+        fn range() -> Range<usize> {
+            4..6
+        }
+        fn from_le_bytes(val: &[u8]) -> u16 {
+            u16::from_le_bytes([val[0], val[1]])
+        }
+        fn from_be_bytes(val: &[u8]) -> u16 {
+            u16::from_be_bytes([val[0], val[1]])
+        }
+        fn to_le_bytes(val: u16) -> [u8; 2] {
+            val.to_le_bytes()
+        }
+        fn to_be_bytes(val: u16) -> [u8; 2] {
+            val.to_be_bytes()
+        }
+    }
+    crate::impl_field_size!(Half => 2);
+
+    #[test]
+    fn write_into_slice() {
+        let mut out = [0xffu8; 4];
+        Half::write_le_bytes(0x1234, &mut out).unwrap();
+        assert_eq!(out, [0x34, 0x12, 0xff, 0xff]);
+        Half::write_be_bytes(0x1234, &mut out[2..]).unwrap();
+        assert_eq!(out, [0x34, 0x12, 0x12, 0x34]);
+        assert_eq!(
+            Half::write_le_bytes(0, &mut out[3..]),
+            Err(ElfError::OutOfBounds(0..2))
+        );
+        assert_eq!(out[3], 0x34);
+
+        write_be_bytes_sized(0x12_3456, 3, &mut out).unwrap();
+        assert_eq!(out, [0x12, 0x34, 0x56, 0x34]);
+        write_le_bytes_sized(0x12_3456, 3, &mut out[1..]).unwrap();
+        assert_eq!(out, [0x12, 0x56, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn size_matches_range() {
+        use crate::test_util::{EType, EiVersion, PRange, ShRange, StName};
+
+        assert_eq!(Half::SIZE, Half::range().len());
+        assert_eq!(EiVersion::SIZE, 1);
+        assert_eq!(EType::SIZE, 2);
+        assert_eq!(StName::SIZE, 4);
+        assert_eq!(ShRange::SIZE, ShRange::range().len());
+        assert_eq!(PRange::SIZE, 32);
+    }
+
+    #[test]
+    fn absolute_field_ranges() {
+        use crate::class_field::{EShoff, EhdrLayout};
+        use crate::test_util::{table, ShOffset, Shdr64};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let buf = Rc::new(RefCell::new(vec![0u8; 0x100]));
+        let sht = table(&buf, 0x80..0x100, Shdr64);
+        assert_eq!(sht[1].get_range(), 0xc0..0x100);
+        assert_eq!(sht[1].field_range::<ShOffset>(), 0xd8..0xe0);
+
+        // 位置只取决于对象的起始位置，这里借用位于 0x80 处的表项
+        let ehdr = &sht[0];
+        assert_eq!(ehdr.class_field_range::<EShoff>(Class::Elf32), 0xa0..0xa4);
+        let map = ehdr.field_map::<EhdrLayout>(Class::Elf64);
+        assert_eq!(map[0], ("e_ident", 0x80..0x90));
+        assert_eq!(map[6], ("e_shoff", 0xa8..0xb0));
+        assert_eq!(map.last().unwrap().1.end, 0x80 + 64);
+    }
+
+    #[test]
+    fn three_byte_round_trip() {
+        let le = to_le_bytes_sized(0x12_3456, 3);
+        assert_eq!(le, [0x56, 0x34, 0x12]);
+        assert_eq!(from_le_bytes_sized(&le, 3), 0x12_3456);
+        let be = to_be_bytes_sized(0x12_3456, 3);
+        assert_eq!(be, [0x12, 0x34, 0x56]);
+        assert_eq!(from_be_bytes_sized(&be, 3), 0x12_3456);
+
+        // 最高位为 1 时，无符号版本补零，有符号版本扩展符号位
+        let neg = to_le_bytes_sized(-2i64 as u64, 3);
+        assert_eq!(from_le_bytes_sized(&neg, 3), 0xff_fffe);
+        assert_eq!(from_le_bytes_sized_signed(&neg, 3), -2);
+        let neg = to_be_bytes_sized(-2i64 as u64, 3);
+        assert_eq!(from_be_bytes_sized_signed(&neg, 3), -2);
+        assert_eq!(
+            from_be_bytes_sized_signed(&[0x7f, 0xff, 0xff], 3),
+            0x7f_ffff
+        );
+    }
+
+    #[test]
+    fn field_eq_across_encode() {
+        use crate::test_util::{EEntry, Obj};
+        use accessor::{Encode, Setter};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut le = vec![0u8; 64];
+        le[24..32].copy_from_slice(&0x401000u64.to_le_bytes());
+        let mut be = vec![0u8; 64];
+        be[24..32].copy_from_slice(&0x401000u64.to_be_bytes());
+        let obj = |buf, encode| Obj {
+            buf: Rc::new(RefCell::new(buf)),
+            base: 0,
+            encode,
+        };
+        let le = obj(le, Encode::Le);
+        let be = obj(be, Encode::Be);
+        assert!(le.field_eq::<EEntry>(&be));
+        be.with::<EEntry>(0x402000);
+        assert!(!le.field_eq::<EEntry>(&be));
+    }
+
+    #[test]
+    fn checked_writes() {
+        use crate::test_util::*;
+        use accessor::{Encode, Getter};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let ehdr = Ehdr64(Obj {
+            buf: Rc::new(RefCell::new(vec![0u8; 64])),
+            base: 0,
+            encode: Encode::Le,
+        });
+        ehdr.with_checked::<EType>(ObjectType::Dyn).unwrap();
+        assert_eq!(
+            ehdr.with_checked::<EType>(ObjectType::from(0xffff)).err(),
+            Some(ElfError::Malformed("unknown e_type"))
+        );
+        assert_eq!(ehdr.get::<EType>(), ObjectType::Dyn);
+        ehdr.with_checked::<EType>(ObjectType::from(0xfe00))
+            .unwrap();
+        ehdr.with_checked::<EType>(ObjectType::from(0xfeff))
+            .unwrap();
+        assert!(ehdr
+            .with_checked::<EType>(ObjectType::from(0xff00))
+            .is_err());
+        assert!(ehdr.with_checked::<EiVersion>(0).is_err());
+        ehdr.with_checked::<EiVersion>(1).unwrap();
+
+        let shdr = Shdr64(Obj {
+            buf: Rc::new(RefCell::new(vec![0u8; 64])),
+            base: 0,
+            encode: Encode::Le,
+        });
+        assert!(shdr.with_checked::<ShAddralign>(24).is_err());
+        shdr.with_checked::<ShAddralign>(0).unwrap();
+        assert_eq!(
+            shdr.with_checked::<ShAddralign>(16)
+                .unwrap()
+                .get::<ShAddralign>(),
+            16
+        );
+    }
+}
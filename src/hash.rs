@@ -0,0 +1,271 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `.gnu.hash` 与 `.hash`（SysV）符号哈希表。
+//!
+//! 两种表都只记录符号索引，判断索引处的符号名称是否匹配由调用者提供，
+//! 因此可以与任意的 [`Symtab`](crate::Symtab) 与 [`Strtab`](crate::Strtab) 实现配合使用。
+
+use crate::codec::Codec;
+use crate::{Class, ElfError};
+#[cfg(feature = "rayon")]
+use crate::{Strtab, Sym, Symtab};
+use accessor::Encode;
+
+/// GNU 风格的符号名哈希
+pub fn gnu_hash(name: &[u8]) -> u32 {
+    name.iter()
+        .fold(5381u32, |h, &c| h.wrapping_mul(33).wrapping_add(c as u32))
+}
+
+/// SysV 风格的符号名哈希（elf_hash）
+pub fn sysv_hash(name: &[u8]) -> u32 {
+    name.iter().fold(0u32, |h, &c| {
+        let h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf000_0000;
+        (h ^ (g >> 24)) & !g
+    })
+}
+
+/// 并行计算 symtab 中每个符号名称的 GNU 哈希，结果按符号索引排列（需要 `rayon` feature）。
+///
+/// 名称无效的符号按空字符串计算。结果可以配合 [`GnuHash::lookup_hashed`] 使用：
+/// 在 `matches` 中先比较 `hashes[idx]`，相等时再比较名称，避免对每个候选符号重复计算哈希。
+#[cfg(feature = "rayon")]
+pub fn precompute_hashes<T: Sym>(symtab: &impl Symtab<T>, strtab: &impl Strtab) -> Vec<u32> {
+    precompute_name_hashes(symtab.iter().map(|sym| sym.get::<T::Name>()), strtab)
+}
+
+/// 与 [`precompute_hashes`] 相同，但直接接收每个符号的 st_name，
+/// 用于 [`DynSymView`](crate::symbol::DynSymView) 这类不实现 [`Sym`] 的符号表（需要 `rayon` feature）
+#[cfg(feature = "rayon")]
+pub fn precompute_name_hashes(
+    names: impl IntoIterator<Item = usize>,
+    strtab: &impl Strtab,
+) -> Vec<u32> {
+    use rayon::prelude::*;
+
+    // 字符串表的实现不一定是 Sync 的，只把名称切片交给其他线程
+    let names: Vec<&[u8]> = names
+        .into_iter()
+        .map(|name| strtab.get(name).unwrap_or("").as_bytes())
+        .collect();
+    names.par_iter().map(|name| gnu_hash(name)).collect()
+}
+
+fn words(
+    c: Codec,
+    data: &[u8],
+    off: usize,
+    count: usize,
+    size: usize,
+) -> Result<Vec<u64>, ElfError> {
+    (0..count)
+        .map(|i| c.read(data, off + i * size, size))
+        .collect()
+}
+
+/// 解析后的 `.gnu.hash`
+#[derive(Debug, Clone)]
+pub struct GnuHash {
+    symoffset: usize,
+    bloom_shift: u32,
+    bloom_bits: u32,
+    bloom: Vec<u64>,
+    buckets: Vec<u32>,
+    chains: Vec<u32>,
+}
+
+impl GnuHash {
+    pub fn parse(data: &[u8], class: Class, encode: Encode) -> Result<Self, ElfError> {
+        let c = Codec::new(class, encode);
+        let header = words(c, data, 0, 4, 4)?;
+        let (nbuckets, symoffset, bloom_size, bloom_shift) = (
+            header[0] as usize,
+            header[1] as usize,
+            header[2] as usize,
+            header[3] as u32,
+        );
+        let word = class.word_size();
+        // 查找时需要计算 hash >> bloom_shift，移位量不小于 bloom 字的位数是无意义的
+        if bloom_shift >= word as u32 * 8 {
+            return Err(ElfError::Malformed("gnu hash bloom shift"));
+        }
+        let bloom = words(c, data, 16, bloom_size, word)?;
+        let buckets_off = 16 + bloom_size * word;
+        let buckets = words(c, data, buckets_off, nbuckets, 4)?;
+        let chains_off = buckets_off + nbuckets * 4;
+        let nchains = data.len().saturating_sub(chains_off) / 4;
+        let chains = words(c, data, chains_off, nchains, 4)?;
+        Ok(GnuHash {
+            symoffset,
+            bloom_shift,
+            bloom_bits: word as u32 * 8,
+            bloom,
+            buckets: buckets.into_iter().map(|v| v as u32).collect(),
+            chains: chains.into_iter().map(|v| v as u32).collect(),
+        })
+    }
+
+    /// 查找名称为 name 的符号索引，`matches(idx)` 判断索引处符号的名称是否为 name
+    pub fn lookup(&self, name: &str, matches: impl Fn(usize) -> bool) -> Option<usize> {
+        self.lookup_hashed(gnu_hash(name.as_bytes()), matches)
+    }
+
+    /// 与 [`GnuHash::lookup`] 相同，但使用已经算好的哈希值
+    pub fn lookup_hashed(&self, hash: u32, matches: impl Fn(usize) -> bool) -> Option<usize> {
+        if self.buckets.is_empty() || self.bloom.is_empty() {
+            return None;
+        }
+        let word = self.bloom[(hash / self.bloom_bits) as usize % self.bloom.len()];
+        let mask = (1u64 << (hash % self.bloom_bits))
+            | (1u64 << ((hash >> self.bloom_shift) % self.bloom_bits));
+        if word & mask != mask {
+            return None;
+        }
+        let mut idx = self.buckets[hash as usize % self.buckets.len()] as usize;
+        if idx < self.symoffset {
+            return None;
+        }
+        loop {
+            let chain = *self.chains.get(idx - self.symoffset)?;
+            if chain | 1 == hash | 1 && matches(idx) {
+                return Some(idx);
+            }
+            if chain & 1 != 0 {
+                return None;
+            }
+            idx += 1;
+        }
+    }
+}
+
+/// 解析后的 `.hash`
+#[derive(Debug, Clone)]
+pub struct SysvHash {
+    buckets: Vec<u32>,
+    chains: Vec<u32>,
+}
+
+impl SysvHash {
+    /// `.hash` 的表项固定为 4 字节，与 Class 无关
+    pub fn parse(data: &[u8], encode: Encode) -> Result<Self, ElfError> {
+        let c = Codec::new(Class::Elf32, encode);
+        let header = words(c, data, 0, 2, 4)?;
+        let (nbucket, nchain) = (header[0] as usize, header[1] as usize);
+        let buckets = words(c, data, 8, nbucket, 4)?;
+        let chains = words(c, data, 8 + nbucket * 4, nchain, 4)?;
+        Ok(SysvHash {
+            buckets: buckets.into_iter().map(|v| v as u32).collect(),
+            chains: chains.into_iter().map(|v| v as u32).collect(),
+        })
+    }
+
+    /// 查找名称为 name 的符号索引，`matches(idx)` 判断索引处符号的名称是否为 name
+    pub fn lookup(&self, name: &str, matches: impl Fn(usize) -> bool) -> Option<usize> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let hash = sysv_hash(name.as_bytes()) as usize;
+        let mut idx = self.buckets[hash % self.buckets.len()] as usize;
+        // 链长不会超过符号数，防止构造的循环链导致死循环
+        for _ in 0..=self.chains.len() {
+            if idx == 0 {
+                return None;
+            }
+            if matches(idx) {
+                return Some(idx);
+            }
+            idx = *self.chains.get(idx)? as usize;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn known_hashes() {
+        assert_eq!(gnu_hash(b""), 5381);
+        assert_eq!(gnu_hash(b"printf"), 0x156b_2bb8);
+        assert_eq!(sysv_hash(b"printf"), 0x0779_05a6);
+    }
+
+    #[test]
+    fn gnu_lookup() {
+        let names = ["", "foo", "bar", "baz"];
+        // 单个 bucket，symoffset 为 1，bloom 只有一个 64 位字
+        let shift = 6;
+        let mut bloom = 0u64;
+        let mut data = Vec::new();
+        for word in &[1u32, 1, 1, shift] {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        let hashes: Vec<u32> = names[1..].iter().map(|n| gnu_hash(n.as_bytes())).collect();
+        for h in &hashes {
+            bloom |= 1 << (h % 64) | 1 << ((h >> shift) % 64);
+        }
+        data.extend_from_slice(&bloom.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        for (i, h) in hashes.iter().enumerate() {
+            let last = i == hashes.len() - 1;
+            data.extend_from_slice(&(h & !1 | last as u32).to_le_bytes());
+        }
+        let table = GnuHash::parse(&data, Class::Elf64, Encode::Le).unwrap();
+        let matches = |name: &'static str| move |idx: usize| names[idx] == name;
+        assert_eq!(table.lookup("bar", matches("bar")), Some(2));
+        assert_eq!(table.lookup("baz", matches("baz")), Some(3));
+        assert_eq!(table.lookup("qux", matches("qux")), None);
+    }
+
+    #[test]
+    fn gnu_rejects_bloom_shift() {
+        let mut data = Vec::new();
+        for word in &[1u32, 1, 1, 64, 0, 0, 1] {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        assert!(matches!(
+            GnuHash::parse(&data, Class::Elf64, Encode::Le),
+            Err(ElfError::Malformed(_))
+        ));
+        data[12..16].copy_from_slice(&32u32.to_le_bytes());
+        assert!(GnuHash::parse(&data, Class::Elf32, Encode::Le).is_err());
+        assert!(GnuHash::parse(&data, Class::Elf64, Encode::Le).is_ok());
+    }
+
+    #[test]
+    fn sysv_lookup() {
+        let names = ["", "foo", "bar"];
+        // 单个 bucket 指向 2，链为 2 -> 1 -> 0
+        let mut data = Vec::new();
+        for word in &[1u32, 3, 2, 0, 0, 1] {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        let table = SysvHash::parse(&data, Encode::Le).unwrap();
+        let matches = |name: &'static str| move |idx: usize| names[idx] == name;
+        assert_eq!(table.lookup("foo", matches("foo")), Some(1));
+        assert_eq!(table.lookup("bar", matches("bar")), Some(2));
+        assert_eq!(table.lookup("qux", matches("qux")), None);
+
+        // 1 -> 2 -> 1 构成循环，查找仍会结束
+        data[16..20].copy_from_slice(&2u32.to_le_bytes());
+        let table = SysvHash::parse(&data, Encode::Le).unwrap();
+        assert_eq!(table.lookup("qux", matches("qux")), None);
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 与 `readelf -h` 对应的 Elf Header 概要
+
+use crate::codec::Codec;
+use crate::{Class, Ehdr, ElfError, Ident, IdentInfo, Machine, OsAbi, OwnedEhdr};
+use accessor::Encode;
+
+/// section 索引的保留区间起点，section 数量不小于此值时 e_shnum 为 0
+pub const SHN_LORESERVE: u16 = 0xff00;
+/// e_phnum 的特殊值，表示实际数量保存在 0 号 section 的 sh_info 中
+pub const PN_XNUM: u16 = 0xffff;
+/// e_shstrndx 的特殊值，表示实际索引保存在 0 号 section 的 sh_link 中
+pub const SHN_XINDEX: u16 = 0xffff;
+/// e_type 中 OS 保留区间的起点与终点
+pub const ET_LOOS: u16 = 0xfe00;
+pub const ET_HIOS: u16 = 0xfeff;
+
+elf_enum! {
+    /// e_type 字段的值
+    pub enum ObjectType: u16 {
+        None = 0 => "NONE",
+        Rel = 1 => "REL",
+        Exec = 2 => "EXEC",
+        Dyn = 3 => "DYN",
+        Core = 4 => "CORE",
+    }
+}
+
+impl ObjectType {
+    /// 规范中列出的类型或 OS 保留区间 `ET_LOOS..=ET_HIOS` 内的值。
+    ///
+    /// 处理器保留区间 0xff00..=0xffff 的含义取决于具体的处理器，不视为已知类型。
+    pub fn is_known(self) -> bool {
+        match self {
+            ObjectType::Other(ty) => (ET_LOOS..=ET_HIOS).contains(&ty),
+            _ => true,
+        }
+    }
+}
+
+/// 解码后的 ident 与 Elf Header 的全部标准字段，不含任何引用，便于序列化
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeaderSummary {
+    pub class: Class,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impl::encode"))]
+    pub data: Encode,
+    pub os_abi: OsAbi,
+    pub abi_version: u8,
+    pub e_type: ObjectType,
+    pub machine: Machine,
+    pub version: u32,
+    pub entry: u64,
+    pub phoff: u64,
+    pub shoff: u64,
+    pub flags: u32,
+    pub ehsize: u16,
+    pub phentsize: u16,
+    pub phnum: u16,
+    pub shentsize: u16,
+    pub shnum: u16,
+    pub shstrndx: u16,
+}
+
+/// 一次读出 header 的全部字段。
+///
+/// 只读取 ident 与 Elf Header 本身，不依赖 Program Header Table 或 Section Header Table 是否存在。
+/// 魔数或 EI_CLASS 无效时返回错误。
+pub fn header_summary<E: Ehdr>(ident: &impl Ident, ehdr: &E) -> Result<HeaderSummary, ElfError> {
+    let info = ident.decode()?;
+    Ok(HeaderSummary {
+        class: info.class,
+        data: info.data,
+        os_abi: info.os_abi,
+        abi_version: info.abi_version,
+        e_type: ehdr.get::<E::Type>(),
+        machine: ehdr.get::<E::Machine>(),
+        version: ehdr.get::<E::Version>(),
+        entry: ehdr.get::<E::Entry>(),
+        phoff: ehdr.get::<E::PhtRange>().start as u64,
+        shoff: ehdr.get::<E::ShtRange>().start as u64,
+        flags: ehdr.get::<E::EFlags>(),
+        ehsize: ehdr.get::<E::Ehsize>(),
+        phentsize: ehdr.get::<E::Phentsize>(),
+        phnum: ehdr.get::<E::Phnum>(),
+        shentsize: ehdr.get::<E::Shentsize>(),
+        shnum: ehdr.get::<E::Shnum>(),
+        shstrndx: ehdr.get::<E::Shstrndx>() as u16,
+    })
+}
+
+/// 只根据文件开头的字节解码 header，适用于只保存了文件头部的场景（如软件包索引）。
+///
+/// 需要的最少字节数为 ELF32 的 52 字节与 ELF64 的 64 字节，即 ident 与 Elf Header 本身；
+/// 不足 16 字节时无法确定 class，同样返回 `ElfError::OutOfBounds`。
+/// 不访问 Program Header Table、Section Header Table 或任何 section，
+/// e_phoff、e_shoff 超出 bytes 也不会出错。
+pub fn inspect_header(bytes: &[u8]) -> Result<HeaderSummary, ElfError> {
+    let info = IdentInfo::parse(bytes)?;
+    let size = info.class.ehdr_size();
+    if bytes.len() < size {
+        return Err(ElfError::OutOfBounds(0..size));
+    }
+    let ehdr = OwnedEhdr::parse(Codec::new(info.class, info.data), bytes)?;
+    Ok(HeaderSummary {
+        class: info.class,
+        data: info.data,
+        os_abi: info.os_abi,
+        abi_version: info.abi_version,
+        e_type: ehdr.e_type.into(),
+        machine: ehdr.e_machine.into(),
+        version: ehdr.e_version,
+        entry: ehdr.e_entry,
+        phoff: ehdr.e_phoff,
+        shoff: ehdr.e_shoff,
+        flags: ehdr.e_flags,
+        ehsize: ehdr.e_ehsize,
+        phentsize: ehdr.e_phentsize,
+        phnum: ehdr.e_phnum,
+        shentsize: ehdr.e_shentsize,
+        shnum: ehdr.e_shnum,
+        shstrndx: ehdr.e_shstrndx,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+    use accessor::{Getter, Setter};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn all_fields() {
+        let mut bytes = vec![0u8; 64];
+        bytes[..9].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 3, 0]);
+        let ehdr = Ehdr64(Obj {
+            buf: Rc::new(RefCell::new(bytes)),
+            base: 0,
+            encode: Encode::Le,
+        });
+        ehdr.with::<EType>(ObjectType::Dyn)
+            .with::<EMachine>(Machine::from(62))
+            .with::<EVersion>(1)
+            .with::<EEntry>(0x1040)
+            .with::<EPhoff>(64)
+            .with::<EShoff>(0x3000)
+            .with::<EEhsize>(64)
+            .with::<EPhentsize>(56)
+            .with::<EPhnum>(11)
+            .with::<EShentsize>(64)
+            .with::<EShnum>(0)
+            .with::<EShstrndx>(0);
+
+        let summary = header_summary(&ehdr, &ehdr).unwrap();
+        assert_eq!(summary.class, Class::Elf64);
+        assert_eq!(summary.data, Encode::Le);
+        assert_eq!(summary.os_abi, OsAbi::from(3));
+        assert_eq!(summary.e_type, ObjectType::Dyn);
+        assert_eq!(summary.machine.raw(), 62);
+        assert_eq!(summary.entry, 0x1040);
+        assert_eq!((summary.phoff, summary.shoff), (64, 0x3000));
+        assert_eq!((summary.phentsize, summary.phnum), (56, 11));
+        assert_eq!((summary.shnum, summary.shstrndx), (0, 0));
+
+        // 写回表范围时只改写偏移与表项数，不影响中间的其他字段
+        ehdr.with::<EPhtRange>(64..64 + 3 * 56);
+        ehdr.with::<EShtRange>(0x4000..0x4000 + 7 * 64);
+        let summary = header_summary(&ehdr, &ehdr).unwrap();
+        assert_eq!(
+            (summary.phoff, summary.phnum, summary.phentsize),
+            (64, 3, 56)
+        );
+        assert_eq!(
+            (summary.shoff, summary.shnum, summary.shentsize),
+            (0x4000, 7, 64)
+        );
+        assert_eq!((summary.ehsize, summary.shstrndx), (64, 0));
+        assert_eq!(ehdr.get::<EPhtRange>(), 64..64 + 3 * 56);
+
+        ehdr.with::<EiDataRaw>(0);
+        assert_eq!(header_summary(&ehdr, &ehdr), Err(ElfError::BadEncode(0)));
+        ehdr.with::<EiDataRaw>(3);
+        assert_eq!(header_summary(&ehdr, &ehdr), Err(ElfError::BadEncode(3)));
+        ehdr.with::<EiMagic>(*b"\x7fELG");
+        assert_eq!(header_summary(&ehdr, &ehdr), Err(ElfError::BadMagic));
+    }
+
+    #[test]
+    fn header_only_bytes() {
+        let mut bytes = vec![0u8; 52];
+        bytes[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 1, 2, 1]);
+        bytes[16..20].copy_from_slice(&[0, 2, 0, 8]);
+        bytes[28..32].copy_from_slice(&0xffff_0000u32.to_be_bytes());
+        bytes[44..46].copy_from_slice(&[0, 3]);
+
+        let summary = inspect_header(&bytes).unwrap();
+        assert_eq!(summary.class, Class::Elf32);
+        assert_eq!(summary.data, Encode::Be);
+        assert_eq!(summary.e_type, ObjectType::Exec);
+        assert_eq!(summary.machine.raw(), 8);
+        assert_eq!((summary.phoff, summary.phnum), (0xffff_0000, 3));
+
+        assert_eq!(
+            inspect_header(&bytes[..51]),
+            Err(ElfError::OutOfBounds(0..52))
+        );
+        bytes[4] = 2;
+        assert_eq!(inspect_header(&bytes), Err(ElfError::OutOfBounds(0..64)));
+    }
+
+    #[test]
+    fn extended_counts() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 2 * 64]));
+        let ehdr = Ehdr64(Obj {
+            buf: buf.clone(),
+            base: 0,
+            encode: Encode::Le,
+        });
+        let sht = table(&buf, 64..128, Shdr64);
+        sht[0].with::<ShSize>(0x1_0000).with::<ShInfo>(0x2_0000);
+        ehdr.with::<EShnum>(5).with::<EPhnum>(3);
+        assert_eq!(ehdr.section_count(&sht), 5);
+        assert_eq!(ehdr.program_count(&sht), 3);
+
+        // e_shoff 为 0 时没有 Section Header Table
+        ehdr.with::<EShnum>(0).with::<EPhnum>(PN_XNUM);
+        assert_eq!(ehdr.section_count(&sht), 0);
+        ehdr.with::<EShoff>(64);
+        assert_eq!(ehdr.section_count(&sht), 0x1_0000);
+        assert_eq!(ehdr.program_count(&sht), 0x2_0000);
+        assert_eq!(ehdr.section_count(&table(&buf, 64..64, Shdr64)), 0);
+    }
+}
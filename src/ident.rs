@@ -0,0 +1,189 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::ElfError;
+use accessor::Encode;
+use std::convert::TryFrom;
+use std::fmt;
+
+pub const ELFMAG: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+
+/// EI_CLASS 字段的值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    Elf32,
+    Elf64,
+}
+
+impl Class {
+    /// 地址、偏移等字长相关字段的字节长度
+    pub fn word_size(self) -> usize {
+        match self {
+            Class::Elf32 => 4,
+            Class::Elf64 => 8,
+        }
+    }
+    pub fn ehdr_size(self) -> usize {
+        match self {
+            Class::Elf32 => 52,
+            Class::Elf64 => 64,
+        }
+    }
+    pub fn shdr_size(self) -> usize {
+        match self {
+            Class::Elf32 => 40,
+            Class::Elf64 => 64,
+        }
+    }
+    pub fn phdr_size(self) -> usize {
+        match self {
+            Class::Elf32 => 32,
+            Class::Elf64 => 56,
+        }
+    }
+}
+
+impl TryFrom<u8> for Class {
+    type Error = ElfError;
+    fn try_from(val: u8) -> Result<Self, ElfError> {
+        match val {
+            1 => Ok(Class::Elf32),
+            2 => Ok(Class::Elf64),
+            other => Err(ElfError::BadClass(other)),
+        }
+    }
+}
+
+impl From<Class> for u8 {
+    fn from(val: Class) -> Self {
+        match val {
+            Class::Elf32 => 1,
+            Class::Elf64 => 2,
+        }
+    }
+}
+
+elf_enum! {
+    /// EI_OSABI 字段的值
+    pub enum OsAbi: u8 {
+        SysV = 0 => "SYSV",
+        HpUx = 1 => "HPUX",
+        NetBsd = 2 => "NETBSD",
+        /// GNU/Linux
+        Gnu = 3 => "GNU",
+        Solaris = 6 => "SOLARIS",
+        Aix = 7 => "AIX",
+        Irix = 8 => "IRIX",
+        FreeBsd = 9 => "FREEBSD",
+        Tru64 = 10 => "TRU64",
+        Modesto = 11 => "MODESTO",
+        OpenBsd = 12 => "OPENBSD",
+        ArmAeabi = 64 => "ARM_AEABI",
+        Arm = 97 => "ARM",
+        Standalone = 255 => "STANDALONE",
+    }
+}
+
+/// 解码后的 16 字节 ident
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdentInfo {
+    pub class: Class,
+    pub data: Encode,
+    /// EI_VERSION，当前版本为 1
+    pub version: u8,
+    pub os_abi: OsAbi,
+    pub abi_version: u8,
+    /// EI_PAD 开始的保留字节
+    pub pad: [u8; 7],
+}
+
+impl IdentInfo {
+    /// 从原始字节解码，依次检查魔数、EI_CLASS 与 EI_DATA
+    pub fn parse(ident: &[u8]) -> Result<Self, ElfError> {
+        if ident.len() < 16 {
+            return Err(ElfError::OutOfBounds(0..16));
+        }
+        if ident[0..4] != ELFMAG {
+            return Err(ElfError::BadMagic);
+        }
+        let class = Class::try_from(ident[4])?;
+        let data = match ident[5] {
+            1 => Encode::Le,
+            2 => Encode::Be,
+            other => return Err(ElfError::BadEncode(other)),
+        };
+        let mut pad = [0u8; 7];
+        pad.copy_from_slice(&ident[9..16]);
+        Ok(IdentInfo {
+            class,
+            data,
+            version: ident[6],
+            os_abi: ident[7].into(),
+            abi_version: ident[8],
+            pad,
+        })
+    }
+}
+
+impl fmt::Display for IdentInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let class = match self.class {
+            Class::Elf32 => "ELF32",
+            Class::Elf64 => "ELF64",
+        };
+        let data = match self.data {
+            Encode::Le => "little endian",
+            Encode::Be => "big endian",
+        };
+        write!(
+            f,
+            "{}, {}, version {}, OS/ABI {:?}, ABI version {}",
+            class, data, self.version, self.os_abi, self.abi_version
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_ident() {
+        let ident = [0x7f, b'E', b'L', b'F', 2, 1, 1, 3, 0, 0, 0, 0, 0, 0, 0, 0];
+        let info = IdentInfo::parse(&ident).unwrap();
+        assert_eq!(info.class, Class::Elf64);
+        assert_eq!(info.data, Encode::Le);
+        assert_eq!(info.os_abi, OsAbi::Gnu);
+        assert_eq!(
+            info.to_string(),
+            "ELF64, little endian, version 1, OS/ABI Gnu, ABI version 0"
+        );
+
+        let mut bad = ident;
+        bad[0] = 0;
+        assert_eq!(IdentInfo::parse(&bad), Err(ElfError::BadMagic));
+        let mut bad = ident;
+        bad[4] = 3;
+        assert_eq!(IdentInfo::parse(&bad), Err(ElfError::BadClass(3)));
+        // EI_CLASS 先于 EI_DATA 检查
+        bad[5] = 3;
+        assert_eq!(IdentInfo::parse(&bad), Err(ElfError::BadClass(3)));
+        let mut bad = ident;
+        bad[5] = 3;
+        assert_eq!(IdentInfo::parse(&bad), Err(ElfError::BadEncode(3)));
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{read_field, write_field};
+use accessor::*;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// 一次写入操作
+#[derive(Debug, Clone)]
+struct Edit {
+    range: Range<usize>,
+    old: Vec<u8>,
+    new: Vec<u8>,
+    at: Instant,
+}
+
+/// 记录所有写入操作的缓冲区，用于撤销/重做。
+///
+/// 只有通过 `Journaled` 写入时才会记录，直接操作原始缓冲区没有任何额外开销。
+/// 设置合并窗口后，窗口时间内对同一范围的连续写入会合并为一次撤销。
+pub struct Journaled<B> {
+    buf: B,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    window: Option<Duration>,
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Journaled<B> {
+    pub fn new(buf: B) -> Self {
+        Journaled {
+            buf,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            window: None,
+        }
+    }
+
+    /// 设置合并窗口，None 表示不合并（默认）
+    pub fn coalesce_within(&mut self, window: Option<Duration>) -> &mut Self {
+        self.window = window;
+        self
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buf.as_ref()
+    }
+
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+
+    /// 将 bytes 写入 range 并记录。range 越界时 panic。
+    pub fn with_bytes(&mut self, range: Range<usize>, bytes: &[u8]) -> &mut Self {
+        assert_eq!(range.len(), bytes.len(), "bytes 长度与 range 不一致");
+        let now = Instant::now();
+        let target = &mut self.buf.as_mut()[range.clone()];
+        let coalesce = match (self.undo.last(), self.window) {
+            (Some(last), Some(window)) => last.range == range && now - last.at <= window,
+            _ => false,
+        };
+        if coalesce {
+            let last = self.undo.last_mut().unwrap();
+            last.new = bytes.to_vec();
+            last.at = now;
+        } else {
+            self.undo.push(Edit {
+                range,
+                old: target.to_vec(),
+                new: bytes.to_vec(),
+                at: now,
+            });
+        }
+        target.copy_from_slice(bytes);
+        self.redo.clear();
+        self
+    }
+
+    /// 写入位于 base 处的对象的字段 T
+    pub fn with<T>(&mut self, base: usize, value: T::FieldType, encode: Encode) -> &mut Self
+    where
+        T: Field + Mutable,
+        T::BytesType: AsRef<[u8]>,
+    {
+        let range = T::range();
+        let bytes = write_field::<T>(value, encode);
+        self.with_bytes(base + range.start..base + range.end, bytes.as_ref())
+    }
+
+    /// 读取位于 base 处的对象的字段 T
+    pub fn get<T: Field>(&self, base: usize, encode: Encode) -> T::FieldType {
+        let range = T::range();
+        read_field::<T>(
+            &self.as_bytes()[base + range.start..base + range.end],
+            encode,
+        )
+    }
+
+    /// 撤销最近一次写入，没有可撤销的写入时返回 false
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(edit) => {
+                self.buf.as_mut()[edit.range.clone()].copy_from_slice(&edit.old);
+                self.redo.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 重做最近一次撤销的写入，没有可重做的写入时返回 false
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                self.buf.as_mut()[edit.range.clone()].copy_from_slice(&edit.new);
+                self.undo.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Entry;
+    impl Field for Entry {
+        type FieldType = u16;
+        type BytesType = [u8; 2];
+        fn range() -> Range<usize> {
+            2..4
+        }
+        fn from_le_bytes(val: &[u8]) -> u16 {
+            u16::from_le_bytes([val[0], val[1]])
+        }
+        fn from_be_bytes(val: &[u8]) -> u16 {
+            u16::from_be_bytes([val[0], val[1]])
+        }
+        fn to_le_bytes(val: u16) -> [u8; 2] {
+            val.to_le_bytes()
+        }
+        fn to_be_bytes(val: u16) -> [u8; 2] {
+            val.to_be_bytes()
+        }
+    }
+    impl Mutable for Entry {}
+
+    #[test]
+    fn undo_restores_value() {
+        let mut j = Journaled::new(vec![0u8, 0, 0x34, 0x12, 0]);
+        j.with::<Entry>(0, 0xbeef, Encode::Le);
+        assert_eq!(j.get::<Entry>(0, Encode::Le), 0xbeef);
+        assert!(j.undo());
+        assert_eq!(j.get::<Entry>(0, Encode::Le), 0x1234);
+        assert!(!j.undo());
+        assert!(j.redo());
+        assert_eq!(j.get::<Entry>(0, Encode::Le), 0xbeef);
+    }
+
+    #[test]
+    fn coalesce_same_field() {
+        let mut j = Journaled::new(vec![0u8; 4]);
+        j.coalesce_within(Some(Duration::from_secs(3600)));
+        j.with::<Entry>(0, 1, Encode::Be);
+        j.with::<Entry>(0, 2, Encode::Be);
+        assert!(j.undo());
+        assert_eq!(j.get::<Entry>(0, Encode::Be), 0);
+        assert!(!j.undo());
+    }
+}
@@ -16,10 +16,89 @@
 // along with accessor.  If not, see <http://www.gnu.org/licenses/>.
 
 use accessor::*;
+use std::borrow::Cow;
 use std::ops::Range;
 
+#[macro_use]
+mod macros;
+
+#[cfg(feature = "tokio")]
+pub mod async_reader;
+pub mod checksum;
+pub mod class_field;
+mod codec;
+pub mod coredump;
+pub mod dwarf;
+pub mod dynamic;
+mod edit;
+pub mod elf;
+pub mod error;
+pub mod field;
+pub mod hash;
+pub mod header;
+pub mod ident;
+pub mod journal;
+#[cfg(feature = "dwarf")]
+pub mod line;
+pub mod link;
+pub mod machine;
+pub mod mapped;
+pub mod nested;
+mod normalize;
+pub mod note;
+pub mod owned;
+pub mod prelude;
+pub mod reloc;
+pub mod sections;
+pub mod segments;
+#[cfg(feature = "serde")]
+mod serde_impl;
+pub mod signature;
+pub mod strtab;
+pub mod summary;
+pub mod symbol;
+pub mod table;
+#[cfg(test)]
+mod test_util;
+pub mod util;
+pub mod version;
+pub mod view;
+
+pub use elf::{Elf, ParseLimits, ParseWarning, PartialElf};
+pub use error::ElfError;
+pub use field::{CheckedSetter, FieldEq, FieldSize, FieldValidate, FieldWrite, Located};
+pub use header::{header_summary, inspect_header, HeaderSummary, ObjectType};
+pub use ident::{Class, IdentInfo, OsAbi};
+pub use journal::Journaled;
+pub use machine::{Machine, MachineFlags, PointerWidth};
+pub use note::{Note, NoteIter};
+pub use owned::{OwnedEhdr, OwnedElf, OwnedPhdr, OwnedShdr, OwnedSym};
+pub use sections::{SectionFlags, SectionType};
+pub use segments::{SegmentFlags, SegmentType};
+pub use strtab::{StrtabBuilder, StrtabView};
+pub use summary::ElfSummary;
+pub use table::{FixedEntry, FixedTable, FixedTableIter, Table, TableIter};
+pub use view::{EhdrView, PhdrView, ShdrView};
+
+/// 按 encode 解码字段 T
+pub(crate) fn read_field<T: Field>(bytes: &[u8], encode: Encode) -> T::FieldType {
+    match encode {
+        Encode::Le => T::from_le_bytes(bytes),
+        Encode::Be => T::from_be_bytes(bytes),
+    }
+}
+
+/// 按 encode 编码字段 T
+pub(crate) fn write_field<T: Field>(value: T::FieldType, encode: Encode) -> T::BytesType {
+    match encode {
+        Encode::Le => T::to_le_bytes(value),
+        Encode::Be => T::to_be_bytes(value),
+    }
+}
+
 // Elf 文件一般两种操作：修改值，插入新值
 /// ElfObject 指的时 Elf 中诸如 Ehdr 这类结构体，具体实现可以直接将 range 字段暴露出来。
+/// 对象之间的包含关系（如符号位于符号表 section 中）见 [`nested::Nested`]。
 pub trait ElfObject: Sized {
     fn as_slice<'a>(&'a self) -> &'a [u8] {
         unsafe {
@@ -43,6 +122,47 @@ pub trait Ident: Setter + Getter {
     /// Encode 字段必须确保大小端两种读法一致，所以 BytesType 字节长度必须为 1.
     /// 该字段的值通常会被缓存在结构体中。
     type Encode: Field<FieldType = Encode, BytesType = [u8; 1]>;
+    /// EI_DATA 的原始值，用于在解码前拒绝 ELFDATANONE 等无效编码
+    type Data: Field<FieldType = u8>;
+    /// EI_MAG0 到 EI_MAG3
+    type Magic: Field<FieldType = [u8; 4]>;
+    /// EI_CLASS 的原始值
+    type Class: Field<FieldType = u8>;
+    /// EI_VERSION
+    type Version: Field<FieldType = u8>;
+    /// EI_OSABI 的原始值
+    type OsAbi: Field<FieldType = u8>;
+    /// EI_ABIVERSION
+    type AbiVersion: Field<FieldType = u8>;
+    /// EI_PAD 开始的保留字节
+    type Pad: Field<FieldType = [u8; 7]>;
+
+    fn check_magic(&self) -> Result<(), ElfError> {
+        if self.get::<Self::Magic>() == ident::ELFMAG {
+            Ok(())
+        } else {
+            Err(ElfError::BadMagic)
+        }
+    }
+
+    /// 一次解码整个 ident，魔数、EI_CLASS 或 EI_DATA 无效时返回错误
+    fn decode(&self) -> Result<IdentInfo, ElfError> {
+        self.check_magic()?;
+        let class = std::convert::TryFrom::try_from(self.get::<Self::Class>())?;
+        let data = match self.get::<Self::Data>() {
+            1 => Encode::Le,
+            2 => Encode::Be,
+            other => return Err(ElfError::BadEncode(other)),
+        };
+        Ok(IdentInfo {
+            class,
+            data,
+            version: self.get::<Self::Version>(),
+            os_abi: self.get::<Self::OsAbi>().into(),
+            abi_version: self.get::<Self::AbiVersion>(),
+            pad: self.get::<Self::Pad>(),
+        })
+    }
 }
 
 pub trait Ehdr: Setter + Getter {
@@ -56,6 +176,69 @@ pub trait Ehdr: Setter + Getter {
     /// Shstrndx 字段用于存储 section 字符串表头在 shdt 中的位置（索引）,
     /// section 字符串表头用于描述 section 的名称。
     type Shstrndx: Field<FieldType = usize>;
+    /// e_machine 字段，决定了 EFlags 的含义
+    type Machine: Field<FieldType = Machine>;
+    /// e_flags 字段，内容与架构相关
+    type EFlags: Field<FieldType = u32>;
+    /// e_type 字段
+    type Type: Field<FieldType = ObjectType>;
+    /// e_version 字段
+    type Version: Field<FieldType = u32>;
+    /// e_entry 字段，程序入口的虚拟地址
+    type Entry: Field<FieldType = u64>;
+    /// e_ehsize 字段
+    type Ehsize: Field<FieldType = u16>;
+    /// e_phentsize 字段的原始值
+    type Phentsize: Field<FieldType = u16>;
+    /// e_phnum 字段的原始值
+    type Phnum: Field<FieldType = u16>;
+    /// e_shentsize 字段的原始值
+    type Shentsize: Field<FieldType = u16>;
+    /// e_shnum 字段的原始值
+    type Shnum: Field<FieldType = u16>;
+
+    /// 根据 e_machine 解码 e_flags
+    fn flags_decoded(&self) -> MachineFlags {
+        MachineFlags::decode(self.get::<Self::Machine>(), self.get::<Self::EFlags>())
+    }
+
+    /// section 的数量。
+    ///
+    /// section 不少于 [`SHN_LORESERVE`](header::SHN_LORESERVE) 个时 e_shnum 为 0，
+    /// 实际数量保存在 0 号 section 的 sh_size 中，因此 sht 至少需要包含 0 号 section；
+    /// 此时 ShtRange 为空，调用者需要自行按 e_shoff 读取 0 号 section。
+    /// 没有 0 号 section 时返回 0。
+    fn section_count<T: Shdr>(&self, sht: &impl ShdrTab<T>) -> usize {
+        match self.get::<Self::Shnum>() {
+            0 if self.get::<Self::ShtRange>().start != 0 => {
+                sht.get(0).map_or(0, |shdr| shdr.get::<T::SecRange>().len())
+            }
+            num => num as usize,
+        }
+    }
+
+    /// e_type 为 ET_CORE 且没有 section 时返回 `ElfError::Unsupported`。
+    ///
+    /// [`sections`] 中按名称或类型查找 section 的函数在没有 section 时只会返回 None 或空结果，
+    /// 可能是 core dump 的文件应先做此检查。
+    fn require_sections<T: Shdr>(&self, sht: &impl ShdrTab<T>) -> Result<(), ElfError> {
+        if self.get::<Self::Type>() == ObjectType::Core && self.section_count(sht) == 0 {
+            Err(ElfError::Unsupported(coredump::NO_SECTIONS))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// segment 的数量。
+    ///
+    /// e_phnum 为 [`PN_XNUM`](header::PN_XNUM) 时实际数量保存在 0 号 section 的 sh_info 中，
+    /// sht 的要求与 [`Ehdr::section_count`] 相同。
+    fn program_count<T: Shdr>(&self, sht: &impl ShdrTab<T>) -> usize {
+        match self.get::<Self::Phnum>() {
+            header::PN_XNUM => sht.get(0).map_or(0, |shdr| shdr.get::<T::Info>()),
+            num => num as usize,
+        }
+    }
 }
 
 /// Strtab 需要实现的 trait，需实现索引操作
@@ -65,18 +248,232 @@ pub trait Ehdr: Setter + Getter {
 /// let strTable = StrTableImpl{};
 /// let name = strTable[Shdr.sh_name]
 /// ```
-pub trait Strtab: std::ops::Index<usize, Output = String> {}
+pub trait Strtab: std::ops::Index<usize, Output = String> {
+    /// 字符串表的原始字节，`offset_of`、`get` 与 `entries` 都基于它实现
+    fn as_bytes(&self) -> &[u8];
+
+    /// 查找字符串 s 在表中的偏移，可以匹配其他字符串的尾部（如 "bar" 可以指向 "foobar" 的后半段）。
+    ///
+    /// 每次查找都需要线性扫描整个表，复杂度为 O(n)。
+    /// 需要多次查找或插入时应改用 [`StrtabBuilder`]。
+    fn offset_of(&self, s: &str) -> Option<usize> {
+        strtab::find_str(self.as_bytes(), s.as_bytes())
+    }
+
+    /// 与索引操作相同，但偏移无效时返回 None 而不是 panic
+    fn get(&self, offset: usize) -> Option<&str> {
+        strtab::str_at(self.as_bytes(), offset)
+    }
+
+    /// 从偏移 0 开始依次遍历表中的 `(偏移, 字符串)`，偏移即 st_name/sh_name 的值。
+    ///
+    /// 偏移 0 处通常为空字符串；末尾缺少 NUL 的部分与不是 UTF-8 的字符串被跳过。
+    fn entries(&self) -> strtab::StrtabEntries<'_> {
+        strtab::StrtabEntries::new(self.as_bytes())
+    }
+}
 
 /// Section Header 需要实现的 trait
 pub trait Shdr: Setter + Getter {
     /// Section 在文件中的范围
     type SecRange: Field<FieldType = Range<usize>>;
+    /// Section 名称在 section 字符串表中的偏移
+    type Name: Field<FieldType = usize>;
+    /// sh_offset 字段，插入或删除数据后需要修正
+    type Offset: Field<FieldType = u64> + Mutable;
+    /// sh_type 字段
+    type Type: Field<FieldType = SectionType>;
+    /// sh_flags 字段
+    type Flags: Field<FieldType = u64>;
+    /// sh_entsize 字段
+    type Entsize: Field<FieldType = u64>;
+    /// sh_link 字段，含义由 sh_type 决定，如符号表的 sh_link 为其字符串表的索引
+    type Link: Field<FieldType = usize>;
+    /// sh_info 字段，含义由 sh_type 决定，如符号表的 sh_info 为第一个非局部符号的索引
+    type Info: Field<FieldType = usize>;
+    /// sh_addralign 字段的原始值，通过 [`Shdr::addralign`] 读取
+    type Addralign: Field<FieldType = u64>;
+
+    /// section 的对齐，sh_addralign 为 0 时按 1 处理
+    fn addralign(&self) -> u64 {
+        self.get::<Self::Addralign>().max(1)
+    }
+
+    /// section 在文件中占据的范围。SHT_NOBITS 不占据文件空间，
+    /// 无论 sh_offset 与 sh_size 为何值都返回起点为 sh_offset 的空范围
+    fn file_range(&self) -> Range<usize> {
+        let range = self.get::<Self::SecRange>();
+        if self.get::<Self::Type>() == SectionType::Nobits {
+            range.start..range.start
+        } else {
+            range
+        }
+    }
+
+    /// 检查不合理的字段组合：sh_size 非 0 但小于 sh_entsize，
+    /// 或 sh_addralign 既不是 0 也不是 2 的幂。
+    /// 空的 section（如没有表项的 `.rela.dyn`）允许任意 sh_entsize。
+    fn validate(&self) -> Result<(), ElfError> {
+        let size = self.get::<Self::SecRange>().len() as u64;
+        if size != 0 && self.get::<Self::Entsize>() > size {
+            return Err(ElfError::Malformed("sh_entsize larger than sh_size"));
+        }
+        if !self.addralign().is_power_of_two() {
+            return Err(ElfError::Malformed("sh_addralign is not a power of two"));
+        }
+        Ok(())
+    }
+
+    /// 根据 sh_type 得到规范要求的表项大小，没有固定表项大小的类型返回 None
+    fn expected_entsize(&self, class: Class) -> Option<usize> {
+        self.get::<Self::Type>().expected_entsize(class)
+    }
+
+    /// sh_entsize 是否与规范要求一致，没有固定表项大小的类型总是返回 true。
+    /// 通用的表在信任表项大小之前应先做此检查。
+    fn entsize_ok(&self, class: Class) -> bool {
+        match self.expected_entsize(class) {
+            Some(size) => self.get::<Self::Entsize>() == size as u64,
+            None => true,
+        }
+    }
+
+    /// section 的内容。未压缩的 section 直接借用 buf（SHT_NOBITS 为空切片），
+    /// 只有 SHF_COMPRESSED 的 section 才会解压到新的缓冲区。
+    ///
+    /// 数据超出 buf 时返回 `ElfError::OutOfBounds`；解压需要 `compression` feature，
+    /// 否则对压缩的 section 返回 `ElfError::Unsupported`。class 与 encode 用于解码 Compression Header。
+    fn section_bytes<'a>(
+        &self,
+        buf: &'a [u8],
+        class: Class,
+        encode: Encode,
+    ) -> Result<Cow<'a, [u8]>, ElfError> {
+        let range = self.file_range();
+        let data = util::clamped_slice(buf, &range).ok_or(ElfError::OutOfBounds(range))?;
+        if SectionFlags(self.get::<Self::Flags>()).contains(SectionFlags::COMPRESSED)
+            && !data.is_empty()
+        {
+            sections::decompress(data, class, encode).map(Cow::Owned)
+        } else {
+            Ok(Cow::Borrowed(data))
+        }
+    }
+
+    /// 将 section 的数据复制到新的缓冲区，修改副本不影响与其他 section 共享的字节。
+    /// 超出 buf 的部分被忽略，SHT_NOBITS 得到空的 Vec。
+    fn data_owned(&self, buf: &[u8]) -> Vec<u8> {
+        let range = self.file_range();
+        let end = range.end.min(buf.len());
+        util::clamped_slice(buf, &(range.start..end))
+            .unwrap_or_default()
+            .to_vec()
+    }
+
+    /// others 中与本 section 的数据有重叠的其他 section 的索引，空的 section 不与任何 section 重叠。
+    /// self 为 others 中的表项（如 `sht[i].overlaps_any(&sht)`）时，结果不包含其自身。
+    fn overlaps_any<S: Shdr>(&self, others: &impl ShdrTab<S>) -> Vec<usize> {
+        let range = self.file_range();
+        let this = self as *const Self as *const u8;
+        others
+            .iter()
+            .enumerate()
+            .filter(|(_, other)| !core::ptr::eq(this, *other as *const S as *const u8))
+            .filter(|(_, other)| {
+                let other = other.file_range();
+                !range.is_empty()
+                    && !other.is_empty()
+                    && range.start < other.end
+                    && other.start < range.end
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// sh_type 在 `readelf -S` 中的名称
+    fn type_string(&self) -> &'static str {
+        self.get::<Self::Type>().readelf_name()
+    }
+
+    /// 将 sh_offset 加上 delta（可以为负），溢出时返回 `ElfError::Overflow` 且不做修改
+    fn shift_offset(&mut self, delta: i64) -> Result<(), ElfError> {
+        let offset = shift(self.get::<Self::Offset>(), delta)?;
+        self.with::<Self::Offset>(offset);
+        Ok(())
+    }
 }
 
 /// Section Header 需要实现的 trait
 pub trait Phdr: Setter + Getter {
     /// Section 在文件中的范围
     type SegRange: Field<FieldType = Range<usize>>;
+    /// p_offset 字段，插入或删除数据后需要修正
+    type Offset: Field<FieldType = u64> + Mutable;
+    /// p_type 字段
+    type Type: Field<FieldType = SegmentType>;
+    /// p_flags 字段
+    type Flags: Field<FieldType = u32>;
+    /// p_vaddr 字段
+    type Vaddr: Field<FieldType = u64>;
+    /// p_memsz 字段
+    type Memsz: Field<FieldType = u64>;
+
+    /// p_type 在 `readelf -l` 中的名称
+    fn type_string(&self) -> &'static str {
+        self.get::<Self::Type>().readelf_name()
+    }
+
+    /// p_flags 在 `readelf -l` 中的形式，见 [`segments::perm_string`]
+    fn perm_string(&self) -> String {
+        segments::perm_string(self.get::<Self::Flags>())
+    }
+
+    /// 将 p_offset 加上 delta（可以为负），溢出时返回 `ElfError::Overflow` 且不做修改
+    fn shift_offset(&mut self, delta: i64) -> Result<(), ElfError> {
+        let offset = shift(self.get::<Self::Offset>(), delta)?;
+        self.with::<Self::Offset>(offset);
+        Ok(())
+    }
+}
+
+fn shift(offset: u64, delta: i64) -> Result<u64, ElfError> {
+    if delta >= 0 {
+        offset.checked_add(delta as u64)
+    } else {
+        offset.checked_sub(delta.unsigned_abs())
+    }
+    .ok_or(ElfError::Overflow)
+}
+
+/// 将偏移不小于 threshold 的 section 的 sh_offset 都加上 delta，
+/// 用于在 threshold 处插入（delta 为正）或删除（delta 为负）数据之后。
+///
+/// 遇到溢出时立即返回错误，此前已修改的 section 不会回滚。
+pub fn shift_all_after<T: Shdr>(
+    tab: &mut impl ShdrTab<T>,
+    threshold: usize,
+    delta: i64,
+) -> Result<(), ElfError> {
+    for shdr in tab.table_mut().iter_mut() {
+        if shdr.get::<T::Offset>() >= threshold as u64 {
+            shdr.shift_offset(delta)?;
+        }
+    }
+    Ok(())
+}
+
+/// 与 [`shift_all_after`] 相同，作用于 Program Header Table
+pub fn shift_segments_after<T: Phdr>(
+    tab: &mut impl PhdrTab<T>,
+    threshold: usize,
+    delta: i64,
+) -> Result<(), ElfError> {
+    for phdr in tab.table_mut().iter_mut() {
+        if phdr.get::<T::Offset>() >= threshold as u64 {
+            phdr.shift_offset(delta)?;
+        }
+    }
+    Ok(())
 }
 
 pub trait Segmemt<T>: std::ops::Index<usize, Output = T>
@@ -90,8 +487,419 @@ pub trait Section: Getter {
     type Header: Shdr + Field;
 }
 
+/// 符号表项需要实现的 trait
+pub trait Sym: Setter + Getter {
+    /// 符号名称在字符串表中的偏移
+    type Name: Field<FieldType = usize>;
+    /// st_shndx 字段，符号所在 section 的索引或特殊索引
+    type Shndx: Field<FieldType = u16>;
+    /// st_info 字段，高 4 位为绑定，低 4 位为类型
+    type Info: Field<FieldType = u8>;
+}
+
+/// 重定位表项需要实现的 trait
+pub trait Rel: Setter + Getter {
+    /// 需要重定位的位置
+    type Offset: Field<FieldType = u64>;
+    /// r_info 中的符号索引，ELF32 为高 24 位，ELF64 为高 32 位
+    type SymIdx: Field<FieldType = u32>;
+
+    /// 通过 symtab 与其 sh_link 所指的 strtab 得到重定位所引用的符号名称。
+    ///
+    /// 符号索引为 0 表示不引用任何符号，返回 None，如 `R_X86_64_RELATIVE`、`R_AARCH64_RELATIVE`、
+    /// `R_*_IRELATIVE`、`R_*_NONE`，以及 local-dynamic TLS 的 `R_X86_64_DTPMOD64`；
+    /// 索引越界或名称无效时同样返回 None。
+    fn symbol_name<'a, S: Sym>(
+        &self,
+        symtab: &impl Symtab<S>,
+        strtab: &'a impl Strtab,
+    ) -> Option<&'a str> {
+        reloc::symbol_name_at(self.get::<Self::SymIdx>(), symtab, strtab)
+    }
+}
+
+/// Dynamic 表项需要实现的 trait
+pub trait Dyn: Setter + Getter {
+    /// d_tag 字段
+    type Tag: Field<FieldType = i64>;
+    /// d_val/d_ptr 字段，具体含义由 Tag 决定
+    type Val: Field<FieldType = u64>;
+}
+
+/// Section Header Table 需要实现的 trait，具体的索引与遍历由 [`Table`] 提供。
+pub trait ShdrTab<T: Shdr>: std::ops::Index<usize, Output = T> {
+    fn table(&self) -> &Table<T>;
+    fn table_mut(&mut self) -> &mut Table<T>;
+    fn len(&self) -> usize {
+        self.table().len()
+    }
+    fn is_empty(&self) -> bool {
+        self.table().is_empty()
+    }
+    /// 越界时返回 None，适用于不可信的输入
+    fn get(&self, idx: usize) -> Option<&T> {
+        self.table().get(idx)
+    }
+    fn iter(&self) -> TableIter<'_, T> {
+        self.table().iter()
+    }
+    /// 第 idx 个 section 的 sh_link 指向的 section，任意一个索引越界时返回 None
+    fn linked(&self, idx: usize) -> Option<&T> {
+        self.get(self.get(idx)?.get::<T::Link>())
+    }
+}
+
+/// Program Header Table 需要实现的 trait
+pub trait PhdrTab<T: Phdr>: std::ops::Index<usize, Output = T> {
+    fn table(&self) -> &Table<T>;
+    fn table_mut(&mut self) -> &mut Table<T>;
+    fn len(&self) -> usize {
+        self.table().len()
+    }
+    fn is_empty(&self) -> bool {
+        self.table().is_empty()
+    }
+    /// 越界时返回 None，适用于不可信的输入
+    fn get(&self, idx: usize) -> Option<&T> {
+        self.table().get(idx)
+    }
+    fn iter(&self) -> TableIter<'_, T> {
+        self.table().iter()
+    }
+    /// 描述 Program Header Table 自身位置的 PT_PHDR 项
+    fn phdr_self(&self) -> Option<&T> {
+        self.iter()
+            .find(|phdr| phdr.get::<T::Type>() == SegmentType::Phdr)
+    }
+    /// Program Header Table 实际所在的地址 actual_load_addr（如 auxv 中的 AT_PHDR）
+    /// 与 PT_PHDR 的 p_vaddr 之差，即 load bias，没有 PT_PHDR 时返回 None。
+    ///
+    /// 例如 PIE 中 PT_PHDR 的 p_vaddr 为 0x40、AT_PHDR 为 0x5555_5555_4040 时，
+    /// load bias 为 0x5555_5555_4000，p_vaddr 为 v 的内容实际位于 v + bias 处（如 `_DYNAMIC`）。
+    /// 实际地址低于 p_vaddr 时结果按 wrapping 减法得到，使用时应以 `wrapping_add` 相加。
+    fn load_bias(&self, actual_load_addr: u64) -> Option<u64> {
+        let vaddr = self.phdr_self()?.get::<T::Vaddr>();
+        Some(actual_load_addr.wrapping_sub(vaddr))
+    }
+}
+
+/// 符号表需要实现的 trait
+pub trait Symtab<T: Sym>: std::ops::Index<usize, Output = T> {
+    fn table(&self) -> &Table<T>;
+    fn len(&self) -> usize {
+        self.table().len()
+    }
+    fn is_empty(&self) -> bool {
+        self.table().is_empty()
+    }
+    /// 越界时返回 None，适用于不可信的输入
+    fn get(&self, idx: usize) -> Option<&T> {
+        self.table().get(idx)
+    }
+    fn iter(&self) -> TableIter<'_, T> {
+        self.table().iter()
+    }
+    /// 局部符号的数量，即符号表 section 的 sh_info，超出表长时取表长
+    fn local_count<S: Shdr>(&self, symtab_shdr: &S) -> usize {
+        symtab_shdr.get::<S::Info>().min(self.len())
+    }
+    /// 规范要求局部符号位于表的开头，按 sh_info 划分出的局部符号
+    fn locals<S: Shdr>(&self, symtab_shdr: &S) -> TableIter<'_, T> {
+        self.table().iter_range(0..self.local_count(symtab_shdr))
+    }
+    /// 按 sh_info 划分出的非局部符号
+    fn globals<S: Shdr>(&self, symtab_shdr: &S) -> TableIter<'_, T> {
+        self.table()
+            .iter_range(self.local_count(symtab_shdr)..self.len())
+    }
+    /// 检查 sh_info 之前的符号均为 STB_LOCAL、之后的均不是。
+    /// [`Symtab::locals`] 与 [`Symtab::globals`] 只信任 sh_info，不做此检查。
+    fn local_boundary_ok<S: Shdr>(&self, symtab_shdr: &S) -> bool {
+        let is_local = |sym: &T| sym.get::<T::Info>() >> 4 == 0;
+        symtab_shdr.get::<S::Info>() <= self.len()
+            && self.locals(symtab_shdr).all(is_local)
+            && !self.globals(symtab_shdr).any(is_local)
+    }
+}
+
+/// 重定位表需要实现的 trait
+pub trait RelTab<T: Rel>: std::ops::Index<usize, Output = T> {
+    fn table(&self) -> &Table<T>;
+    fn len(&self) -> usize {
+        self.table().len()
+    }
+    fn is_empty(&self) -> bool {
+        self.table().is_empty()
+    }
+    /// 越界时返回 None，适用于不可信的输入
+    fn get(&self, idx: usize) -> Option<&T> {
+        self.table().get(idx)
+    }
+    fn iter(&self) -> TableIter<'_, T> {
+        self.table().iter()
+    }
+}
+
+/// Dynamic 表需要实现的 trait
+pub trait DynamicTab<T: Dyn>: std::ops::Index<usize, Output = T> {
+    fn table(&self) -> &Table<T>;
+    fn len(&self) -> usize {
+        self.table().len()
+    }
+    fn is_empty(&self) -> bool {
+        self.table().is_empty()
+    }
+    /// 越界时返回 None，适用于不可信的输入
+    fn get(&self, idx: usize) -> Option<&T> {
+        self.table().get(idx)
+    }
+    fn iter(&self) -> TableIter<'_, T> {
+        self.table().iter()
+    }
+}
+
+impl<T: Shdr> ShdrTab<T> for Table<T> {
+    fn table(&self) -> &Table<T> {
+        self
+    }
+    fn table_mut(&mut self) -> &mut Table<T> {
+        self
+    }
+}
+
+impl<T: Phdr> PhdrTab<T> for Table<T> {
+    fn table(&self) -> &Table<T> {
+        self
+    }
+    fn table_mut(&mut self) -> &mut Table<T> {
+        self
+    }
+}
+
+impl<T: Sym> Symtab<T> for Table<T> {
+    fn table(&self) -> &Table<T> {
+        self
+    }
+}
+
+impl<T: Rel> RelTab<T> for Table<T> {
+    fn table(&self) -> &Table<T> {
+        self
+    }
+}
+
+impl<T: Dyn> DynamicTab<T> for Table<T> {
+    fn table(&self) -> &Table<T> {
+        self
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::test_util::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
     #[test]
     fn test() {}
+
+    #[test]
+    fn overlapping_sections() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 64]));
+        let sht = table(&buf, 0..3 * 64, Shdr64);
+        sht[0].with::<ShOffset>(0x10).with::<ShSize>(8);
+        sht[1].with::<ShOffset>(0x10).with::<ShSize>(8);
+        sht[2].with::<ShOffset>(0x18).with::<ShSize>(8);
+        assert_eq!(sht[0].overlaps_any(&sht), [1]);
+        assert_eq!(sht[1].overlaps_any(&sht), [0]);
+        assert!(sht[2].overlaps_any(&sht).is_empty());
+        // 不在 others 中的表项与其自身的范围重叠时仍被计入
+        assert_eq!(sht[0].clone().overlaps_any(&sht), [0, 1]);
+
+        let bytes: Vec<u8> = (0..0x20).collect();
+        let mut copy = sht[0].data_owned(&bytes);
+        copy[0] = 0xff;
+        assert_eq!(copy[0], 0xff);
+        assert_eq!(sht[0].data_owned(&bytes), &bytes[0x10..0x18]);
+        assert_eq!(sht[1].data_owned(&bytes), &bytes[0x10..0x18]);
+        assert_eq!(sht[2].data_owned(&bytes[..0x1c]), &bytes[0x18..0x1c]);
+    }
+
+    #[test]
+    fn split_locals() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 64 + 4 * 24]));
+        let shdr = Shdr64(Obj {
+            buf: buf.clone(),
+            base: 0,
+            encode: Encode::Le,
+        });
+        let symtab = table(&buf, 64..64 + 4 * 24, Sym64);
+        // STB_LOCAL、STB_LOCAL、STB_GLOBAL、STB_WEAK
+        for (i, info) in [0x00u8, 0x03, 0x12, 0x22].iter().enumerate() {
+            symtab[i].with::<StInfo>(*info).with::<StName>(i);
+        }
+        shdr.with::<ShInfo>(2);
+        assert_eq!(symtab.local_count(&shdr), 2);
+        let names = |it: TableIter<'_, Sym64>| it.map(|s| s.get::<StName>()).collect::<Vec<_>>();
+        assert_eq!(names(symtab.locals(&shdr)), [0, 1]);
+        assert_eq!(names(symtab.globals(&shdr)), [2, 3]);
+        assert!(symtab.local_boundary_ok(&shdr));
+
+        shdr.with::<ShInfo>(3);
+        assert!(!symtab.local_boundary_ok(&shdr));
+        shdr.with::<ShInfo>(1);
+        assert!(!symtab.local_boundary_ok(&shdr));
+        shdr.with::<ShInfo>(9);
+        assert_eq!(symtab.local_count(&shdr), 4);
+        assert!(!symtab.local_boundary_ok(&shdr));
+    }
+
+    #[test]
+    fn shift_after_insert() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 64]));
+        let mut sht = table(&buf, 0..3 * 64, Shdr64);
+        for (i, off) in [0x40u64, 0x80, 0xc0].iter().enumerate() {
+            sht[i].with::<ShOffset>(*off);
+        }
+        // 在 0x80 处插入 0x20 字节的新 section
+        shift_all_after(&mut sht, 0x80, 0x20).unwrap();
+        let offsets: Vec<_> = sht.iter().map(|s| s.get::<ShOffset>()).collect();
+        assert_eq!(offsets, [0x40, 0xa0, 0xe0]);
+
+        assert_eq!(
+            sht.get_mut(0).unwrap().shift_offset(-0x41),
+            Err(ElfError::Overflow)
+        );
+        assert_eq!(sht[0].get::<ShOffset>(), 0x40);
+    }
+
+    #[test]
+    fn range_fields_write_back() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 64 + 56]));
+        let shdr = table(&buf, 0..64, Shdr64);
+        shdr[0].with::<ShFlags>(6).with::<ShRange>(0x100..0x140);
+        assert_eq!(
+            (shdr[0].get::<ShOffset>(), shdr[0].get::<ShSize>()),
+            (0x100, 0x40)
+        );
+        assert_eq!(shdr[0].get::<ShFlags>(), 6);
+
+        // p_offset 与 p_filesz 之间的 p_vaddr 不受影响
+        let phdr = table(&buf, 64..64 + 56, Phdr64);
+        phdr[0]
+            .with::<PVaddr>(0x40_1000)
+            .with::<PRange>(0x1000..0x1800);
+        assert_eq!(phdr[0].get::<PRange>(), 0x1000..0x1800);
+        assert_eq!(phdr[0].get::<PVaddr>(), 0x40_1000);
+    }
+
+    #[test]
+    fn degenerate_align_and_sizes() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 64]));
+        let shdr = Shdr64(Obj {
+            buf,
+            base: 0,
+            encode: Encode::Le,
+        });
+        shdr.with::<ShType>(SectionType::Progbits)
+            .with::<ShOffset>(0x100)
+            .with::<ShSize>(0x30)
+            .with::<ShEntsize>(0x18);
+        assert_eq!(shdr.addralign(), 1);
+        assert_eq!(shdr.validate(), Ok(()));
+
+        shdr.with::<ShAddralign>(8);
+        assert_eq!(shdr.addralign(), 8);
+        shdr.with::<ShAddralign>(12);
+        assert!(shdr.validate().is_err());
+        shdr.with::<ShAddralign>(0);
+
+        shdr.with::<ShEntsize>(0x40);
+        assert_eq!(
+            shdr.validate(),
+            Err(ElfError::Malformed("sh_entsize larger than sh_size"))
+        );
+        shdr.with::<ShSize>(0);
+        assert_eq!(shdr.validate(), Ok(()));
+    }
+
+    #[test]
+    fn nobits_range_is_empty() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 2 * 64]));
+        let sht = table(&buf, 0..2 * 64, Shdr64);
+        sht[0]
+            .with::<ShType>(SectionType::Nobits)
+            .with::<ShOffset>(0x10)
+            .with::<ShSize>(0x1000);
+        sht[1]
+            .with::<ShType>(SectionType::Progbits)
+            .with::<ShOffset>(0x10)
+            .with::<ShSize>(8);
+        assert_eq!(sht[0].file_range(), 0x10..0x10);
+        assert_eq!(sht[1].file_range(), 0x10..0x18);
+        assert!(sht[1].overlaps_any(&sht).is_empty());
+        assert!(sht[0].data_owned(&[0u8; 0x20]).is_empty());
+    }
+
+    #[test]
+    fn borrowed_section_bytes() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 64]));
+        let sht = table(&buf, 0..3 * 64, Shdr64);
+        sht[0]
+            .with::<ShType>(SectionType::Progbits)
+            .with::<ShOffset>(4)
+            .with::<ShSize>(5);
+        sht[1]
+            .with::<ShType>(SectionType::Nobits)
+            .with::<ShOffset>(4)
+            .with::<ShSize>(0x100);
+        // ELF64 Compression Header 之后是 "hello" 的 zlib 未压缩块
+        let mut data = b"....hello".to_vec();
+        data.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&5u64.to_le_bytes());
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&[0x78, 0x01, 0x01, 5, 0, 0xfa, 0xff]);
+        data.extend_from_slice(b"hello");
+        data.extend_from_slice(&0x062c_0215u32.to_be_bytes());
+        sht[2]
+            .with::<ShType>(SectionType::Progbits)
+            .with::<ShFlags>(SectionFlags::COMPRESSED.bits())
+            .with::<ShOffset>(9)
+            .with::<ShSize>(data.len() as u64 - 9);
+
+        let bytes = |i: usize| sht[i].section_bytes(&data, Class::Elf64, Encode::Le);
+        assert!(matches!(bytes(0), Ok(Cow::Borrowed(b"hello"))));
+        assert!(matches!(bytes(1), Ok(Cow::Borrowed([]))));
+        #[cfg(feature = "compression")]
+        assert_eq!(bytes(2), Ok(Cow::Owned(b"hello".to_vec())));
+        #[cfg(not(feature = "compression"))]
+        assert!(matches!(bytes(2), Err(ElfError::Unsupported(_))));
+
+        sht[0].with::<ShSize>(0x100);
+        assert!(bytes(0).is_err());
+
+        // ch_size 小于实际大小时在达到 ch_size 后停止解压
+        data[17..25].copy_from_slice(&4u64.to_le_bytes());
+        let bytes = |i: usize| sht[i].section_bytes(&data, Class::Elf64, Encode::Le);
+        assert!(bytes(2).is_err());
+        data[17..25].copy_from_slice(&u64::MAX.to_le_bytes());
+        let bytes = |i: usize| sht[i].section_bytes(&data, Class::Elf64, Encode::Le);
+        assert!(matches!(bytes(2), Err(ElfError::LimitExceeded { .. })));
+    }
+
+    #[test]
+    fn bias_from_pt_phdr() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 2 * 56]));
+        let pht = table(&buf, 0..2 * 56, Phdr64);
+        pht[0].with::<PType>(SegmentType::Load);
+        assert!(pht.phdr_self().is_none());
+        assert_eq!(pht.load_bias(0x5555_5555_4040), None);
+
+        pht[1].with::<PType>(SegmentType::Phdr).with::<PVaddr>(0x40);
+        assert_eq!(pht.phdr_self().map(|p| p.get::<PVaddr>()), Some(0x40));
+        assert_eq!(pht.load_bias(0x5555_5555_4040), Some(0x5555_5555_4000));
+        let bias = pht.load_bias(0x20).unwrap();
+        assert_eq!(0x40u64.wrapping_add(bias), 0x20);
+    }
 }
@@ -0,0 +1,228 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 地址到源码行号的转换，行号程序由 `gimli` 解码（需要 `dwarf` feature）。
+//!
+//! 本 crate 只负责定位 section（见 [`dwarf_sections`](crate::dwarf::dwarf_sections)），
+//! [`LineResolver`] 在构造时运行 `.debug_line` 中的所有行号程序并保存结果，之后的查询不再访问原始数据。
+
+use crate::dwarf::DwarfSections;
+use crate::{Class, ElfError};
+use accessor::Encode;
+use gimli::{
+    AttributeValue, DebugLine, DebugLineOffset, DebugLineStr, DebugStr, EndianSlice, RunTimeEndian,
+};
+use std::collections::HashMap;
+
+type Slice<'a> = EndianSlice<'a, RunTimeEndian>;
+
+/// 一个连续地址区间内的行号表
+#[derive(Debug, Clone)]
+struct Sequence {
+    start: u64,
+    end: u64,
+    /// (地址, 文件在 files 中的索引, 行号)，按地址升序排列
+    rows: Vec<(u64, usize, u32)>,
+}
+
+/// 由 `.debug_line` 构建的地址到 (文件, 行号) 查询表。
+///
+/// 序列按起始地址排序，查询时二分查找；链接后的文件中各序列的地址区间互不重叠。
+#[derive(Debug, Clone, Default)]
+pub struct LineResolver {
+    files: Vec<String>,
+    sequences: Vec<Sequence>,
+}
+
+fn malformed(_: gimli::Error) -> ElfError {
+    ElfError::Malformed("invalid .debug_line")
+}
+
+impl LineResolver {
+    /// 依次解码 `.debug_line` 中的所有行号程序。
+    ///
+    /// 文件名可能引用 `.debug_str` 或 `.debug_line_str`（DWARF 5），缺少对应 section 时按空表处理。
+    /// 没有 `.debug_line` 时得到空的查询表。
+    pub fn new(secs: &DwarfSections, class: Class, encode: Encode) -> Result<Self, ElfError> {
+        let endian = match encode {
+            Encode::Le => RunTimeEndian::Little,
+            Encode::Be => RunTimeEndian::Big,
+        };
+        let data = secs.debug_line.unwrap_or(&[]);
+        let debug_line = DebugLine::new(data, endian);
+        let debug_str = DebugStr::new(secs.debug_str.unwrap_or(&[]), endian);
+        let debug_line_str = DebugLineStr::new(secs.debug_line_str.unwrap_or(&[]), endian);
+        let string = |attr: AttributeValue<Slice>| -> Result<String, ElfError> {
+            let s = match attr {
+                AttributeValue::String(s) => s,
+                AttributeValue::DebugStrRef(off) => debug_str.get_str(off).map_err(malformed)?,
+                AttributeValue::DebugLineStrRef(off) => {
+                    debug_line_str.get_str(off).map_err(malformed)?
+                }
+                _ => return Ok(String::new()),
+            };
+            Ok(String::from_utf8_lossy(s.slice()).into_owned())
+        };
+
+        let mut resolver = LineResolver::default();
+        // 文件名 → files 中的索引，不同行号程序引用的同一文件只保存一次
+        let mut names: HashMap<String, usize> = HashMap::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let program = debug_line
+                .program(DebugLineOffset(offset), class.word_size() as u8, None, None)
+                .map_err(malformed)?;
+            let header = program.header();
+            offset +=
+                header.unit_length() + header.encoding().format.initial_length_size() as usize;
+
+            let mut rows = program.rows();
+            let mut seq: Option<Sequence> = None;
+            // 行号程序中的文件索引 → files 中的索引，每个文件的路径只拼接一次
+            let mut file_map: HashMap<u64, usize> = HashMap::new();
+            while let Some((header, row)) = rows.next_row().map_err(malformed)? {
+                if row.end_sequence() {
+                    if let Some(mut s) = seq.take() {
+                        s.end = row.address();
+                        resolver.sequences.push(s);
+                    }
+                    continue;
+                }
+                let file = match file_map.get(&row.file_index()) {
+                    Some(&idx) => idx,
+                    None => {
+                        let path = match row.file(header) {
+                            Some(entry) => {
+                                let name = string(entry.path_name())?;
+                                let dir = match entry.directory(header) {
+                                    Some(dir) if !name.starts_with('/') => string(dir)?,
+                                    _ => String::new(),
+                                };
+                                if dir.is_empty() {
+                                    name
+                                } else {
+                                    format!("{}/{}", dir.trim_end_matches('/'), name)
+                                }
+                            }
+                            None => String::new(),
+                        };
+                        let idx = match names.get(&path) {
+                            Some(&idx) => idx,
+                            None => {
+                                resolver.files.push(path.clone());
+                                names.insert(path, resolver.files.len() - 1);
+                                resolver.files.len() - 1
+                            }
+                        };
+                        file_map.insert(row.file_index(), idx);
+                        idx
+                    }
+                };
+                let line = row.line().map_or(0, |l| l.get() as u32);
+                seq.get_or_insert_with(|| Sequence {
+                    start: row.address(),
+                    end: row.address(),
+                    rows: Vec::new(),
+                })
+                .rows
+                .push((row.address(), file, line));
+            }
+        }
+        resolver.sequences.sort_by_key(|s| s.start);
+        Ok(resolver)
+    }
+
+    /// addr 所在的源文件与行号，addr 不在任何行号序列中时返回 None。
+    ///
+    /// addr 为 DWARF 中记录的地址，对位置无关的可执行文件需要先减去加载基址。
+    pub fn line_for_address(&self, addr: u64) -> Option<(String, u32)> {
+        let after = self.sequences.partition_point(|s| s.start <= addr);
+        let seq = &self.sequences[after.checked_sub(1)?];
+        if addr >= seq.end {
+            return None;
+        }
+        let idx = match seq.rows.binary_search_by_key(&addr, |r| r.0) {
+            Ok(idx) => idx,
+            Err(idx) => idx.checked_sub(1)?,
+        };
+        let (_, file, line) = seq.rows[idx];
+        Some((self.files[file].clone(), line))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// 手工编码的 DWARF 2 行号程序，文件 1 为 src/a.c，文件 2 为没有目录的 b.c。
+    ///
+    /// 第一个序列：base 处 a.c:10，base+4 处 a.c:12，base+8 处 b.c:20，结束于 base+12；
+    /// 第二个序列：base+0x1000 处 a.c:1，结束于 base+0x1002。
+    fn unit(base: u64) -> Vec<u8> {
+        let mut header = vec![1, 1, -5i8 as u8, 14, 13];
+        header.extend_from_slice(&[0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1]);
+        header.extend_from_slice(b"src\0\0");
+        header.extend_from_slice(b"a.c\0\x01\0\0b.c\0\0\0\0\0");
+        let set_address = |addr: u64| {
+            let mut op = vec![0, 9, 2];
+            op.extend_from_slice(&addr.to_le_bytes());
+            op
+        };
+        let mut program = set_address(base);
+        // line+9, copy, pc+4, line+2, copy, file 2, pc+4, line+8, copy, pc+4, end_sequence
+        program.extend_from_slice(&[3, 9, 1, 2, 4, 3, 2, 1, 4, 2, 2, 4, 3, 8, 1, 2, 4, 0, 1, 1]);
+        program.extend(set_address(base + 0x1000));
+        // copy, pc+2, end_sequence
+        program.extend_from_slice(&[1, 2, 2, 0, 1, 1]);
+
+        let mut unit = 2u16.to_le_bytes().to_vec();
+        unit.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        unit.extend(header);
+        unit.extend(program);
+        let mut out = (unit.len() as u32).to_le_bytes().to_vec();
+        out.extend(unit);
+        out
+    }
+
+    #[test]
+    fn resolve_hand_assembled_program() {
+        let mut debug_line = unit(0x1000);
+        debug_line.extend(unit(0x4000));
+        let secs = DwarfSections {
+            debug_line: Some(&debug_line[..]),
+            ..Default::default()
+        };
+        let resolver = LineResolver::new(&secs, Class::Elf64, Encode::Le).unwrap();
+        // 两个行号程序引用的文件只保存一次
+        assert_eq!(resolver.files, ["src/a.c", "b.c"]);
+        assert_eq!(resolver.sequences.len(), 4);
+
+        let at = |addr| resolver.line_for_address(addr);
+        assert_eq!(at(0x1000), Some(("src/a.c".to_string(), 10)));
+        assert_eq!(at(0x1006), Some(("src/a.c".to_string(), 12)));
+        assert_eq!(at(0x100b), Some(("b.c".to_string(), 20)));
+        assert_eq!(at(0x2001), Some(("src/a.c".to_string(), 1)));
+        assert_eq!(at(0x4008), Some(("b.c".to_string(), 20)));
+        assert_eq!(at(0x0fff), None);
+        assert_eq!(at(0x100c), None);
+        assert_eq!(at(0x2002), None);
+        assert_eq!(at(0x6000), None);
+
+        let empty = LineResolver::new(&DwarfSections::default(), Class::Elf64, Encode::Le);
+        assert_eq!(empty.unwrap().line_for_address(0x1000), None);
+    }
+}
@@ -0,0 +1,581 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 合并两个可重定位文件，相当于 `ld -r` 的一个最小子集。
+//!
+//! 目前只支持 x86-64 的 ELF64 小端 ET_REL 文件。
+
+use crate::codec::Codec;
+use crate::header::SHN_LORESERVE;
+use crate::owned::{OwnedEhdr, OwnedElf, OwnedShdr, OwnedSym};
+use crate::sections::SectionFlags;
+use crate::strtab::str_at;
+use crate::symbol::{SHN_COMMON, SHN_UNDEF, STB_LOCAL, STB_WEAK};
+use crate::util::{align_up, field_align_up};
+use crate::{Class, ElfError, Machine, ObjectType, SectionType, StrtabBuilder};
+use accessor::Encode;
+use std::collections::HashMap;
+
+const RELA_SIZE: usize = 24;
+
+/// 合并后的一个数据 section
+struct OutSection {
+    name: String,
+    shdr: OwnedShdr,
+    data: Vec<u8>,
+    /// 第一次出现时所在的输入与索引，用于修正 sh_link
+    origin: (usize, usize),
+}
+
+/// 一个输入文件，以及它的 section 与符号在输出中的位置
+struct Input<'a> {
+    elf: &'a OwnedElf,
+    symtab: Option<usize>,
+    /// 输入 section 索引 → (输出 section 索引, 在输出 section 中的偏移)
+    sections: Vec<Option<(usize, u64)>>,
+    /// 输入符号索引 → 输出符号索引
+    symbols: Vec<u32>,
+}
+
+impl<'a> Input<'a> {
+    fn new(elf: &'a OwnedElf) -> Result<Self, ElfError> {
+        let supported = elf.class()? == Class::Elf64
+            && elf.encode()? == Encode::Le
+            && elf.ehdr.e_type == ObjectType::Rel.raw()
+            && Machine::from(elf.ehdr.e_machine) == Machine::X86_64;
+        if !supported {
+            return Err(ElfError::Unsupported(
+                "merge of objects other than x86-64 ELF64 ET_REL",
+            ));
+        }
+        elf.require_sections()?;
+        Ok(Input {
+            elf,
+            symtab: elf.find_section(SectionType::Symtab.raw()),
+            sections: vec![None; elf.shdrs.len()],
+            symbols: Vec::new(),
+        })
+    }
+
+    fn ty(&self, idx: usize) -> SectionType {
+        SectionType::from(self.elf.shdrs[idx].sh_type)
+    }
+
+    /// 符号表的字符串表 section 索引
+    fn strtab(&self) -> Option<usize> {
+        self.symtab.map(|idx| self.elf.shdrs[idx].sh_link as usize)
+    }
+
+    fn symbols(&self) -> Result<Vec<OwnedSym>, ElfError> {
+        match self.symtab {
+            Some(idx) => self.elf.symbols(idx),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn symbol_name(&self, sym: &OwnedSym) -> Result<&'a str, ElfError> {
+        let strtab = self
+            .strtab()
+            .and_then(|idx| self.elf.section_data(idx).ok())
+            .unwrap_or_default();
+        str_at(strtab, sym.st_name as usize).ok_or(ElfError::Malformed("bad symbol name"))
+    }
+
+    /// 将符号的 st_shndx 与 st_value 换算到输出文件中
+    fn place(&self, mut sym: OwnedSym) -> Result<OwnedSym, ElfError> {
+        if sym.st_shndx != SHN_UNDEF && sym.st_shndx < SHN_LORESERVE {
+            let (idx, offset) = self
+                .sections
+                .get(sym.st_shndx as usize)
+                .copied()
+                .flatten()
+                .ok_or(ElfError::Malformed("symbol in unsupported section"))?;
+            sym.st_shndx = idx as u16;
+            sym.st_value = sym.st_value.checked_add(offset).ok_or(ElfError::Overflow)?;
+        }
+        Ok(sym)
+    }
+}
+
+/// 将 section 按名称拼接到 out 中
+fn merge_sections(inputs: &mut [Input<'_>], out: &mut Vec<OutSection>) -> Result<(), ElfError> {
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    for (n, input) in inputs.iter_mut().enumerate() {
+        let elf = input.elf;
+        for idx in 1..elf.shdrs.len() {
+            let shdr = elf.shdrs[idx];
+            match input.ty(idx) {
+                SectionType::Symtab | SectionType::Rela => continue,
+                SectionType::Strtab if idx == elf.shstrndx() || Some(idx) == input.strtab() => {
+                    continue
+                }
+                SectionType::Strtab
+                | SectionType::Rel
+                | SectionType::Group
+                | SectionType::SymtabShndx
+                | SectionType::Dynsym
+                | SectionType::Dynamic => {
+                    return Err(ElfError::Unsupported("merge of this section type"))
+                }
+                _ => {}
+            }
+            let name = elf.section_name(idx)?;
+            let data = elf.section_data(idx)?;
+            let pos = *by_name.entry(name.to_string()).or_insert_with(|| {
+                out.push(OutSection {
+                    name: name.to_string(),
+                    shdr: OwnedShdr {
+                        sh_size: 0,
+                        sh_addralign: 1,
+                        ..shdr
+                    },
+                    data: Vec::new(),
+                    origin: (n, idx),
+                });
+                out.len() - 1
+            });
+            let sec = &mut out[pos];
+            if sec.shdr.sh_type != shdr.sh_type {
+                return Err(ElfError::Malformed("section type mismatch"));
+            }
+            let align = shdr.sh_addralign.max(1);
+            let offset = field_align_up(sec.shdr.sh_size as usize, align)? as u64;
+            if shdr.sh_type != SectionType::Nobits.raw() {
+                sec.data.resize(offset as usize, 0);
+                sec.data.extend_from_slice(data);
+            }
+            sec.shdr.sh_size = offset.checked_add(shdr.sh_size).ok_or(ElfError::Overflow)?;
+            sec.shdr.sh_addralign = sec.shdr.sh_addralign.max(align);
+            sec.shdr.sh_flags |= shdr.sh_flags;
+            input.sections[idx] = Some((pos + 1, offset));
+        }
+    }
+    for sec in out.iter_mut() {
+        let (n, _) = sec.origin;
+        let link = sec.shdr.sh_link as usize;
+        sec.shdr.sh_link = match inputs[n].sections.get(link).copied().flatten() {
+            Some((idx, _)) if link != 0 => idx as u32,
+            _ => 0,
+        };
+    }
+    Ok(())
+}
+
+/// 合并符号表，局部符号在前；返回所有符号、它们的名称以及局部符号的数量
+fn merge_symbols(
+    inputs: &mut [Input<'_>],
+) -> Result<(Vec<OwnedSym>, Vec<String>, usize), ElfError> {
+    let mut syms = vec![OwnedSym::default()];
+    let mut names = vec![String::new()];
+    let tables = inputs
+        .iter()
+        .map(Input::symbols)
+        .collect::<Result<Vec<_>, _>>()?;
+    for (input, table) in inputs.iter_mut().zip(&tables) {
+        input.symbols = vec![0; table.len()];
+        for (i, sym) in table.iter().enumerate().skip(1) {
+            if sym.bind() == STB_LOCAL {
+                input.symbols[i] = syms.len() as u32;
+                syms.push(input.place(*sym)?);
+                names.push(input.symbol_name(sym)?.to_string());
+            }
+        }
+    }
+    let locals = syms.len();
+
+    let mut globals: HashMap<String, usize> = HashMap::new();
+    for (input, table) in inputs.iter_mut().zip(&tables) {
+        for (i, sym) in table.iter().enumerate().skip(1) {
+            if sym.bind() == STB_LOCAL {
+                continue;
+            }
+            let name = input.symbol_name(sym)?;
+            let placed = input.place(*sym)?;
+            let idx = match globals.get(name) {
+                Some(&idx) => {
+                    let old = &mut syms[idx];
+                    let old_def = old.st_shndx != SHN_UNDEF;
+                    let new_def = placed.st_shndx != SHN_UNDEF;
+                    let old_weak = old.bind() == STB_WEAK;
+                    let new_weak = placed.bind() == STB_WEAK;
+                    let old_common = old.st_shndx == SHN_COMMON;
+                    let new_common = placed.st_shndx == SHN_COMMON;
+                    match (old_def, new_def) {
+                        // 两个 COMMON 合并为一个，取较大的大小与对齐（COMMON 的 st_value 为对齐值）
+                        _ if old_common && new_common => {
+                            old.st_size = old.st_size.max(placed.st_size);
+                            old.st_value = old.st_value.max(placed.st_value);
+                        }
+                        // 全局定义覆盖 COMMON，COMMON 覆盖未定义与弱定义
+                        _ if old_common && new_def && !new_weak => *old = placed,
+                        _ if old_common => {}
+                        _ if new_common && (!old_def || old_weak) => *old = placed,
+                        _ if new_common => {}
+                        (true, true) if !old_weak && !new_weak => {
+                            return Err(ElfError::Malformed("duplicate global symbol"))
+                        }
+                        (true, true) | (false, false) if old_weak && !new_weak => *old = placed,
+                        (false, true) => *old = placed,
+                        _ => {}
+                    }
+                    idx
+                }
+                None => {
+                    globals.insert(name.to_string(), syms.len());
+                    syms.push(placed);
+                    names.push(name.to_string());
+                    syms.len() - 1
+                }
+            };
+            input.symbols[i] = idx as u32;
+        }
+    }
+    Ok((syms, names, locals))
+}
+
+/// 按目标 section 合并 RELA，修正 r_offset 与符号索引，返回 (输出目标 section 索引, 表项字节)
+fn merge_relocations(inputs: &[Input<'_>]) -> Result<Vec<(usize, Vec<u8>)>, ElfError> {
+    let c = Codec::new(Class::Elf64, Encode::Le);
+    let mut out: Vec<(usize, Vec<u8>)> = Vec::new();
+    for input in inputs {
+        let elf = input.elf;
+        for idx in 1..elf.shdrs.len() {
+            if input.ty(idx) != SectionType::Rela {
+                continue;
+            }
+            let shdr = elf.shdrs[idx];
+            if Some(shdr.sh_link as usize) != input.symtab || shdr.sh_entsize != RELA_SIZE as u64 {
+                return Err(ElfError::Malformed(
+                    "relocation section not bound to .symtab",
+                ));
+            }
+            let (target, offset) = input
+                .sections
+                .get(shdr.sh_info as usize)
+                .copied()
+                .flatten()
+                .ok_or(ElfError::Malformed("relocation target not merged"))?;
+            let data = elf.section_data(idx)?;
+            let pos = match out.iter().position(|(t, _)| *t == target) {
+                Some(pos) => pos,
+                None => {
+                    out.push((target, Vec::new()));
+                    out.len() - 1
+                }
+            };
+            for entry in data.chunks_exact(RELA_SIZE) {
+                let mut r = c.reader(entry, 0);
+                let (r_offset, r_info, r_addend) = (r.word()?, r.word()?, r.word()?);
+                let sym = *input
+                    .symbols
+                    .get((r_info >> 32) as usize)
+                    .ok_or(ElfError::Malformed("relocation symbol out of range"))?;
+                let mut bytes = [0u8; RELA_SIZE];
+                let mut w = c.writer(&mut bytes, 0);
+                w.word(r_offset.checked_add(offset).ok_or(ElfError::Overflow)?);
+                w.word((sym as u64) << 32 | (r_info & 0xffff_ffff));
+                w.word(r_addend);
+                out[pos].1.extend_from_slice(&bytes);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// 合并两个可重定位文件，结果通过 [`OwnedElf::to_bytes`] 得到。
+///
+/// - 同名的数据 section（SHT_PROGBITS、SHT_NOBITS 等）按 a、b 的顺序拼接，b 的部分按其 sh_addralign 对齐；
+/// - 符号表合并后局部符号在前，全局符号按名称合并：定义覆盖未定义，全局定义覆盖弱定义，
+///   两个全局定义同名时返回 `ElfError::Malformed`；
+/// - 同名的 SHN_COMMON 符号（`-fcommon` 下的暂定定义）合并为一个，取较大的大小与对齐，
+///   全局定义覆盖 COMMON，COMMON 覆盖未定义与弱定义；
+/// - section 的 sh_addralign 不是 2 的幂或过大时返回 `ElfError::Malformed`；
+/// - RELA 按目标 section 合并，r_offset 与符号索引随之修正。
+///
+/// 只支持 x86-64 的 ELF64 小端 ET_REL 文件，包含 SHT_REL、SHT_GROUP 等 section 时返回 `ElfError::Unsupported`。
+pub fn merge(a: OwnedElf, b: OwnedElf) -> Result<OwnedElf, ElfError> {
+    let mut inputs = [Input::new(&a)?, Input::new(&b)?];
+    let mut sections = Vec::new();
+    merge_sections(&mut inputs, &mut sections)?;
+    let (syms, names, locals) = merge_symbols(&mut inputs)?;
+    let relas = merge_relocations(&inputs)?;
+
+    let c = Codec::new(Class::Elf64, Encode::Le);
+    let symtab_idx = 1 + sections.len() + relas.len();
+    let mut strtab = StrtabBuilder::new();
+    let mut symtab = vec![0u8; syms.len() * OwnedSym::size(Class::Elf64)];
+    for (i, (sym, name)) in syms.iter().zip(&names).enumerate() {
+        let sym = OwnedSym {
+            st_name: strtab.add(name) as u32,
+            ..*sym
+        };
+        sym.write(c, &mut symtab, i * OwnedSym::size(Class::Elf64));
+    }
+    let table = |sh_type: SectionType, link, info, flags: SectionFlags, entsize| OwnedShdr {
+        sh_type: sh_type.raw(),
+        sh_flags: flags.bits(),
+        sh_link: link as u32,
+        sh_info: info as u32,
+        sh_addralign: 8,
+        sh_entsize: entsize,
+        ..Default::default()
+    };
+    let mut all: Vec<(String, OwnedShdr, Vec<u8>)> = sections
+        .into_iter()
+        .map(|sec| (sec.name, sec.shdr, sec.data))
+        .collect();
+    for (target, data) in relas {
+        let name = format!(".rela{}", all[target - 1].0);
+        let shdr = table(
+            SectionType::Rela,
+            symtab_idx,
+            target,
+            SectionFlags::INFO_LINK,
+            RELA_SIZE as u64,
+        );
+        all.push((name, shdr, data));
+    }
+    let none = SectionFlags::default();
+    let symtab_shdr = table(SectionType::Symtab, symtab_idx + 1, locals, none, 24);
+    all.push((".symtab".to_string(), symtab_shdr, symtab));
+    let strtab_shdr = OwnedShdr {
+        sh_addralign: 1,
+        ..table(SectionType::Strtab, 0, 0, none, 0)
+    };
+    all.push((".strtab".to_string(), strtab_shdr, strtab.into_bytes()));
+    all.push((".shstrtab".to_string(), strtab_shdr, Vec::new()));
+    let mut shstrtab = StrtabBuilder::new();
+    let sh_names: Vec<usize> = all.iter().map(|(name, _, _)| shstrtab.add(name)).collect();
+    if let Some(last) = all.last_mut() {
+        last.2 = shstrtab.into_bytes();
+    }
+
+    let mut data = vec![0u8; Class::Elf64.ehdr_size()];
+    let mut shdrs = vec![OwnedShdr::default()];
+    for ((_, mut shdr, bytes), sh_name) in all.into_iter().zip(sh_names) {
+        shdr.sh_name = sh_name as u32;
+        shdr.sh_offset = field_align_up(data.len(), shdr.sh_addralign)? as u64;
+        if shdr.sh_type != SectionType::Nobits.raw() {
+            data.resize(shdr.sh_offset as usize, 0);
+            data.extend_from_slice(&bytes);
+            shdr.sh_size = bytes.len() as u64;
+        }
+        shdrs.push(shdr);
+    }
+    let e_shoff = align_up(data.len(), 8);
+    data.resize(e_shoff, 0);
+    Ok(OwnedElf {
+        ehdr: OwnedEhdr {
+            e_phoff: 0,
+            e_phentsize: 0,
+            e_phnum: 0,
+            e_shoff: e_shoff as u64,
+            e_ehsize: Class::Elf64.ehdr_size() as u16,
+            e_shentsize: Class::Elf64.shdr_size() as u16,
+            e_shnum: shdrs.len() as u16,
+            e_shstrndx: (shdrs.len() - 1) as u16,
+            ..a.ehdr
+        },
+        phdrs: Vec::new(),
+        shdrs,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reloc::relocations;
+    use crate::symbol::STB_GLOBAL;
+    use crate::test_util::{elf64_ident, table, Shdr64};
+    use crate::Strtab;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const R_X86_64_PLT32: u64 = 4;
+    const STT_FUNC: u8 = 2;
+    const STT_SECTION: u8 = 3;
+
+    /// 只有 .text、.rela.text、.symtab、.strtab 与 .shstrtab 的目标文件，
+    /// syms 为 (名称, st_info, 是否定义于 .text, st_value)，relas 为 (r_offset, 符号索引)
+    fn object(text: &[u8], syms: &[(&str, u8, bool, u64)], relas: &[(u64, u64)]) -> OwnedElf {
+        let c = Codec::new(Class::Elf64, Encode::Le);
+        let mut strtab = StrtabBuilder::new();
+        let mut symtab = vec![0u8; (syms.len() + 1) * 24];
+        for (i, &(name, st_info, defined, st_value)) in syms.iter().enumerate() {
+            let sym = OwnedSym {
+                st_name: strtab.add(name) as u32,
+                st_info,
+                st_shndx: if defined { 1 } else { SHN_UNDEF },
+                st_value,
+                ..Default::default()
+            };
+            sym.write(c, &mut symtab, (i + 1) * 24);
+        }
+        let mut rela = Vec::new();
+        for &(offset, sym) in relas {
+            rela.extend_from_slice(&offset.to_le_bytes());
+            rela.extend_from_slice(&(sym << 32 | R_X86_64_PLT32).to_le_bytes());
+            rela.extend_from_slice(&(-4i64).to_le_bytes());
+        }
+        let shstrtab = b"\0.text\0.rela.text\0.symtab\0.strtab\0.shstrtab\0";
+        let shdr = |sh_name, sh_type, sh_link, sh_info, sh_addralign, sh_entsize| OwnedShdr {
+            sh_name,
+            sh_type,
+            sh_link,
+            sh_info,
+            sh_addralign,
+            sh_entsize,
+            ..Default::default()
+        };
+        let sections = [
+            (shdr(1, 1, 0, 0, 16, 0), text),
+            (shdr(7, 4, 3, 1, 8, 24), &rela[..]),
+            (shdr(18, 2, 4, 1, 8, 24), &symtab[..]),
+            (shdr(26, 3, 0, 0, 1, 0), strtab.as_bytes()),
+            (shdr(34, 3, 0, 0, 1, 0), &shstrtab[..]),
+        ];
+        let mut data = vec![0u8; 64];
+        let mut shdrs = vec![OwnedShdr::default()];
+        for (mut shdr, bytes) in sections.iter().copied() {
+            data.resize(align_up(data.len(), shdr.sh_addralign as usize), 0);
+            shdr.sh_offset = data.len() as u64;
+            shdr.sh_size = bytes.len() as u64;
+            shdrs.push(shdr);
+            data.extend_from_slice(bytes);
+        }
+        let e_shoff = align_up(data.len(), 8) as u64;
+        let bytes = OwnedElf {
+            ehdr: OwnedEhdr {
+                ident: elf64_ident(),
+                e_type: ObjectType::Rel.raw(),
+                e_machine: Machine::X86_64.raw(),
+                e_shoff,
+                e_shnum: 6,
+                e_shstrndx: 5,
+                ..Default::default()
+            },
+            phdrs: Vec::new(),
+            shdrs,
+            data,
+        }
+        .to_bytes()
+        .unwrap();
+        OwnedElf::parse(&bytes).unwrap()
+    }
+
+    #[test]
+    fn cross_references() {
+        let global = STB_GLOBAL << 4 | STT_FUNC;
+        // a: call b; ret
+        let a = object(
+            &[0xe8, 0, 0, 0, 0, 0xc3],
+            &[("a", global, true, 0), ("b", STB_GLOBAL << 4, false, 0)],
+            &[(1, 2)],
+        );
+        // b: 局部的 section 符号，call a; ret
+        let b = object(
+            &[0xe8, 0, 0, 0, 0, 0xc3],
+            &[
+                ("", STT_SECTION, true, 0),
+                ("b", global, true, 0),
+                ("a", STB_GLOBAL << 4, false, 0),
+            ],
+            &[(1, 3)],
+        );
+        let merged = merge(a.clone(), b).unwrap();
+        let elf = OwnedElf::parse(&merged.to_bytes().unwrap()).unwrap();
+        let names: Vec<_> = (1..elf.shdrs.len())
+            .map(|idx| elf.section_name(idx).unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [".text", ".rela.text", ".symtab", ".strtab", ".shstrtab"]
+        );
+        assert_eq!(elf.section_data(1).unwrap()[16..], [0xe8, 0, 0, 0, 0, 0xc3]);
+        assert_eq!(elf.shdrs[1].sh_size, 22);
+
+        let syms = elf.symbols(3).unwrap();
+        assert_eq!(elf.shdrs[3].sh_info, 2);
+        assert_eq!((syms[1].sym_type(), syms[1].st_value), (STT_SECTION, 16));
+        let strtab = elf.section_data(4).unwrap();
+        let name = |sym: &OwnedSym| str_at(strtab, sym.st_name as usize).unwrap();
+        assert_eq!((name(&syms[2]), syms[2].st_value), ("a", 0));
+        assert_eq!((name(&syms[3]), syms[3].st_value), ("b", 16));
+        assert!(syms[2..].iter().all(|sym| sym.st_shndx == 1));
+
+        let data = merged.to_bytes().unwrap();
+        let buf = Rc::new(RefCell::new(data.clone()));
+        let sht = table(&buf, elf.ehdr.e_shoff as usize..data.len(), Shdr64);
+        let relocs: Vec<_> = relocations(&sht[2], &data, Encode::Le)
+            .map(|r| (r.offset, r.sym, r.addend))
+            .collect();
+        assert_eq!(relocs, [(1, 3, Some(-4)), (17, 2, Some(-4))]);
+
+        assert_eq!(
+            merge(a.clone(), a.clone()).err(),
+            Some(ElfError::Malformed("duplicate global symbol"))
+        );
+
+        let mut bad = a.clone();
+        bad.shdrs[1].sh_addralign = 24;
+        assert!(matches!(merge(a, bad), Err(ElfError::Malformed(_))));
+    }
+
+    #[test]
+    fn offsets_overflow() {
+        let global = STB_GLOBAL << 4 | STT_FUNC;
+        let a = object(&[0xc3], &[("a", global, true, 0)], &[]);
+        // b 的 .text 位于 a 之后，符号与重定位的偏移加上起始位置后溢出
+        let b = object(&[0xc3], &[("b", global, true, u64::MAX)], &[]);
+        assert_eq!(merge(a.clone(), b).err(), Some(ElfError::Overflow));
+        let b = object(&[0xc3], &[("b", global, true, 0)], &[(u64::MAX, 1)]);
+        assert_eq!(merge(a, b).err(), Some(ElfError::Overflow));
+    }
+
+    /// 将 .symtab 中的第 idx 个符号改为大小为 size、对齐为 align 的 COMMON 符号
+    fn make_common(elf: &mut OwnedElf, idx: usize, size: u64, align: u64) {
+        let mut sym = elf.symbols(3).unwrap()[idx];
+        sym.st_shndx = SHN_COMMON;
+        sym.st_size = size;
+        sym.st_value = align;
+        let off = elf.shdrs[3].sh_offset as usize + idx * 24;
+        sym.write(Codec::new(Class::Elf64, Encode::Le), &mut elf.data, off);
+    }
+
+    #[test]
+    fn common_symbols() {
+        let object_sym = STB_GLOBAL << 4 | 1;
+        let with_x = || object(&[0xc3], &[("x", object_sym, true, 0)], &[]);
+        let (mut a, mut b) = (with_x(), with_x());
+        make_common(&mut a, 1, 4, 4);
+        make_common(&mut b, 1, 8, 8);
+        let merged = merge(a.clone(), b).unwrap();
+        let syms = merged.symbols(3).unwrap();
+        assert_eq!(syms.len(), 2);
+        assert_eq!(
+            (syms[1].st_shndx, syms[1].st_size, syms[1].st_value),
+            (SHN_COMMON, 8, 8)
+        );
+
+        // 定义覆盖 COMMON，与顺序无关
+        for (a, b) in [(a.clone(), with_x()), (with_x(), a)].iter().cloned() {
+            let syms = merge(a, b).unwrap().symbols(3).unwrap();
+            assert_eq!(syms[1].st_shndx, 1);
+        }
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::Class;
+
+elf_enum! {
+    /// e_machine 字段的值
+    pub enum Machine: u16 {
+        None = 0 => "NONE",
+        Sparc = 2 => "SPARC",
+        I386 = 3 => "386",
+        M68k = 4 => "68K",
+        Mips = 8 => "MIPS",
+        Ppc = 20 => "PPC",
+        Ppc64 = 21 => "PPC64",
+        S390 = 22 => "S390",
+        Arm = 40 => "ARM",
+        SparcV9 = 43 => "SPARCV9",
+        Ia64 = 50 => "IA_64",
+        X86_64 = 62 => "X86_64",
+        Aarch64 = 183 => "AARCH64",
+        RiscV = 243 => "RISCV",
+        LoongArch = 258 => "LOONGARCH",
+    }
+}
+
+/// 指针宽度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointerWidth {
+    Bits32,
+    Bits64,
+}
+
+impl PointerWidth {
+    /// 指针的字节数
+    pub fn bytes(self) -> usize {
+        match self {
+            PointerWidth::Bits32 => 4,
+            PointerWidth::Bits64 => 8,
+        }
+    }
+}
+
+impl Machine {
+    /// 架构固有的指针宽度。
+    ///
+    /// 同一 e_machine 同时用于 32 位与 64 位的架构（如 MIPS、RISC-V、S390、LoongArch）返回 None，
+    /// 此时应以 EI_CLASS 为准。
+    pub fn pointer_width(self) -> Option<PointerWidth> {
+        match self {
+            Machine::Sparc | Machine::I386 | Machine::M68k | Machine::Ppc | Machine::Arm => {
+                Some(PointerWidth::Bits32)
+            }
+            Machine::Ppc64
+            | Machine::SparcV9
+            | Machine::Ia64
+            | Machine::X86_64
+            | Machine::Aarch64 => Some(PointerWidth::Bits64),
+            _ => None,
+        }
+    }
+}
+
+/// EI_CLASS 与 e_machine 所暗示的指针宽度是否一致，没有固定宽度的架构总是返回 true。
+///
+/// 注意 x32 ABI（ELFCLASS32 的 x86-64）与 ILP32 的 AArch64 是合法的组合，但也会被判定为不一致。
+pub fn class_matches_machine(class: Class, machine: Machine) -> bool {
+    match machine.pointer_width() {
+        Some(width) => width.bytes() == class.word_size(),
+        None => true,
+    }
+}
+
+/// RISC-V 的浮点 ABI（EF_RISCV_FLOAT_ABI）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiscVFloatAbi {
+    Soft,
+    Single,
+    Double,
+    Quad,
+}
+
+/// ARM 的浮点 ABI（EF_ARM_ABI_FLOAT_*）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArmFloatAbi {
+    Unspecified,
+    Soft,
+    Hard,
+}
+
+/// MIPS 的 ABI（EF_MIPS_ABI 以及 EF_MIPS_ABI2）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipsAbi {
+    /// 未标明 ABI，64 位文件通常为 n64
+    Unspecified,
+    O32,
+    O64,
+    Eabi32,
+    Eabi64,
+    N32,
+    Other(u32),
+}
+
+/// 根据 e_machine 解码后的 e_flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineFlags {
+    RiscV {
+        float_abi: RiscVFloatAbi,
+        /// 使用压缩指令（C 扩展）
+        rvc: bool,
+        /// 使用 RV32E/RV64E
+        rve: bool,
+        /// 使用 TSO 内存模型
+        tso: bool,
+    },
+    Arm {
+        /// EABI 版本，0 表示非 EABI
+        eabi_version: u8,
+        float_abi: ArmFloatAbi,
+        be8: bool,
+    },
+    Mips {
+        /// EF_MIPS_ARCH 字段的原始值
+        arch: u8,
+        abi: MipsAbi,
+        noreorder: bool,
+        pic: bool,
+        cpic: bool,
+    },
+    /// 尚未支持解码的架构
+    Raw(u32),
+}
+
+impl MachineFlags {
+    pub fn decode(machine: Machine, flags: u32) -> Self {
+        match machine {
+            Machine::RiscV => MachineFlags::RiscV {
+                float_abi: match flags & 0x6 {
+                    0x0 => RiscVFloatAbi::Soft,
+                    0x2 => RiscVFloatAbi::Single,
+                    0x4 => RiscVFloatAbi::Double,
+                    _ => RiscVFloatAbi::Quad,
+                },
+                rvc: flags & 0x1 != 0,
+                rve: flags & 0x8 != 0,
+                tso: flags & 0x10 != 0,
+            },
+            Machine::Arm => MachineFlags::Arm {
+                eabi_version: (flags >> 24) as u8,
+                float_abi: if flags & 0x400 != 0 {
+                    ArmFloatAbi::Hard
+                } else if flags & 0x200 != 0 {
+                    ArmFloatAbi::Soft
+                } else {
+                    ArmFloatAbi::Unspecified
+                },
+                be8: flags & 0x0080_0000 != 0,
+            },
+            Machine::Mips => MachineFlags::Mips {
+                arch: (flags >> 28) as u8,
+                abi: match flags & 0xf000 {
+                    0x0000 if flags & 0x20 != 0 => MipsAbi::N32,
+                    0x0000 => MipsAbi::Unspecified,
+                    0x1000 => MipsAbi::O32,
+                    0x2000 => MipsAbi::O64,
+                    0x3000 => MipsAbi::Eabi32,
+                    0x4000 => MipsAbi::Eabi64,
+                    other => MipsAbi::Other(other),
+                },
+                noreorder: flags & 0x1 != 0,
+                pic: flags & 0x2 != 0,
+                cpic: flags & 0x4 != 0,
+            },
+            _ => MachineFlags::Raw(flags),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn raw_round_trip() {
+        for raw in 0..=u16::MAX {
+            let machine = Machine::from(raw);
+            assert_eq!(machine.raw(), raw);
+            assert_eq!(u16::from(machine), raw);
+            assert_eq!(Machine::from(machine.raw()), machine);
+        }
+        assert_eq!(Machine::from(62), Machine::X86_64);
+        assert_eq!(Machine::from(0x1234), Machine::Other(0x1234));
+    }
+
+    #[test]
+    fn class_machine_mismatch() {
+        assert_eq!(Machine::X86_64.pointer_width(), Some(PointerWidth::Bits64));
+        assert!(class_matches_machine(Class::Elf64, Machine::X86_64));
+        assert!(!class_matches_machine(Class::Elf32, Machine::X86_64));
+        assert!(!class_matches_machine(Class::Elf64, Machine::I386));
+        assert!(class_matches_machine(Class::Elf32, Machine::RiscV));
+        assert!(class_matches_machine(Class::Elf64, Machine::Other(0x1234)));
+    }
+
+    #[test]
+    fn riscv_float_abi() {
+        let abi = |flags| match MachineFlags::decode(Machine::RiscV, flags) {
+            MachineFlags::RiscV { float_abi, .. } => float_abi,
+            _ => unreachable!(),
+        };
+        assert_eq!(abi(0x0), RiscVFloatAbi::Soft);
+        assert_eq!(abi(0x2), RiscVFloatAbi::Single);
+        assert_eq!(abi(0x5), RiscVFloatAbi::Double);
+        assert_eq!(abi(0x6), RiscVFloatAbi::Quad);
+        assert_eq!(
+            MachineFlags::decode(Machine::RiscV, 0x5),
+            MachineFlags::RiscV {
+                float_abi: RiscVFloatAbi::Double,
+                rvc: true,
+                rve: false,
+                tso: false,
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_machine_is_raw() {
+        assert_eq!(
+            MachineFlags::decode(Machine::X86_64, 0x1234),
+            MachineFlags::Raw(0x1234)
+        );
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! crate 内部使用的宏
+
+/// 定义与整数一一对应的枚举，未列出的值由 `Other` 保存。
+///
+/// 每个变体还需给出规范中常量名去掉前缀后的名称，如 `Progbits = 1 => "PROGBITS"`。
+///
+/// 生成枚举本身、`From<$int>`、`From<枚举> for $int`、`raw()` 与 `canonical_name()`，
+/// 所有取值只需在一处列出，两个方向的转换总是一致的。`Default` 为 0 对应的值。
+/// 启用 `serde` feature 时序列化为规范名称，`Other` 序列化为原始整数；
+/// 反序列化同时接受规范名称与整数。
+macro_rules! elf_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident: $int:ty {
+            $($(#[$vmeta:meta])* $variant:ident = $value:literal => $canon:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vmeta])* $variant,)*
+            /// 未知的值
+            Other($int),
+        }
+
+        impl From<$int> for $name {
+            fn from(val: $int) -> Self {
+                match val {
+                    $($value => $name::$variant,)*
+                    other => $name::Other(other),
+                }
+            }
+        }
+
+        impl From<$name> for $int {
+            fn from(val: $name) -> Self {
+                val.raw()
+            }
+        }
+
+        impl $name {
+            /// 对应的原始整数
+            pub fn raw(self) -> $int {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Other(other) => other,
+                }
+            }
+
+            /// 规范中的常量名去掉前缀后的名称，未知的值返回 None
+            pub fn canonical_name(self) -> Option<&'static str> {
+                match self {
+                    $($name::$variant => Some($canon),)*
+                    $name::Other(_) => None,
+                }
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name::from(0)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    $($name::$variant => serializer.serialize_str($canon),)*
+                    $name::Other(other) => serde::Serialize::serialize(other, serializer),
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct Visitor;
+                impl<'de> serde::de::Visitor<'de> for Visitor {
+                    type Value = $name;
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str(concat!(stringify!($name), " name or integer"))
+                    }
+                    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<$name, E> {
+                        match v {
+                            $($canon => Ok($name::$variant),)*
+                            _ => Err(E::invalid_value(serde::de::Unexpected::Str(v), &self)),
+                        }
+                    }
+                    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<$name, E> {
+                        <$int as std::convert::TryFrom<u64>>::try_from(v)
+                            .map($name::from)
+                            .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+                    }
+                }
+                deserializer.deserialize_any(Visitor)
+            }
+        }
+    };
+}
+
+/// 定义由整数保存的位标志类型。
+///
+/// 生成保存原始整数的结构体、各个标志常量、`From<$int>` 与 `From<类型> for $int`。
+/// 转换保留所有位，未定义的位（如厂商扩展）在读取后写回时不会丢失，
+/// 因此可以像 [`elf_enum!`] 生成的枚举一样作为字段的值类型。
+macro_rules! elf_flags {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident: $int:ty {
+            $($(#[$fmeta:meta])* const $flag:ident = $value:expr;)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+        pub struct $name(pub $int);
+
+        impl $name {
+            $($(#[$fmeta])* pub const $flag: $name = $name($value);)*
+
+            /// 保留所有位，包括未定义的位
+            pub const fn from_bits_retain(bits: $int) -> Self {
+                $name(bits)
+            }
+
+            pub const fn bits(self) -> $int {
+                self.0
+            }
+
+            /// 是否包含 other 中的所有位
+            pub fn contains(self, other: $name) -> bool {
+                self.0 & other.0 == other.0
+            }
+        }
+
+        impl std::ops::BitOr for $name {
+            type Output = $name;
+            fn bitor(self, rhs: $name) -> $name {
+                $name(self.0 | rhs.0)
+            }
+        }
+
+        impl From<$int> for $name {
+            fn from(bits: $int) -> Self {
+                $name::from_bits_retain(bits)
+            }
+        }
+
+        impl From<$name> for $int {
+            fn from(val: $name) -> Self {
+                val.bits()
+            }
+        }
+    };
+}
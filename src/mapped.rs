@@ -0,0 +1,127 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 字段不按线性方式存放的对象，如在数据中穿插元数据的容器格式。
+//!
+//! `Field::range()` 仍按 ELF 规范描述逻辑位置，读写前由偏移映射换算出字段在缓冲区中的实际位置。
+
+use crate::view::{checked_cells, read_cells, write_cells};
+use crate::{read_field, ElfError};
+use accessor::*;
+use std::cell::Cell;
+
+/// 借用缓冲区、按偏移映射读写字段的对象，同时实现 Getter 与 Setter
+pub struct MappedObject<'a> {
+    bytes: &'a [Cell<u8>],
+    encode: Encode,
+    map: Option<Box<dyn Fn(usize) -> usize + 'a>>,
+}
+
+impl<'a> MappedObject<'a> {
+    /// 不做映射，字段的逻辑偏移即为在 buf 中的偏移
+    pub fn new(buf: &'a mut [u8], encode: Encode) -> Self {
+        MappedObject {
+            bytes: Cell::from_mut(buf).as_slice_of_cells(),
+            encode,
+            map: None,
+        }
+    }
+
+    /// 设置偏移映射：字段的起始偏移 `Field::range().start` 经过 map 后得到其在 buf 中的起始位置，
+    /// 字段本身的字节仍然是连续的
+    pub fn with_offset_map(mut self, map: impl Fn(usize) -> usize + 'a) -> Self {
+        self.map = Some(Box::new(map));
+        self
+    }
+
+    /// 字段 T 在缓冲区中的位置，映射后超出缓冲区时返回 `ElfError::OutOfBounds`
+    fn cells<T: Field>(&self) -> Result<&'a [Cell<u8>], ElfError> {
+        let range = T::range();
+        let start = match &self.map {
+            Some(map) => map(range.start),
+            None => range.start,
+        };
+        let end = start.checked_add(range.len()).ok_or(ElfError::Overflow)?;
+        checked_cells(self.bytes, start..end)
+    }
+
+    /// 与 `Getter::get` 相同，映射后的字段超出缓冲区时返回错误
+    pub fn try_get<T: Field>(&self) -> Result<T::FieldType, ElfError> {
+        Ok(read_cells(self.cells::<T>()?, |b| {
+            read_field::<T>(b, self.encode)
+        }))
+    }
+
+    /// 与 `Setter::with` 相同，映射后的字段超出缓冲区时返回错误且不做修改
+    pub fn try_with<T: Field + Mutable>(&self, value: T::FieldType) -> Result<&Self, ElfError> {
+        write_cells::<T>(self.cells::<T>()?, value, self.encode);
+        Ok(self)
+    }
+}
+
+impl<'a> Getter for MappedObject<'a> {
+    /// 映射后的字段超出缓冲区时 panic，偏移映射不可信时应使用 [`MappedObject::try_get`]
+    fn get<T: Field>(&self) -> T::FieldType {
+        self.try_get::<T>().expect("映射后的字段超出缓冲区")
+    }
+}
+
+impl<'a> Setter for MappedObject<'a> {
+    /// 映射后的字段超出缓冲区时 panic，偏移映射不可信时应使用 [`MappedObject::try_with`]
+    fn with<T: Field + Mutable>(&self, value: T::FieldType) -> &Self {
+        self.try_with::<T>(value).expect("映射后的字段超出缓冲区")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{EEntry, EType};
+    use crate::ObjectType;
+
+    #[test]
+    fn shifted_header() {
+        // 容器在每 16 字节的数据之前插入 4 字节的元数据
+        let shifted = |off: usize| off + (off / 16 + 1) * 4;
+        let mut buf = vec![0u8; 96];
+        buf[shifted(16)..shifted(16) + 2].copy_from_slice(&3u16.to_le_bytes());
+        buf[shifted(24)..shifted(24) + 8].copy_from_slice(&0x4010u64.to_le_bytes());
+        {
+            let ehdr = MappedObject::new(&mut buf, Encode::Le).with_offset_map(shifted);
+            assert_eq!(ehdr.get::<EType>(), ObjectType::Dyn);
+            assert_eq!(ehdr.get::<EEntry>(), 0x4010);
+            ehdr.with::<EEntry>(0x5000);
+        }
+        assert_eq!(&buf[32..40], &0x5000u64.to_le_bytes());
+
+        let plain = MappedObject::new(&mut buf, Encode::Le);
+        assert_eq!(plain.get::<EType>(), ObjectType::None);
+    }
+
+    #[test]
+    fn map_out_of_bounds() {
+        let mut buf = vec![0u8; 40];
+        let ehdr = MappedObject::new(&mut buf, Encode::Le).with_offset_map(|off| off + 16);
+        assert_eq!(ehdr.try_get::<EType>(), Ok(ObjectType::None));
+        assert_eq!(ehdr.try_get::<EEntry>(), Err(ElfError::OutOfBounds(40..48)));
+        assert!(ehdr.try_with::<EEntry>(1).is_err());
+
+        let mut small = [0u8; 8];
+        let wrapped = MappedObject::new(&mut small, Encode::Le).with_offset_map(|_| usize::MAX);
+        assert_eq!(wrapped.try_get::<EType>(), Err(ElfError::Overflow));
+    }
+}
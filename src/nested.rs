@@ -0,0 +1,202 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 嵌套对象的范围：符号位于符号表 section 中，section 又位于文件中。
+//!
+//! 每个对象只记录相对于父对象的范围，绝对范围在需要时逐级计算，
+//! 避免在各处手工累加偏移。
+
+use crate::field::Located;
+use crate::view::{checked_cells, read_cells, write_cells};
+use crate::{read_field, ElfError};
+use accessor::*;
+use std::cell::Cell;
+use std::ops::Range;
+
+/// 相对于父对象定位的对象
+pub trait Nested {
+    /// 相对于父对象的范围
+    fn local_range(&self) -> Range<usize>;
+    /// 父对象的绝对范围，None 表示没有父对象，此时 local_range 即为绝对范围
+    fn parent_range(&self) -> Option<Range<usize>>;
+    fn set_parent_range(&mut self, parent: Range<usize>);
+
+    /// 绝对范围为 `parent.start + local`，并截断到父对象的范围内
+    fn absolute_range(&self) -> Range<usize> {
+        let local = self.local_range();
+        match self.parent_range() {
+            Some(parent) => {
+                let clamp = |off: usize| parent.start.saturating_add(off).min(parent.end);
+                clamp(local.start)..clamp(local.end)
+            }
+            None => local,
+        }
+    }
+}
+
+impl<N: Nested> Located for N {
+    fn get_range(&self) -> Range<usize> {
+        self.absolute_range()
+    }
+}
+
+/// 记录相对范围与父对象范围的 [`Nested`] 实现
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NestedRange {
+    local: Range<usize>,
+    parent: Option<Range<usize>>,
+}
+
+impl NestedRange {
+    pub fn new(local: Range<usize>) -> Self {
+        NestedRange {
+            local,
+            parent: None,
+        }
+    }
+}
+
+impl Nested for NestedRange {
+    fn local_range(&self) -> Range<usize> {
+        self.local.clone()
+    }
+    fn parent_range(&self) -> Option<Range<usize>> {
+        self.parent.clone()
+    }
+    fn set_parent_range(&mut self, parent: Range<usize>) {
+        self.parent = Some(parent);
+    }
+}
+
+/// 借用整个文件缓冲区、按绝对范围读写字段的嵌套对象
+pub struct NestedObject<'a> {
+    buf: &'a [Cell<u8>],
+    range: NestedRange,
+    encode: Encode,
+}
+
+impl<'a> NestedObject<'a> {
+    /// buf 为整个文件，local 为相对于父对象的范围
+    pub fn new(buf: &'a [Cell<u8>], local: Range<usize>, encode: Encode) -> Self {
+        NestedObject {
+            buf,
+            range: NestedRange::new(local),
+            encode,
+        }
+    }
+
+    /// 以 self 为父对象，创建相对于 self 定位的子对象
+    pub fn child(&self, local: Range<usize>) -> Self {
+        let mut child = NestedObject::new(self.buf, local, self.encode);
+        child.set_parent_range(self.absolute_range());
+        child
+    }
+
+    /// 字段 T 在缓冲区中的位置，字段超出对象（被父对象截断后）的范围或超出缓冲区时返回错误
+    fn cells<T: Field>(&self) -> Result<&'a [Cell<u8>], ElfError> {
+        let abs = self.absolute_range();
+        let object = checked_cells(self.buf, abs)?;
+        checked_cells(object, T::range())
+    }
+
+    /// 与 `Getter::get` 相同，字段超出对象或缓冲区时返回错误
+    pub fn try_get<T: Field>(&self) -> Result<T::FieldType, ElfError> {
+        Ok(read_cells(self.cells::<T>()?, |b| {
+            read_field::<T>(b, self.encode)
+        }))
+    }
+
+    /// 与 `Setter::with` 相同，字段超出对象或缓冲区时返回错误且不做修改
+    pub fn try_with<T: Field + Mutable>(&self, value: T::FieldType) -> Result<&Self, ElfError> {
+        write_cells::<T>(self.cells::<T>()?, value, self.encode);
+        Ok(self)
+    }
+}
+
+impl<'a> Nested for NestedObject<'a> {
+    fn local_range(&self) -> Range<usize> {
+        self.range.local_range()
+    }
+    fn parent_range(&self) -> Option<Range<usize>> {
+        self.range.parent_range()
+    }
+    fn set_parent_range(&mut self, parent: Range<usize>) {
+        self.range.set_parent_range(parent)
+    }
+}
+
+impl<'a> Getter for NestedObject<'a> {
+    /// 字段超出对象或缓冲区时 panic，不希望 panic 时应使用 [`NestedObject::try_get`]
+    fn get<T: Field>(&self) -> T::FieldType {
+        self.try_get::<T>().expect("字段超出对象范围")
+    }
+}
+
+impl<'a> Setter for NestedObject<'a> {
+    /// 字段超出对象或缓冲区时 panic，不希望 panic 时应使用 [`NestedObject::try_with`]
+    fn with<T: Field + Mutable>(&self, value: T::FieldType) -> &Self {
+        self.try_with::<T>(value).expect("字段超出对象范围")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{StInfo, StName};
+
+    #[test]
+    fn symbol_in_section_in_file() {
+        let mut buf = vec![0u8; 0x200];
+        let cells = Cell::from_mut(&mut buf[..]).as_slice_of_cells();
+        let file = NestedObject::new(cells, 0..0x200, Encode::Le);
+        let symtab = file.child(0x100..0x160);
+        let sym = symtab.child(2 * 24..3 * 24);
+        assert_eq!(sym.absolute_range(), 0x130..0x148);
+        assert_eq!(sym.field_range::<StName>(), 0x130..0x134);
+
+        sym.with::<StName>(0x1234);
+        assert_eq!(sym.get::<StName>(), 0x1234);
+        assert_eq!(&buf[0x130..0x134], &0x1234u32.to_le_bytes());
+
+        // 超出父对象的部分被截断
+        let mut tail = NestedRange::new(0x50..0x68);
+        tail.set_parent_range(0x100..0x160);
+        assert_eq!(tail.absolute_range(), 0x150..0x160);
+    }
+
+    #[test]
+    fn out_of_range_fields() {
+        let mut buf = vec![0u8; 0x200];
+        let cells = Cell::from_mut(&mut buf[..]).as_slice_of_cells();
+        let file = NestedObject::new(cells, 0..0x200, Encode::Le);
+        let symtab = file.child(0x100..0x160);
+
+        // 被父对象截断的符号与超出文件的对象都通过错误报告
+        let cut = symtab.child(0x58..0x70);
+        assert_eq!(cut.try_get::<StName>(), Ok(0));
+        assert_eq!(
+            cut.child(4..8).try_get::<StInfo>(),
+            Err(ElfError::OutOfBounds(4..5))
+        );
+        let short = NestedObject::new(cells, 0x1fe..0x220, Encode::Le);
+        assert_eq!(
+            short.try_get::<StName>(),
+            Err(ElfError::OutOfBounds(0x1fe..0x220))
+        );
+        assert!(short.try_with::<StName>(1).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 将可重定位文件改写为规范形式，保证输出可按字节复现。
+
+use crate::codec::Codec;
+use crate::header::SHN_LORESERVE;
+use crate::owned::{OwnedElf, OwnedShdr};
+use crate::util::{align_up, field_align_up};
+use crate::{strtab, Class, ElfError, SectionFlags, SectionType};
+
+impl OwnedElf {
+    /// 将文件改写为规范形式，并把结果写入 buf。
+    ///
+    /// 规范形式为：
+    /// - 除 0 号 section 外，所有 section 按（名称，类型，内容）排序；
+    /// - section 依次紧密排列，只保留 sh_addralign 要求的最小对齐，填充字节全部为 0；
+    /// - 重新生成 section 名称字符串表，并修正 sh_link、sh_info、符号的 st_shndx 与 section 组中的索引；
+    /// - Section Header Table 紧跟在最后一个 section 之后。
+    ///
+    /// 该操作是幂等的，对结果再次调用会得到完全相同的字节。
+    /// 带有 Program Header 的文件的布局由 segment 决定，无法重排，返回 `ElfError::Unsupported`。
+    pub fn normalize(&mut self, buf: &mut Vec<u8>) -> Result<(), ElfError> {
+        if !self.phdrs.is_empty() {
+            return Err(ElfError::Unsupported(
+                "normalize on file with program headers",
+            ));
+        }
+        let c = self.codec()?;
+        let count = self.shdrs.len();
+        let shstrndx = self.shstrndx();
+        let names: Vec<String> = (0..count)
+            .map(|i| self.section_name(i).unwrap_or("").to_string())
+            .collect();
+        let datas: Vec<Vec<u8>> = (0..count)
+            .map(|i| self.section_data(i).unwrap_or(&[]).to_vec())
+            .collect();
+
+        let mut order: Vec<usize> = (1..count).collect();
+        order.sort_by(|&a, &b| {
+            (&names[a], self.shdrs[a].sh_type, &datas[a]).cmp(&(
+                &names[b],
+                self.shdrs[b].sh_type,
+                &datas[b],
+            ))
+        });
+        if count > 0 {
+            order.insert(0, 0);
+        }
+        let mut new_index = vec![0usize; count];
+        for (new, &old) in order.iter().enumerate() {
+            new_index[old] = new;
+        }
+        let remap = |idx: u64| -> u64 {
+            if idx != 0 && idx < u64::from(SHN_LORESERVE) && (idx as usize) < count {
+                new_index[idx as usize] as u64
+            } else {
+                idx
+            }
+        };
+
+        // 重新生成 section 名称字符串表
+        let regen_shstrtab = shstrndx != 0 && shstrndx < count;
+        let mut shstrtab = vec![0u8];
+        let mut name_off = vec![0u32; count];
+        for &old in order.iter().skip(1) {
+            let name = names[old].as_bytes();
+            name_off[old] = match strtab::find_str(&shstrtab, name) {
+                Some(off) => off as u32,
+                None => {
+                    let off = shstrtab.len();
+                    shstrtab.extend_from_slice(name);
+                    shstrtab.push(0);
+                    off as u32
+                }
+            };
+        }
+
+        let mut out = vec![0u8; c.class.ehdr_size()];
+        let mut shdrs = Vec::with_capacity(count);
+        for &old in order.iter() {
+            let mut shdr: OwnedShdr = self.shdrs[old];
+            if old == 0 {
+                shdrs.push(shdr);
+                continue;
+            }
+            let mut data = if regen_shstrtab && old == shstrndx {
+                shstrtab.clone()
+            } else {
+                datas[old].clone()
+            };
+            let sh_type = SectionType::from(shdr.sh_type);
+            match sh_type {
+                SectionType::Symtab | SectionType::Dynsym => remap_symbols(c, &mut data, &remap),
+                SectionType::Group => {
+                    for word in data.chunks_exact_mut(4).skip(1) {
+                        let idx = c.uint(word);
+                        c.put_uint(word, remap(idx));
+                    }
+                }
+                _ => {}
+            }
+            if regen_shstrtab {
+                shdr.sh_name = name_off[old];
+            }
+            shdr.sh_link = remap(shdr.sh_link as u64) as u32;
+            if matches!(sh_type, SectionType::Rel | SectionType::Rela)
+                || SectionFlags::from(shdr.sh_flags).contains(SectionFlags::INFO_LINK)
+            {
+                shdr.sh_info = remap(shdr.sh_info as u64) as u32;
+            }
+            let start = field_align_up(out.len(), shdr.sh_addralign)?;
+            out.resize(start, 0);
+            shdr.sh_offset = start as u64;
+            if sh_type != SectionType::Nobits {
+                shdr.sh_size = data.len() as u64;
+                out.extend_from_slice(&data);
+            }
+            shdrs.push(shdr);
+        }
+
+        let shoff = align_up(out.len(), c.class.word_size());
+        out.resize(shoff, 0);
+        let mut ehdr = self.ehdr;
+        ehdr.e_phoff = 0;
+        ehdr.e_phentsize = 0;
+        ehdr.e_phnum = 0;
+        ehdr.e_ehsize = c.class.ehdr_size() as u16;
+        ehdr.e_shentsize = c.class.shdr_size() as u16;
+        ehdr.e_shoff = if count == 0 { 0 } else { shoff as u64 };
+        ehdr.e_shstrndx = remap(shstrndx as u64) as u16;
+
+        self.ehdr = ehdr;
+        self.shdrs = shdrs;
+        self.data = out;
+        *buf = self.to_bytes()?;
+        self.data = buf.clone();
+        Ok(())
+    }
+}
+
+/// 修正符号表中每个符号的 st_shndx
+fn remap_symbols(c: Codec, data: &mut [u8], remap: &dyn Fn(u64) -> u64) {
+    let (entsize, shndx) = match c.class {
+        Class::Elf32 => (16, 14),
+        Class::Elf64 => (24, 6),
+    };
+    for sym in data.chunks_exact_mut(entsize) {
+        let field = &mut sym[shndx..shndx + 2];
+        let idx = c.uint(field);
+        c.put_uint(field, remap(idx));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::owned::*;
+    use crate::test_util::elf64_ident;
+    use crate::ElfError;
+
+    /// 构造一个 section 顺序混乱、填充字节非零的 ELF64 可重定位文件
+    fn sample() -> Vec<u8> {
+        let mut data = vec![0xaau8; 0x200];
+        let shstrtab = b"\0.text\0.shstrtab\0.data\0.symtab\0";
+        data[0x40..0x40 + shstrtab.len()].copy_from_slice(shstrtab);
+        data[0x70..0x75].copy_from_slice(&[0x55, 0x48, 0x89, 0xe5, 0xc3]);
+        data[0x90..0x93].copy_from_slice(&[1, 2, 3]);
+        // 两个符号：null 与指向 .text（索引 2）的符号
+        let mut sym = [0u8; 24];
+        sym[6] = 2;
+        data[0xa0..0xb8].copy_from_slice(&[0u8; 24]);
+        data[0xb8..0xd0].copy_from_slice(&sym);
+        let shdr = |name, ty, off, size, align| OwnedShdr {
+            sh_name: name,
+            sh_type: ty,
+            sh_offset: off,
+            sh_size: size,
+            sh_addralign: align,
+            ..Default::default()
+        };
+        let mut symtab = shdr(23, 2, 0xa0, 48, 8);
+        symtab.sh_entsize = 24;
+        symtab.sh_info = 1;
+        let elf = OwnedElf {
+            ehdr: OwnedEhdr {
+                ident: elf64_ident(),
+                e_type: 1,
+                e_machine: 62,
+                e_version: 1,
+                e_shoff: 0x100,
+                e_ehsize: 64,
+                e_shentsize: 64,
+                e_shnum: 5,
+                e_shstrndx: 1,
+                ..Default::default()
+            },
+            phdrs: Vec::new(),
+            shdrs: vec![
+                OwnedShdr::default(),
+                shdr(7, 3, 0x40, shstrtab.len() as u64, 1),
+                shdr(1, 1, 0x70, 5, 16),
+                shdr(17, 1, 0x90, 3, 4),
+                symtab,
+            ],
+            data,
+        };
+        elf.to_bytes().unwrap()
+    }
+
+    #[test]
+    fn normalize_idempotent() {
+        let mut elf = OwnedElf::parse(&sample()).unwrap();
+        let mut first = Vec::new();
+        elf.normalize(&mut first).unwrap();
+        let mut again = OwnedElf::parse(&first).unwrap();
+        let mut second = Vec::new();
+        again.normalize(&mut second).unwrap();
+        assert_eq!(first, second);
+
+        let names: Vec<_> = (0..again.shdrs.len())
+            .map(|i| again.section_name(i).unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["", ".data", ".shstrtab", ".symtab", ".text"]);
+        assert_eq!(
+            again.section_data(4).unwrap(),
+            &[0x55, 0x48, 0x89, 0xe5, 0xc3]
+        );
+        // 符号的 st_shndx 跟随 .text 移动到了 4
+        assert_eq!(again.section_data(3).unwrap()[24 + 6], 4);
+        assert_eq!(again.shdrs[4].sh_offset % 16, 0);
+        assert!(first.len() < sample().len());
+    }
+
+    #[test]
+    fn normalize_rejects_bad_alignment() {
+        let mut elf = OwnedElf::parse(&sample()).unwrap();
+        elf.shdrs[1].sh_addralign = 24;
+        let mut out = Vec::new();
+        assert!(matches!(
+            elf.normalize(&mut out),
+            Err(ElfError::Malformed(_))
+        ));
+    }
+}
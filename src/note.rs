@@ -0,0 +1,319 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Note section/segment 的解析。
+//!
+//! Note 项的名称与描述符长度可变，无法用定长字段描述，因此以 [`Note`] trait 统一访问，
+//! [`NoteIter`] 从原始字节中依次切分出各项。
+
+use crate::codec::Codec;
+use crate::util::checked_align_up;
+use crate::Class;
+use accessor::Encode;
+
+pub const NT_GNU_ABI_TAG: u32 = 1;
+pub const NT_GNU_BUILD_ID: u32 = 3;
+pub const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc000_0000;
+pub const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc000_0002;
+
+/// Note 项需要实现的 trait
+pub trait Note {
+    fn n_type(&self) -> u32;
+    /// 名称，不含结尾的 NUL
+    fn name(&self) -> &[u8];
+    /// 描述符
+    fn desc(&self) -> &[u8];
+    /// 解码描述符所用的字节序
+    fn encode(&self) -> Encode;
+
+    /// 描述符中的第 idx 个 4 字节字
+    fn desc_word(&self, idx: usize) -> Option<u32> {
+        let c = Codec::new(Class::Elf32, self.encode());
+        c.read(self.desc(), idx * 4, 4).ok().map(|v| v as u32)
+    }
+}
+
+/// 由 [`NoteIter`] 切分出的 note 项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteEntry<'a> {
+    pub n_type: u32,
+    pub name: &'a [u8],
+    pub desc: &'a [u8],
+    pub encode: Encode,
+}
+
+impl<'a> Note for NoteEntry<'a> {
+    fn n_type(&self) -> u32 {
+        self.n_type
+    }
+    fn name(&self) -> &[u8] {
+        self.name
+    }
+    fn desc(&self) -> &[u8] {
+        self.desc
+    }
+    fn encode(&self) -> Encode {
+        self.encode
+    }
+}
+
+/// 遍历 note section 或 PT_NOTE segment 中的所有项，遇到截断的项时停止
+pub struct NoteIter<'a> {
+    data: &'a [u8],
+    codec: Codec,
+    align: usize,
+}
+
+impl<'a> NoteIter<'a> {
+    /// align 为名称与描述符的对齐，通常取 section 的 sh_addralign（4 或 8），
+    /// 小于 4 或不是 2 的幂时按 4 处理
+    pub fn new(data: &'a [u8], encode: Encode, align: usize) -> Self {
+        NoteIter {
+            data,
+            codec: Codec::new(Class::Elf32, encode),
+            align: if align.is_power_of_two() {
+                align.max(4)
+            } else {
+                4
+            },
+        }
+    }
+
+    fn pad(&self, len: usize) -> Option<usize> {
+        checked_align_up(len, self.align)
+    }
+}
+
+impl<'a> Iterator for NoteIter<'a> {
+    type Item = NoteEntry<'a>;
+    fn next(&mut self) -> Option<NoteEntry<'a>> {
+        let word = |off| self.codec.read(self.data, off, 4).ok().map(|v| v as usize);
+        let namesz = word(0)?;
+        let descsz = word(4)?;
+        let n_type = word(8)? as u32;
+        let desc_start = self.pad(12usize.checked_add(namesz)?)?;
+        let desc_end = desc_start.checked_add(descsz)?;
+        let name = self.data.get(12..12 + namesz)?;
+        let desc = self.data.get(desc_start..desc_end)?;
+        let next = self.pad(desc_end)?.min(self.data.len());
+        let entry = NoteEntry {
+            n_type,
+            name: name.strip_suffix(&[0]).unwrap_or(name),
+            desc,
+            encode: self.codec.encode,
+        };
+        self.data = &self.data[next..];
+        Some(entry)
+    }
+}
+
+/// `.note.ABI-tag` 中的操作系统
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbiOs {
+    Linux,
+    Hurd,
+    Solaris,
+    FreeBsd,
+    NetBsd,
+    Syllable,
+    Other(u32),
+}
+
+impl From<u32> for AbiOs {
+    fn from(val: u32) -> Self {
+        match val {
+            0 => AbiOs::Linux,
+            1 => AbiOs::Hurd,
+            2 => AbiOs::Solaris,
+            3 => AbiOs::FreeBsd,
+            4 => AbiOs::NetBsd,
+            5 => AbiOs::Syllable,
+            other => AbiOs::Other(other),
+        }
+    }
+}
+
+/// `.note.ABI-tag` 的内容：目标操作系统与最低内核版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiTag {
+    pub os: AbiOs,
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+/// 在 notes 中查找名称为 "GNU"、类型为 NT_GNU_ABI_TAG 的项并解码
+pub fn abi_tag<N: Note>(notes: impl IntoIterator<Item = N>) -> Option<AbiTag> {
+    notes
+        .into_iter()
+        .find(|n| n.name() == b"GNU" && n.n_type() == NT_GNU_ABI_TAG)
+        .and_then(|n| {
+            Some(AbiTag {
+                os: n.desc_word(0)?.into(),
+                major: n.desc_word(1)?,
+                minor: n.desc_word(2)?,
+                patch: n.desc_word(3)?,
+            })
+        })
+}
+
+/// `.note.gnu.property`（PT_GNU_PROPERTY）中的一项属性
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GnuProperty {
+    /// Intel CET：间接跳转跟踪与影子栈
+    X86Features { ibt: bool, shstk: bool },
+    /// AArch64 的分支目标识别与指针认证
+    Aarch64Features { bti: bool, pac: bool },
+    /// 其他属性，data 为未解码的 pr_data
+    Other { ty: u32, data: Vec<u8> },
+}
+
+/// 解码 notes 中所有名称为 "GNU"、类型为 NT_GNU_PROPERTY_TYPE_0 的项的属性数组。
+///
+/// 每项属性为 `pr_type: u32, pr_datasz: u32, pr_data`，pr_data 填充到 ELF64 的 8 字节
+/// 或 ELF32 的 4 字节边界，因此需要传入 class。遇到截断的属性时跳过该 note 余下的部分。
+pub fn gnu_properties<N: Note>(
+    notes: impl IntoIterator<Item = N>,
+    class: Class,
+) -> Vec<GnuProperty> {
+    let mut props = Vec::new();
+    for note in notes {
+        if note.name() != b"GNU" || note.n_type() != NT_GNU_PROPERTY_TYPE_0 {
+            continue;
+        }
+        let c = Codec::new(Class::Elf32, note.encode());
+        let desc = note.desc();
+        let mut off = 0;
+        while let (Ok(ty), Ok(size)) = (c.read(desc, off, 4), c.read(desc, off + 4, 4)) {
+            let (ty, size) = (ty as u32, size as usize);
+            let data = match desc.get(off + 8..).and_then(|rest| rest.get(..size)) {
+                Some(data) => data,
+                None => break,
+            };
+            let bits = c.read(data, 0, 4).unwrap_or(0);
+            props.push(match ty {
+                GNU_PROPERTY_X86_FEATURE_1_AND if size == 4 => GnuProperty::X86Features {
+                    ibt: bits & 1 != 0,
+                    shstk: bits & 2 != 0,
+                },
+                GNU_PROPERTY_AARCH64_FEATURE_1_AND if size == 4 => GnuProperty::Aarch64Features {
+                    bti: bits & 1 != 0,
+                    pac: bits & 2 != 0,
+                },
+                _ => GnuProperty::Other {
+                    ty,
+                    data: data.to_vec(),
+                },
+            });
+            off = match checked_align_up(off + 8 + size, class.word_size()) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+    props
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_abi_tag() {
+        let mut data = Vec::new();
+        for word in &[4u32, 16, NT_GNU_ABI_TAG] {
+            data.extend_from_slice(&word.to_be_bytes());
+        }
+        data.extend_from_slice(b"GNU\0");
+        for word in &[0u32, 3, 2, 0] {
+            data.extend_from_slice(&word.to_be_bytes());
+        }
+        let notes = NoteIter::new(&data, Encode::Be, 4);
+        assert_eq!(
+            abi_tag(notes),
+            Some(AbiTag {
+                os: AbiOs::Linux,
+                major: 3,
+                minor: 2,
+                patch: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn cet_and_unknown_properties() {
+        let mut desc = Vec::new();
+        for word in &[GNU_PROPERTY_X86_FEATURE_1_AND, 4, 0b11, 0] {
+            desc.extend_from_slice(&word.to_le_bytes());
+        }
+        for word in &[0xc000_8002u32, 8, 0x1234, 0x5678] {
+            desc.extend_from_slice(&word.to_le_bytes());
+        }
+        let note = NoteEntry {
+            n_type: NT_GNU_PROPERTY_TYPE_0,
+            name: b"GNU",
+            desc: &desc,
+            encode: Encode::Le,
+        };
+        let props = gnu_properties(vec![note], Class::Elf64);
+        assert_eq!(props.len(), 2);
+        assert_eq!(
+            props[0],
+            GnuProperty::X86Features {
+                ibt: true,
+                shstk: true
+            }
+        );
+        match &props[1] {
+            GnuProperty::Other { ty, data } => {
+                assert_eq!(*ty, 0xc000_8002);
+                assert_eq!(data.len(), 8);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // ELF32 只填充到 4 字节，第一项之后紧跟下一项
+        let mut desc = Vec::new();
+        for word in &[GNU_PROPERTY_AARCH64_FEATURE_1_AND, 4, 0b01] {
+            desc.extend_from_slice(&word.to_be_bytes());
+        }
+        for word in &[GNU_PROPERTY_AARCH64_FEATURE_1_AND, 4, 0b10] {
+            desc.extend_from_slice(&word.to_be_bytes());
+        }
+        let note = NoteEntry {
+            n_type: NT_GNU_PROPERTY_TYPE_0,
+            name: b"GNU",
+            desc: &desc,
+            encode: Encode::Be,
+        };
+        assert_eq!(
+            gnu_properties(vec![note], Class::Elf32),
+            [
+                GnuProperty::Aarch64Features {
+                    bti: true,
+                    pac: false
+                },
+                GnuProperty::Aarch64Features {
+                    bti: false,
+                    pac: true
+                },
+            ]
+        );
+    }
+}
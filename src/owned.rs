@@ -0,0 +1,572 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 与缓冲区无关的 Elf 数据结构，字段均已解码为原生整数，便于整体修改后重新序列化。
+
+use crate::codec::Codec;
+use crate::elf::{read_phdrs, read_shdrs, HeaderCounts};
+use crate::header::SHN_XINDEX;
+use crate::util::clamped_slice;
+use crate::{strtab, Class, ElfError, IdentInfo, ParseLimits, SectionType};
+use accessor::Encode;
+use std::convert::TryFrom;
+
+/// 已解码的 Elf Header
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedEhdr {
+    pub ident: [u8; 16],
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_version: u32,
+    pub e_entry: u64,
+    pub e_phoff: u64,
+    pub e_shoff: u64,
+    pub e_flags: u32,
+    pub e_ehsize: u16,
+    pub e_phentsize: u16,
+    pub e_phnum: u16,
+    pub e_shentsize: u16,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+/// 已解码的 Section Header
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedShdr {
+    pub sh_name: u32,
+    pub sh_type: u32,
+    pub sh_flags: u64,
+    pub sh_addr: u64,
+    pub sh_offset: u64,
+    pub sh_size: u64,
+    pub sh_link: u32,
+    pub sh_info: u32,
+    pub sh_addralign: u64,
+    pub sh_entsize: u64,
+}
+
+/// 已解码的 Program Header
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedPhdr {
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_offset: u64,
+    pub p_vaddr: u64,
+    pub p_paddr: u64,
+    pub p_filesz: u64,
+    pub p_memsz: u64,
+    pub p_align: u64,
+}
+
+/// 已解码的符号
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedSym {
+    pub st_name: u32,
+    pub st_info: u8,
+    pub st_other: u8,
+    pub st_shndx: u16,
+    pub st_value: u64,
+    pub st_size: u64,
+}
+
+impl OwnedEhdr {
+    /// 从 ident 中读取 Class 与 Encode
+    pub(crate) fn codec(ident: &[u8]) -> Result<Codec, ElfError> {
+        let info = IdentInfo::parse(ident)?;
+        Ok(Codec::new(info.class, info.data))
+    }
+
+    pub(crate) fn parse(c: Codec, buf: &[u8]) -> Result<Self, ElfError> {
+        let mut ident = [0u8; 16];
+        ident.copy_from_slice(buf.get(0..16).ok_or(ElfError::OutOfBounds(0..16))?);
+        let mut r = c.reader(buf, 16);
+        Ok(OwnedEhdr {
+            ident,
+            e_type: r.u16()?,
+            e_machine: r.u16()?,
+            e_version: r.u32()?,
+            e_entry: r.word()?,
+            e_phoff: r.word()?,
+            e_shoff: r.word()?,
+            e_flags: r.u32()?,
+            e_ehsize: r.u16()?,
+            e_phentsize: r.u16()?,
+            e_phnum: r.u16()?,
+            e_shentsize: r.u16()?,
+            e_shnum: r.u16()?,
+            e_shstrndx: r.u16()?,
+        })
+    }
+
+    pub(crate) fn write(&self, c: Codec, buf: &mut [u8]) {
+        buf[0..16].copy_from_slice(&self.ident);
+        let mut w = c.writer(buf, 16);
+        w.u16(self.e_type);
+        w.u16(self.e_machine);
+        w.u32(self.e_version);
+        w.word(self.e_entry);
+        w.word(self.e_phoff);
+        w.word(self.e_shoff);
+        w.u32(self.e_flags);
+        w.u16(self.e_ehsize);
+        w.u16(self.e_phentsize);
+        w.u16(self.e_phnum);
+        w.u16(self.e_shentsize);
+        w.u16(self.e_shnum);
+        w.u16(self.e_shstrndx);
+    }
+}
+
+impl OwnedShdr {
+    pub(crate) fn parse(c: Codec, buf: &[u8], off: usize) -> Result<Self, ElfError> {
+        let mut r = c.reader(buf, off);
+        Ok(OwnedShdr {
+            sh_name: r.u32()?,
+            sh_type: r.u32()?,
+            sh_flags: r.word()?,
+            sh_addr: r.word()?,
+            sh_offset: r.word()?,
+            sh_size: r.word()?,
+            sh_link: r.u32()?,
+            sh_info: r.u32()?,
+            sh_addralign: r.word()?,
+            sh_entsize: r.word()?,
+        })
+    }
+
+    pub(crate) fn write(&self, c: Codec, buf: &mut [u8], off: usize) {
+        let mut w = c.writer(buf, off);
+        w.u32(self.sh_name);
+        w.u32(self.sh_type);
+        w.word(self.sh_flags);
+        w.word(self.sh_addr);
+        w.word(self.sh_offset);
+        w.word(self.sh_size);
+        w.u32(self.sh_link);
+        w.u32(self.sh_info);
+        w.word(self.sh_addralign);
+        w.word(self.sh_entsize);
+    }
+
+    /// section 在文件中占据的范围，SHT_NOBITS 为空
+    pub fn file_range(&self) -> std::ops::Range<usize> {
+        let start = self.sh_offset as usize;
+        if self.sh_type == SectionType::Nobits.raw() {
+            start..start
+        } else {
+            start..start.saturating_add(self.sh_size as usize)
+        }
+    }
+}
+
+impl OwnedPhdr {
+    pub(crate) fn parse(c: Codec, buf: &[u8], off: usize) -> Result<Self, ElfError> {
+        let mut r = c.reader(buf, off);
+        let mut phdr = OwnedPhdr {
+            p_type: r.u32()?,
+            ..Default::default()
+        };
+        if c.class == Class::Elf64 {
+            phdr.p_flags = r.u32()?;
+        }
+        phdr.p_offset = r.word()?;
+        phdr.p_vaddr = r.word()?;
+        phdr.p_paddr = r.word()?;
+        phdr.p_filesz = r.word()?;
+        phdr.p_memsz = r.word()?;
+        if c.class == Class::Elf32 {
+            phdr.p_flags = r.u32()?;
+        }
+        phdr.p_align = r.word()?;
+        Ok(phdr)
+    }
+
+    pub(crate) fn write(&self, c: Codec, buf: &mut [u8], off: usize) {
+        let mut w = c.writer(buf, off);
+        w.u32(self.p_type);
+        if c.class == Class::Elf64 {
+            w.u32(self.p_flags);
+        }
+        w.word(self.p_offset);
+        w.word(self.p_vaddr);
+        w.word(self.p_paddr);
+        w.word(self.p_filesz);
+        w.word(self.p_memsz);
+        if c.class == Class::Elf32 {
+            w.u32(self.p_flags);
+        }
+        w.word(self.p_align);
+    }
+
+    /// segment 在文件中占据的范围
+    pub fn file_range(&self) -> std::ops::Range<usize> {
+        let start = self.p_offset as usize;
+        start..start.saturating_add(self.p_filesz as usize)
+    }
+}
+
+impl OwnedSym {
+    pub fn size(class: Class) -> usize {
+        match class {
+            Class::Elf32 => 16,
+            Class::Elf64 => 24,
+        }
+    }
+
+    pub(crate) fn parse(c: Codec, buf: &[u8], off: usize) -> Result<Self, ElfError> {
+        let mut r = c.reader(buf, off);
+        let mut sym = OwnedSym {
+            st_name: r.u32()?,
+            ..Default::default()
+        };
+        if c.class == Class::Elf32 {
+            sym.st_value = r.word()?;
+            sym.st_size = r.word()?;
+        }
+        let pos = off + if c.class == Class::Elf32 { 12 } else { 4 };
+        let info = buf
+            .get(pos..pos + 2)
+            .ok_or(ElfError::OutOfBounds(pos..pos + 2))?;
+        sym.st_info = info[0];
+        sym.st_other = info[1];
+        let mut r = c.reader(buf, pos + 2);
+        sym.st_shndx = r.u16()?;
+        if c.class == Class::Elf64 {
+            sym.st_value = r.word()?;
+            sym.st_size = r.word()?;
+        }
+        Ok(sym)
+    }
+
+    pub(crate) fn write(&self, c: Codec, buf: &mut [u8], off: usize) {
+        let mut w = c.writer(buf, off);
+        w.u32(self.st_name);
+        if c.class == Class::Elf32 {
+            w.word(self.st_value);
+            w.word(self.st_size);
+        }
+        let pos = off + if c.class == Class::Elf32 { 12 } else { 4 };
+        buf[pos] = self.st_info;
+        buf[pos + 1] = self.st_other;
+        let mut w = c.writer(buf, pos + 2);
+        w.u16(self.st_shndx);
+        if c.class == Class::Elf64 {
+            w.word(self.st_value);
+            w.word(self.st_size);
+        }
+    }
+
+    /// st_info 的高 4 位
+    pub fn bind(&self) -> u8 {
+        self.st_info >> 4
+    }
+
+    /// st_info 的低 4 位
+    pub fn sym_type(&self) -> u8 {
+        self.st_info & 0xf
+    }
+}
+
+/// 完整拥有数据的 Elf 文件，`data` 为整个文件的字节，各 header 为其解码后的副本。
+///
+/// 修改 header 后通过 [`OwnedElf::to_bytes`] 将 header 写回并得到新的文件内容。
+#[derive(Debug, Clone)]
+pub struct OwnedElf {
+    pub ehdr: OwnedEhdr,
+    pub phdrs: Vec<OwnedPhdr>,
+    pub shdrs: Vec<OwnedShdr>,
+    pub data: Vec<u8>,
+}
+
+impl OwnedElf {
+    /// 以默认的 [`ParseLimits`] 解析
+    pub fn parse(bytes: &[u8]) -> Result<Self, ElfError> {
+        Self::parse_with_limits(bytes, &ParseLimits::default())
+    }
+
+    /// 解析 bytes，program header 与 section header 的数量按 limits 限制。
+    ///
+    /// 数量使用扩展编号（e_shnum 为 0、e_phnum 为 PN_XNUM）时从 0 号 section header 中读取实际的值。
+    /// 截断时 ehdr 保持文件中的值，`phdrs`、`shdrs` 只包含前 limit 项。
+    pub fn parse_with_limits(bytes: &[u8], limits: &ParseLimits) -> Result<Self, ElfError> {
+        let c = OwnedEhdr::codec(bytes)?;
+        let ehdr = OwnedEhdr::parse(c, bytes)?;
+        let counts = HeaderCounts::read(c, &ehdr, bytes)?;
+        Ok(OwnedElf {
+            ehdr,
+            phdrs: read_phdrs(c, &ehdr, &counts, bytes, limits)?,
+            shdrs: read_shdrs(c, &ehdr, &counts, bytes, limits)?,
+            data: bytes.to_vec(),
+        })
+    }
+
+    /// section 字符串表的索引，e_shstrndx 为 SHN_XINDEX 时为 0 号 section 的 sh_link
+    pub fn shstrndx(&self) -> usize {
+        match (self.ehdr.e_shstrndx, self.shdrs.first()) {
+            (SHN_XINDEX, Some(first)) => first.sh_link as usize,
+            (idx, _) => idx as usize,
+        }
+    }
+
+    /// 从 ehdr.ident 中读取 Class 与 Encode。
+    ///
+    /// 字段都是公开的，手动构造或 `Default` 得到的 ident 可能无效，此时返回错误。
+    pub(crate) fn codec(&self) -> Result<Codec, ElfError> {
+        OwnedEhdr::codec(&self.ehdr.ident)
+    }
+
+    pub fn class(&self) -> Result<Class, ElfError> {
+        Ok(self.codec()?.class)
+    }
+
+    pub fn encode(&self) -> Result<Encode, ElfError> {
+        Ok(self.codec()?.encode)
+    }
+
+    /// 写回时使用的表项大小，为 0 时取 Class 对应的大小，小于该大小时返回 `ElfError::Malformed`
+    fn entsize(entsize: u16, size: usize) -> Result<usize, ElfError> {
+        match entsize as usize {
+            0 => Ok(size),
+            entsize if entsize < size => Err(ElfError::Malformed("entry size too small")),
+            entsize => Ok(entsize),
+        }
+    }
+
+    /// off 处共 num 项的表的结束位置，没有表项时为 0
+    fn table_end(off: u64, entsize: usize, num: usize) -> Result<usize, ElfError> {
+        if num == 0 {
+            return Ok(0);
+        }
+        usize::try_from(off)
+            .ok()
+            .and_then(|off| off.checked_add(num.checked_mul(entsize)?))
+            .ok_or(ElfError::Overflow)
+    }
+
+    /// 第 idx 个 section 的数据，SHT_NOBITS 返回空切片。
+    ///
+    /// section 不存在或数据超出文件时返回 `ElfError::OutOfBounds`，
+    /// 没有 section 的 core dump 返回 `ElfError::Unsupported`
+    pub fn section_data(&self, idx: usize) -> Result<&[u8], ElfError> {
+        let range = self.shdr(idx)?.file_range();
+        clamped_slice(&self.data, &range).ok_or(ElfError::OutOfBounds(range))
+    }
+
+    /// 通过 e_shstrndx 所指的字符串表获取第 idx 个 section 的名称，
+    /// 错误与 [`OwnedElf::section_data`] 相同，名称无效时返回 `ElfError::Malformed`
+    pub fn section_name(&self, idx: usize) -> Result<&str, ElfError> {
+        let strtab = self.section_data(self.shstrndx())?;
+        strtab::str_at(strtab, self.shdr(idx)?.sh_name as usize)
+            .ok_or(ElfError::Malformed("invalid section name"))
+    }
+
+    fn shdr(&self, idx: usize) -> Result<&OwnedShdr, ElfError> {
+        self.require_sections()?;
+        self.shdrs
+            .get(idx)
+            .ok_or(ElfError::OutOfBounds(idx..idx + 1))
+    }
+
+    /// 解析第 idx 个 section 中的符号，section 不存在或数据不完整时返回错误，
+    /// 没有 section 的 core dump（见 [`CoreFile`](crate::coredump::CoreFile)）返回 `ElfError::Unsupported`
+    pub fn symbols(&self, idx: usize) -> Result<Vec<OwnedSym>, ElfError> {
+        self.symbols_with_limits(idx, &ParseLimits::default())
+    }
+
+    /// 与 [`OwnedElf::symbols`] 相同，符号数量按 limits 中的 max_symbols 限制
+    pub fn symbols_with_limits(
+        &self,
+        idx: usize,
+        limits: &ParseLimits,
+    ) -> Result<Vec<OwnedSym>, ElfError> {
+        let range = self.shdr(idx)?.file_range();
+        let c = self.codec()?;
+        let size = OwnedSym::size(c.class);
+        let num = limits.cap("symbols", range.len() / size, limits.max_symbols)?;
+        (0..num)
+            .map(|i| OwnedSym::parse(c, &self.data, range.start + i * size))
+            .collect()
+    }
+
+    /// 第一个类型为 sh_type 的 section 的索引
+    pub fn find_section(&self, sh_type: u32) -> Option<usize> {
+        self.shdrs.iter().position(|shdr| shdr.sh_type == sh_type)
+    }
+
+    /// 将各 header 写回 data 后返回整个文件，header table 超出 data 时自动补零。
+    ///
+    /// ehdr.ident 无效时返回错误；非 0 的 e_phentsize、e_shentsize 小于 Class 对应的大小时返回
+    /// `ElfError::Malformed`，header table 的结束位置溢出时返回 `ElfError::Overflow`。
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ElfError> {
+        let c = self.codec()?;
+        let phentsize = Self::entsize(self.ehdr.e_phentsize, c.class.phdr_size())?;
+        let shentsize = Self::entsize(self.ehdr.e_shentsize, c.class.shdr_size())?;
+        let ph_end = Self::table_end(self.ehdr.e_phoff, phentsize, self.phdrs.len())?;
+        let sh_end = Self::table_end(self.ehdr.e_shoff, shentsize, self.shdrs.len())?;
+        let len = self
+            .data
+            .len()
+            .max(c.class.ehdr_size())
+            .max(ph_end)
+            .max(sh_end);
+        let mut out = self.data.clone();
+        out.resize(len, 0);
+        self.ehdr.write(c, &mut out);
+        for (i, phdr) in self.phdrs.iter().enumerate() {
+            phdr.write(c, &mut out, self.ehdr.e_phoff as usize + i * phentsize);
+        }
+        for (i, shdr) in self.shdrs.iter().enumerate() {
+            shdr.write(c, &mut out, self.ehdr.e_shoff as usize + i * shentsize);
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::header::PN_XNUM;
+    use crate::test_util::elf64_ident;
+    use std::collections::HashSet;
+
+    #[test]
+    fn equal_headers_in_set() {
+        // 同样的字段值位于两个缓冲区的不同位置
+        let mut a = [0u8; 64];
+        a[4..8].copy_from_slice(&1u32.to_le_bytes());
+        a[24..32].copy_from_slice(&0x40u64.to_le_bytes());
+        let mut b = vec![0xffu8; 0x40];
+        b.extend_from_slice(&a);
+        let c = Codec::new(Class::Elf64, Encode::Le);
+
+        let shdrs: HashSet<_> = [(&a[..], 0), (&b[..], 0x40)]
+            .iter()
+            .map(|&(buf, off)| OwnedShdr::parse(c, buf, off).unwrap())
+            .collect();
+        assert_eq!(shdrs.len(), 1);
+        assert_eq!(shdrs.iter().next().unwrap().sh_offset, 0x40);
+
+        let phdrs: HashSet<_> = [(&a[..], 0), (&b[..], 0x40)]
+            .iter()
+            .map(|&(buf, off)| OwnedPhdr::parse(c, buf, off).unwrap())
+            .collect();
+        assert_eq!(phdrs.len(), 1);
+    }
+
+    #[test]
+    fn invalid_ident_is_an_error() {
+        let mut elf = OwnedElf {
+            ehdr: OwnedEhdr::default(),
+            phdrs: Vec::new(),
+            shdrs: Vec::new(),
+            data: Vec::new(),
+        };
+        assert!(elf.class().is_err());
+        assert!(elf.encode().is_err());
+        assert!(elf.to_bytes().is_err());
+        assert!(elf.normalize(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn to_bytes_rejects_bad_tables() {
+        let mut elf = OwnedElf {
+            ehdr: OwnedEhdr {
+                ident: elf64_ident(),
+                e_phoff: u64::MAX - 8,
+                ..Default::default()
+            },
+            phdrs: vec![OwnedPhdr::default()],
+            shdrs: Vec::new(),
+            data: Vec::new(),
+        };
+        assert_eq!(elf.to_bytes().unwrap_err(), ElfError::Overflow);
+
+        elf.ehdr.e_phoff = 64;
+        elf.ehdr.e_phentsize = 8;
+        assert!(matches!(elf.to_bytes(), Err(ElfError::Malformed(_))));
+        elf.ehdr.e_phentsize = 64;
+        assert_eq!(elf.to_bytes().unwrap().len(), 128);
+
+        elf.shdrs.push(OwnedShdr::default());
+        elf.ehdr.e_shoff = 0x100;
+        elf.ehdr.e_shentsize = 40;
+        assert!(matches!(elf.to_bytes(), Err(ElfError::Malformed(_))));
+    }
+
+    #[test]
+    fn extended_numbering() {
+        let shstrtab = b"\0.text\0.shstrtab\0";
+        let mut data = vec![0u8; 0x200];
+        data.extend_from_slice(shstrtab);
+        let bytes = OwnedElf {
+            ehdr: OwnedEhdr {
+                ident: elf64_ident(),
+                e_phoff: 64,
+                e_phnum: PN_XNUM,
+                e_shoff: 0x100,
+                e_shstrndx: SHN_XINDEX,
+                ..Default::default()
+            },
+            phdrs: vec![OwnedPhdr::default(); 2],
+            shdrs: vec![
+                OwnedShdr {
+                    sh_size: 3,
+                    sh_info: 2,
+                    sh_link: 2,
+                    ..Default::default()
+                },
+                OwnedShdr {
+                    sh_name: 1,
+                    ..Default::default()
+                },
+                OwnedShdr {
+                    sh_name: 7,
+                    sh_offset: 0x200,
+                    sh_size: shstrtab.len() as u64,
+                    ..Default::default()
+                },
+            ],
+            data,
+        }
+        .to_bytes()
+        .unwrap();
+
+        let elf = OwnedElf::parse(&bytes).unwrap();
+        assert_eq!((elf.ehdr.e_shnum, elf.ehdr.e_phnum), (0, PN_XNUM));
+        assert_eq!((elf.phdrs.len(), elf.shdrs.len()), (2, 3));
+        assert_eq!(elf.shstrndx(), 2);
+        assert_eq!(elf.section_name(1).unwrap(), ".text");
+        assert_eq!(elf.section_name(2).unwrap(), ".shstrtab");
+
+        let limits = ParseLimits {
+            max_sections: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            OwnedElf::parse_with_limits(&bytes, &limits).unwrap_err(),
+            ElfError::LimitExceeded {
+                what: "sections",
+                count: 3,
+                limit: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 常用 trait 与类型的集合，`use elf_trait::prelude::*;` 即可使用本 crate 的主要功能。
+//!
+//! 只包含实现或调用 trait 时几乎总会用到的项，具体功能模块中的辅助函数与结构体仍需单独导入。
+
+#[doc(inline)]
+pub use accessor::{Encode, Field, Getter, Mutable, Setter};
+
+#[doc(inline)]
+pub use crate::{
+    Dyn, DynamicTab, Ehdr, ElfObject, Ident, Phdr, PhdrTab, Rel, RelTab, Section, Segmemt, Shdr,
+    ShdrTab, Strtab, Sym, Symtab,
+};
+
+#[doc(inline)]
+pub use crate::{
+    CheckedSetter, Class, ElfError, FieldEq, FieldSize, Located, Machine, Note, SectionType,
+    SegmentType, Table,
+};
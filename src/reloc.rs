@@ -0,0 +1,233 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 统一 REL 与 RELA 格式的重定位遍历
+
+use crate::codec::Codec;
+use crate::util::clamped_slice;
+use crate::{Class, SectionType, Shdr, Strtab, Sym, Symtab};
+use accessor::Encode;
+
+/// 一个重定位项，r_info 已拆分为符号索引与类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Relocation {
+    pub offset: u64,
+    pub sym: u32,
+    pub r_type: u32,
+    /// RELA 中显式给出的 addend；REL 为 None，addend 需从被重定位的位置读取
+    pub addend: Option<i64>,
+}
+
+impl Relocation {
+    /// 与 [`Rel::symbol_name`](crate::Rel::symbol_name) 相同，符号索引为 0 时返回 None
+    pub fn symbol_name<'a, S: Sym>(
+        &self,
+        symtab: &impl Symtab<S>,
+        strtab: &'a impl Strtab,
+    ) -> Option<&'a str> {
+        symbol_name_at(self.sym, symtab, strtab)
+    }
+}
+
+pub(crate) fn symbol_name_at<'a, S: Sym>(
+    idx: u32,
+    symtab: &impl Symtab<S>,
+    strtab: &'a impl Strtab,
+) -> Option<&'a str> {
+    if idx == 0 {
+        return None;
+    }
+    strtab.get(symtab.get(idx as usize)?.get::<S::Name>())
+}
+
+/// 遍历一个重定位 section 中的所有项，遇到截断的项时停止
+pub struct RelocIter<'a> {
+    data: &'a [u8],
+    codec: Codec,
+    rela: bool,
+}
+
+impl<'a> RelocIter<'a> {
+    fn empty(encode: Encode) -> Self {
+        RelocIter {
+            data: &[],
+            codec: Codec::new(Class::Elf64, encode),
+            rela: false,
+        }
+    }
+
+    fn entsize(&self) -> usize {
+        let word = self.codec.class.word_size();
+        if self.rela {
+            3 * word
+        } else {
+            2 * word
+        }
+    }
+}
+
+impl<'a> Iterator for RelocIter<'a> {
+    type Item = Relocation;
+    fn next(&mut self) -> Option<Relocation> {
+        let entsize = self.entsize();
+        if self.data.len() < entsize {
+            return None;
+        }
+        let mut r = self.codec.reader(self.data, 0);
+        let offset = r.word().ok()?;
+        let info = r.word().ok()?;
+        let addend = if self.rela {
+            let word = r.word().ok()?;
+            Some(match self.codec.class {
+                Class::Elf32 => word as u32 as i32 as i64,
+                Class::Elf64 => word as i64,
+            })
+        } else {
+            None
+        };
+        let (sym, r_type) = match self.codec.class {
+            Class::Elf32 => ((info >> 8) as u32, (info & 0xff) as u32),
+            Class::Elf64 => ((info >> 32) as u32, info as u32),
+        };
+        self.data = &self.data[entsize..];
+        Some(Relocation {
+            offset,
+            sym,
+            r_type,
+            addend,
+        })
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.data.len() / self.entsize();
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for RelocIter<'a> {}
+
+/// 遍历 SHT_REL 或 SHT_RELA section 中的重定位项，Class 由 sh_entsize 确定。
+///
+/// 其他类型的 section、sh_entsize 不是合法的表项大小或数据超出 buf 时得到空的迭代器。
+/// MIPS64 小端文件的 r_info 布局不同，不适用于此函数。
+pub fn relocations<'a, T: Shdr>(shdr: &T, buf: &'a [u8], encode: Encode) -> RelocIter<'a> {
+    let rela = match shdr.get::<T::Type>() {
+        SectionType::Rel => false,
+        SectionType::Rela => true,
+        _ => return RelocIter::empty(encode),
+    };
+    let class = match (rela, shdr.get::<T::Entsize>()) {
+        (false, 8) | (true, 12) => Class::Elf32,
+        (false, 16) | (true, 24) => Class::Elf64,
+        _ => return RelocIter::empty(encode),
+    };
+    RelocIter {
+        data: clamped_slice(buf, &shdr.get::<T::SecRange>()).unwrap_or(&[]),
+        codec: Codec::new(class, encode),
+        rela,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+    use accessor::{Getter, Setter};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn rel_and_rela() {
+        let mut bytes = Vec::new();
+        // ELF32 REL：offset 0x100，符号 3，类型 R_386_32
+        bytes.extend_from_slice(&0x100u32.to_le_bytes());
+        bytes.extend_from_slice(&(3u32 << 8 | 1).to_le_bytes());
+        // ELF64 RELA：offset 0x200，符号 5，类型 R_X86_64_PLT32，addend -4
+        bytes.extend_from_slice(&0x200u64.to_le_bytes());
+        bytes.extend_from_slice(&(5u64 << 32 | 4).to_le_bytes());
+        bytes.extend_from_slice(&(-4i64).to_le_bytes());
+
+        let buf = Rc::new(RefCell::new(vec![0u8; 2 * 64]));
+        let sht = table(&buf, 0..2 * 64, Shdr64);
+        sht[0]
+            .with::<ShType>(SectionType::Rel)
+            .with::<ShOffset>(0)
+            .with::<ShSize>(8)
+            .with::<ShEntsize>(8);
+        sht[1]
+            .with::<ShType>(SectionType::Rela)
+            .with::<ShOffset>(8)
+            .with::<ShSize>(24)
+            .with::<ShEntsize>(24);
+
+        let rels: Vec<_> = sht
+            .iter()
+            .flat_map(|shdr| relocations(shdr, &bytes, Encode::Le))
+            .collect();
+        assert_eq!(
+            rels,
+            [
+                Relocation {
+                    offset: 0x100,
+                    sym: 3,
+                    r_type: 1,
+                    addend: None,
+                },
+                Relocation {
+                    offset: 0x200,
+                    sym: 5,
+                    r_type: 4,
+                    addend: Some(-4),
+                },
+            ]
+        );
+
+        sht[1].with::<ShEntsize>(0);
+        assert_eq!(relocations(&sht[1], &bytes, Encode::Le).len(), 0);
+    }
+
+    #[test]
+    fn resolve_symbol_names() {
+        use crate::{Rel, StrtabView};
+
+        let strtab = StrtabView::new(b"\0printf\0");
+        let syms = Rc::new(RefCell::new(vec![0u8; 2 * 24]));
+        let symtab = table(&syms, 0..2 * 24, Sym64);
+        symtab[1].with::<StName>(1);
+
+        let rels = Rc::new(RefCell::new(vec![0u8; 3 * 24]));
+        let relatab = table(&rels, 0..3 * 24, Rela64);
+        relatab[0].with::<RInfo>(1 << 32 | 7);
+        relatab[1].with::<RInfo>(8);
+        relatab[2].with::<RInfo>(9 << 32 | 1);
+        // 只改写符号索引，r_info 低 32 位的类型保持不变
+        relatab[2].with::<RSym>(9);
+        assert_eq!(relatab[2].get::<RInfo>(), 9 << 32 | 1);
+        let names: Vec<_> = relatab
+            .iter()
+            .map(|rel| rel.symbol_name(&symtab, &strtab))
+            .collect();
+        assert_eq!(names, [Some("printf"), None, None]);
+
+        let rel = Relocation {
+            offset: 0,
+            sym: 1,
+            r_type: 7,
+            addend: None,
+        };
+        assert_eq!(rel.symbol_name(&symtab, &strtab), Some("printf"));
+    }
+}
@@ -0,0 +1,506 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Section 类型以及基于 Section Header Table 的常用查找
+
+use crate::codec::Codec;
+use crate::util::clamped_slice;
+use crate::{Class, ElfError, Shdr, ShdrTab, Strtab};
+use accessor::Encode;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
+
+elf_enum! {
+    /// sh_type 字段的值
+    pub enum SectionType: u32 {
+        Null = 0 => "NULL",
+        Progbits = 1 => "PROGBITS",
+        Symtab = 2 => "SYMTAB",
+        Strtab = 3 => "STRTAB",
+        Rela = 4 => "RELA",
+        Hash = 5 => "HASH",
+        Dynamic = 6 => "DYNAMIC",
+        Note = 7 => "NOTE",
+        Nobits = 8 => "NOBITS",
+        Rel = 9 => "REL",
+        Shlib = 10 => "SHLIB",
+        Dynsym = 11 => "DYNSYM",
+        InitArray = 14 => "INIT_ARRAY",
+        FiniArray = 15 => "FINI_ARRAY",
+        PreinitArray = 16 => "PREINIT_ARRAY",
+        Group = 17 => "GROUP",
+        SymtabShndx = 18 => "SYMTAB_SHNDX",
+        Relr = 19 => "RELR",
+        GnuAttributes = 0x6fff_fff5 => "GNU_ATTRIBUTES",
+        GnuHash = 0x6fff_fff6 => "GNU_HASH",
+        GnuLiblist = 0x6fff_fff7 => "GNU_LIBLIST",
+        GnuVerdef = 0x6fff_fffd => "GNU_verdef",
+        GnuVerneed = 0x6fff_fffe => "GNU_verneed",
+        GnuVersym = 0x6fff_ffff => "GNU_versym",
+    }
+}
+
+impl SectionType {
+    /// 与 `readelf -S` 的 Type 列一致的名称，未知类型为 `"<unknown>"`
+    pub fn readelf_name(self) -> &'static str {
+        match self {
+            SectionType::Null => "NULL",
+            SectionType::Progbits => "PROGBITS",
+            SectionType::Symtab => "SYMTAB",
+            SectionType::Strtab => "STRTAB",
+            SectionType::Rela => "RELA",
+            SectionType::Hash => "HASH",
+            SectionType::Dynamic => "DYNAMIC",
+            SectionType::Note => "NOTE",
+            SectionType::Nobits => "NOBITS",
+            SectionType::Rel => "REL",
+            SectionType::Shlib => "SHLIB",
+            SectionType::Dynsym => "DYNSYM",
+            SectionType::InitArray => "INIT_ARRAY",
+            SectionType::FiniArray => "FINI_ARRAY",
+            SectionType::PreinitArray => "PREINIT_ARRAY",
+            SectionType::Group => "GROUP",
+            SectionType::SymtabShndx => "SYMTAB SECTION INDICES",
+            SectionType::Relr => "RELR",
+            SectionType::GnuAttributes => "GNU_ATTRIBUTES",
+            SectionType::GnuHash => "GNU_HASH",
+            SectionType::GnuLiblist => "GNU_LIBLIST",
+            SectionType::GnuVerdef => "VERDEF",
+            SectionType::GnuVerneed => "VERNEED",
+            SectionType::GnuVersym => "VERSYM",
+            SectionType::Other(_) => "<unknown>",
+        }
+    }
+
+    /// 规范要求的表项大小，没有固定表项大小的类型返回 None
+    pub fn expected_entsize(self, class: Class) -> Option<usize> {
+        let word = class.word_size();
+        match self {
+            SectionType::Rela => Some(3 * word),
+            SectionType::Rel => Some(2 * word),
+            SectionType::Dynamic => Some(2 * word),
+            SectionType::Symtab | SectionType::Dynsym => Some(match class {
+                Class::Elf32 => 16,
+                Class::Elf64 => 24,
+            }),
+            SectionType::InitArray | SectionType::FiniArray | SectionType::PreinitArray => {
+                Some(word)
+            }
+            SectionType::Relr => Some(word),
+            SectionType::Hash | SectionType::Group | SectionType::SymtabShndx => Some(4),
+            SectionType::GnuVersym => Some(2),
+            _ => None,
+        }
+    }
+}
+
+elf_flags! {
+    /// sh_flags 字段的值
+    pub struct SectionFlags: u64 {
+        const WRITE = 0x1;
+        const ALLOC = 0x2;
+        const EXECINSTR = 0x4;
+        const MERGE = 0x10;
+        const STRINGS = 0x20;
+        const INFO_LINK = 0x40;
+        const LINK_ORDER = 0x80;
+        const GROUP = 0x200;
+        const TLS = 0x400;
+        const COMPRESSED = 0x800;
+    }
+}
+
+/// Compression Header 中 ch_type 的 zlib 格式
+pub const ELFCOMPRESS_ZLIB: u32 = 1;
+
+/// ch_size 允许的最大值，更大的压缩 section 返回 `ElfError::LimitExceeded`
+pub const MAX_DECOMPRESSED_SIZE: u64 = 1 << 30;
+
+/// 解码 SHF_COMPRESSED section 开头的 Compression Header 并解压其后的数据。
+///
+/// 解压的输出不会超过 ch_size，ch_size 本身不超过 [`MAX_DECOMPRESSED_SIZE`]。
+/// 没有 `compression` feature 时总是返回 `ElfError::Unsupported`。
+pub(crate) fn decompress(data: &[u8], class: Class, encode: Encode) -> Result<Vec<u8>, ElfError> {
+    let c = Codec::new(class, encode);
+    let mut r = c.reader(data, 0);
+    let ch_type = r.u32()?;
+    if class == Class::Elf64 {
+        r.u32()?; // ch_reserved
+    }
+    let ch_size = r.word()?;
+    let _ch_addralign = r.word()?;
+    let header = match class {
+        Class::Elf32 => 12,
+        Class::Elf64 => 24,
+    };
+    if ch_type != ELFCOMPRESS_ZLIB {
+        return Err(ElfError::Unsupported("compression type other than zlib"));
+    }
+    if ch_size > MAX_DECOMPRESSED_SIZE {
+        return Err(ElfError::LimitExceeded {
+            what: "decompressed bytes",
+            count: ch_size as usize,
+            limit: MAX_DECOMPRESSED_SIZE as usize,
+        });
+    }
+    inflate(&data[header..], ch_size as usize)
+}
+
+#[cfg(feature = "compression")]
+fn inflate(data: &[u8], size: usize) -> Result<Vec<u8>, ElfError> {
+    // 超过 ch_size 的输出同样视为格式错误，无需解压到末尾
+    let out = miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, size)
+        .map_err(|_| ElfError::Malformed("invalid zlib stream"))?;
+    if out.len() != size {
+        return Err(ElfError::Malformed(
+            "ch_size does not match decompressed size",
+        ));
+    }
+    Ok(out)
+}
+
+#[cfg(not(feature = "compression"))]
+fn inflate(_: &[u8], _: usize) -> Result<Vec<u8>, ElfError> {
+    Err(ElfError::Unsupported(
+        "compressed section requires the `compression` feature",
+    ))
+}
+
+/// 按名称查找 section，返回第一个匹配项的索引
+pub fn section_by_name<T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    shstrtab: &impl Strtab,
+    name: &str,
+) -> Option<usize> {
+    sht.iter()
+        .position(|shdr| shstrtab.get(shdr.get::<T::Name>()) == Some(name))
+}
+
+/// 按名称查找 section 并返回其数据，数据超出 buf 时返回 None
+pub fn section_data_by_name<'a, T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    shstrtab: &impl Strtab,
+    buf: &'a [u8],
+    name: &str,
+) -> Option<&'a [u8]> {
+    let idx = section_by_name(sht, shstrtab, name)?;
+    clamped_slice(buf, &sht.get(idx)?.file_range())
+}
+
+/// `.comment` 中以 NUL 分隔的编译器版本信息，没有 `.comment` 时返回空的 Vec
+pub fn producers<T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    shstrtab: &impl Strtab,
+    buf: &[u8],
+) -> Vec<String> {
+    section_data_by_name(sht, shstrtab, buf, ".comment")
+        .map(|data| {
+            data.split(|&b| b == 0)
+                .filter(|s| !s.is_empty())
+                .map(|s| String::from_utf8_lossy(s).into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 一次遍历按 sh_type 对 section 分组，每组中的索引按升序排列
+pub fn sections_by_type<T: Shdr>(sht: &impl ShdrTab<T>) -> HashMap<SectionType, Vec<usize>> {
+    let mut map: HashMap<SectionType, Vec<usize>> = HashMap::new();
+    for (idx, shdr) in sht.iter().enumerate() {
+        map.entry(shdr.get::<T::Type>()).or_default().push(idx);
+    }
+    map
+}
+
+/// 所有 SHT_REL 与 SHT_RELA section 的索引，按升序排列
+pub fn relocation_sections<T: Shdr>(sht: &impl ShdrTab<T>) -> Vec<usize> {
+    sht.iter()
+        .enumerate()
+        .filter(|(_, shdr)| matches!(shdr.get::<T::Type>(), SectionType::Rel | SectionType::Rela))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// [`semantic_hash`] 默认忽略的 section，以 `*` 结尾的项按前缀匹配
+pub const DEFAULT_IGNORE: &[&str] = &[".comment", ".note.gnu.build-id", ".debug*"];
+
+fn ignored(name: &str, ignore: &[&str]) -> bool {
+    ignore.iter().any(|pat| match pat.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == *pat,
+    })
+}
+
+/// 64 位 FNV-1a，结果与平台及编译器版本无关，可以作为持久化的缓存键
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// 构造 [`Fnv1a`] 的 BuildHasher，[`section_digests`] 的默认选择
+pub type FnvBuildHasher = BuildHasherDefault<Fnv1a>;
+
+/// 对 section 的名称、类型、标志与内容计算哈希，用于判断两个目标文件在功能上是否相同。
+///
+/// 名称匹配 ignore 的 section 被跳过（通常传入 [`DEFAULT_IGNORE`]），
+/// section 的偏移与索引不参与计算，因此只有布局不同的文件哈希相同。
+/// SHT_NOBITS 只计入其大小。
+pub fn semantic_hash<T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    shstrtab: &impl Strtab,
+    buf: &[u8],
+    ignore: &[&str],
+) -> u64 {
+    let mut h = Fnv1a::default();
+    for shdr in sht.iter() {
+        let name = shstrtab.get(shdr.get::<T::Name>()).unwrap_or("");
+        if ignored(name, ignore) {
+            continue;
+        }
+        let ty = shdr.get::<T::Type>();
+        let range = shdr.get::<T::SecRange>();
+        h.write(name.as_bytes());
+        h.write(&[0]);
+        h.write(&u32::from(ty).to_le_bytes());
+        h.write(&shdr.get::<T::Flags>().to_le_bytes());
+        h.write(&(range.len() as u64).to_le_bytes());
+        if ty != SectionType::Nobits {
+            h.write(clamped_slice(buf, &range).unwrap_or(&[]));
+        }
+    }
+    h.finish()
+}
+
+/// 按 Section Header Table 的顺序计算每个 section 原始内容的哈希，返回 `(索引, 哈希)`。
+///
+/// SHT_NOBITS 没有文件中的内容，不出现在结果中；内容超出 buf 的 section 按空内容计算。
+/// 需要跨进程比较时应使用与进程无关的哈希（如 [`FnvBuildHasher`]），而不是 `RandomState`。
+pub fn section_digests<T: Shdr, H: BuildHasher>(
+    sht: &impl ShdrTab<T>,
+    buf: &[u8],
+    hasher: &H,
+) -> Vec<(usize, u64)> {
+    sht.iter()
+        .enumerate()
+        .filter(|(_, shdr)| shdr.get::<T::Type>() != SectionType::Nobits)
+        .map(|(idx, shdr)| {
+            let mut h = hasher.build_hasher();
+            h.write(clamped_slice(buf, &shdr.get::<T::SecRange>()).unwrap_or(&[]));
+            (idx, h.finish())
+        })
+        .collect()
+}
+
+/// 按名称比较新旧两个文件的 [`section_digests`] 结果，返回新文件中内容变化或新增的 section 的索引。
+///
+/// 同名的 section 按出现的顺序一一对应；旧文件中被删除的 section 不在结果中。
+/// 两组哈希必须由同一个 hasher 计算。
+pub fn changed_sections<T: Shdr, U: Shdr>(
+    old_sht: &impl ShdrTab<T>,
+    old_shstrtab: &impl Strtab,
+    old: &[(usize, u64)],
+    new_sht: &impl ShdrTab<U>,
+    new_shstrtab: &impl Strtab,
+    new: &[(usize, u64)],
+) -> Vec<usize> {
+    let mut before: HashMap<&str, VecDeque<u64>> = HashMap::new();
+    for &(idx, digest) in old {
+        let name = old_sht
+            .get(idx)
+            .and_then(|shdr| old_shstrtab.get(shdr.get::<T::Name>()))
+            .unwrap_or("");
+        before.entry(name).or_default().push_back(digest);
+    }
+    new.iter()
+        .filter(|&&(idx, digest)| {
+            let name = new_sht
+                .get(idx)
+                .and_then(|shdr| new_shstrtab.get(shdr.get::<U::Name>()))
+                .unwrap_or("");
+            before.get_mut(name).and_then(VecDeque::pop_front) != Some(digest)
+        })
+        .map(|&(idx, _)| idx)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::segments::SegmentFlags;
+    use crate::test_util::*;
+    use crate::{StrtabView, Table};
+    use accessor::{Getter, Setter};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// 由 (sh_name, sh_type) 依次描述的 ELF64 Section Header Table，
+    /// 第 i 个 section 的数据位于 i * size 处，长度为 size
+    fn sht(sections: &[(usize, SectionType)], size: u64) -> Table<Shdr64> {
+        let buf = Rc::new(RefCell::new(vec![0u8; sections.len() * 64]));
+        let sht = table(&buf, 0..sections.len() * 64, Shdr64);
+        for (i, &(name, ty)) in sections.iter().enumerate() {
+            sht[i]
+                .with::<ShName>(name)
+                .with::<ShType>(ty)
+                .with::<ShOffset>(i as u64 * size)
+                .with::<ShSize>(size);
+        }
+        sht
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let high = (0x6fff_fff0..=0x6fff_ffff).chain(0xffff_fff0..=u32::MAX);
+        for raw in (0..=u16::MAX as u32).chain(high) {
+            let ty = SectionType::from(raw);
+            assert_eq!(ty.raw(), raw);
+            assert_eq!(SectionType::from(ty.raw()), ty);
+        }
+        assert_eq!(SectionType::from(0x6fff_fff6), SectionType::GnuHash);
+    }
+
+    #[test]
+    fn entsize_by_class() {
+        let check = |ty: SectionType, elf32, elf64| {
+            assert_eq!(ty.expected_entsize(Class::Elf32), elf32);
+            assert_eq!(ty.expected_entsize(Class::Elf64), elf64);
+        };
+        check(SectionType::Rela, Some(12), Some(24));
+        check(SectionType::Rel, Some(8), Some(16));
+        check(SectionType::Symtab, Some(16), Some(24));
+        check(SectionType::Dynsym, Some(16), Some(24));
+        check(SectionType::Dynamic, Some(8), Some(16));
+        check(SectionType::Hash, Some(4), Some(4));
+        check(SectionType::Progbits, None, None);
+    }
+
+    #[test]
+    fn group_by_type() {
+        let types = [
+            SectionType::Null,
+            SectionType::Rela,
+            SectionType::Progbits,
+            SectionType::Rel,
+            SectionType::Progbits,
+            SectionType::Rela,
+        ];
+        let sections: Vec<_> = types.iter().map(|&ty| (0, ty)).collect();
+        let sht = sht(&sections, 0);
+        let map = sections_by_type(&sht);
+        assert_eq!(map[&SectionType::Progbits], [2, 4]);
+        assert_eq!(map[&SectionType::Rela], [1, 5]);
+        assert!(!map.contains_key(&SectionType::Symtab));
+        assert_eq!(relocation_sections(&sht), [1, 3, 5]);
+    }
+
+    #[test]
+    fn hash_ignores_comment() {
+        let shstrtab = StrtabView::new(b"\0.text\0.comment\0.debug_info\0");
+        let progbits = SectionType::Progbits;
+        let sht = sht(&[(1, progbits), (7, progbits), (16, progbits)], 4);
+        let hash = |data: &[u8], ignore| semantic_hash(&sht, &shstrtab, data, ignore);
+
+        let a = hash(b"codeGCC1dbg1", DEFAULT_IGNORE);
+        assert_eq!(a, hash(b"codeGCC2dbg2", DEFAULT_IGNORE));
+        assert_ne!(a, hash(b"CODEGCC1dbg1", DEFAULT_IGNORE));
+        assert_ne!(hash(b"codeGCC1dbg1", &[]), hash(b"codeGCC2dbg2", &[]));
+
+        let mut ignore = DEFAULT_IGNORE.to_vec();
+        ignore.push(".text");
+        assert_eq!(
+            hash(b"codeGCC1dbg1", &ignore),
+            hash(b"CODEGCC1dbg1", &ignore)
+        );
+        sht[0].with::<ShFlags>(6);
+        assert_ne!(a, hash(b"codeGCC1dbg1", DEFAULT_IGNORE));
+    }
+
+    #[test]
+    fn digests_by_name() {
+        let shstrtab = StrtabView::new(b"\0.text\0.data\0.bss\0");
+        let (text, data, bss) = (
+            (1, SectionType::Progbits),
+            (7, SectionType::Progbits),
+            (13, SectionType::Nobits),
+        );
+        let old_sht = sht(&[text, data, bss], 4);
+        let new_sht = sht(&[data, text, bss], 4);
+        let old = section_digests(&old_sht, b"codedataXXXX", &FnvBuildHasher::default());
+        assert_eq!(old.iter().map(|d| d.0).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(
+            old,
+            section_digests(&old_sht, b"codedataYYYY", &FnvBuildHasher::default())
+        );
+
+        let changed = |data: &[u8]| {
+            let new = section_digests(&new_sht, data, &FnvBuildHasher::default());
+            changed_sections(&old_sht, &shstrtab, &old, &new_sht, &shstrtab, &new)
+        };
+        assert!(changed(b"datacodeZZZZ").is_empty());
+        assert_eq!(changed(b"DATAcodeZZZZ"), [0]);
+    }
+
+    #[test]
+    fn flags_keep_unknown_bits() {
+        let sht = sht(&[(0, SectionType::Progbits)], 0);
+        // 0x0ff0_0000 为 SHF_MASKOS，0x8000_0000 位于 SHF_MASKPROC 中
+        sht[0].with::<ShFlags>(0x8ff0_0000 | SectionFlags::ALLOC.bits());
+        let flags = sht[0].get::<ShFlagsField>();
+        assert!(flags.contains(SectionFlags::ALLOC));
+        sht[0].with::<ShFlagsField>(flags | SectionFlags::WRITE);
+        assert_eq!(sht[0].get::<ShFlags>(), 0x8ff0_0003);
+
+        let buf = Rc::new(RefCell::new(vec![0u8; 56]));
+        let pht = table(&buf, 0..56, Phdr64);
+        pht[0].with::<PFlags>(0xf000_0004);
+        let flags = pht[0].get::<PFlagsField>() | SegmentFlags::X;
+        pht[0].with::<PFlagsField>(flags);
+        assert_eq!(pht[0].get::<PFlags>(), 0xf000_0005);
+    }
+
+    #[test]
+    fn producers_split_comment() {
+        let shstrtab = StrtabView::new(b"\0.text\0.comment\0");
+        let progbits = SectionType::Progbits;
+        let sht = sht(&[(1, progbits), (7, progbits)], 16);
+        let mut data = vec![0u8; 16];
+        data.extend_from_slice(b"GCC: 9\0\0clang 12\0");
+        assert_eq!(
+            producers(&sht, &shstrtab, &data),
+            vec!["GCC: 9".to_string(), "clang 12".to_string()]
+        );
+
+        let sht = self::sht(&[(1, progbits)], 16);
+        assert!(producers(&sht, &shstrtab, &data).is_empty());
+    }
+}
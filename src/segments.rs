@@ -0,0 +1,167 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Segment 类型以及基于 Program Header Table 的常用操作
+
+use crate::util::clamped_slice;
+use crate::{Phdr, PhdrTab};
+
+/// p_flags 中的可执行位
+pub const PF_X: u32 = 0x1;
+/// p_flags 中的可写位
+pub const PF_W: u32 = 0x2;
+/// p_flags 中的可读位
+pub const PF_R: u32 = 0x4;
+
+elf_flags! {
+    /// p_flags 字段的值
+    pub struct SegmentFlags: u32 {
+        const X = PF_X;
+        const W = PF_W;
+        const R = PF_R;
+    }
+}
+
+/// 与 `readelf -l` 的 Flags 列一致的权限字符串，如 `"R E"`、`"RW "`
+pub fn perm_string(flags: u32) -> String {
+    let bit = |mask, c| if flags & mask != 0 { c } else { ' ' };
+    [bit(PF_R, 'R'), bit(PF_W, 'W'), bit(PF_X, 'E')]
+        .iter()
+        .collect()
+}
+
+elf_enum! {
+    /// p_type 字段的值
+    pub enum SegmentType: u32 {
+        Null = 0 => "NULL",
+        Load = 1 => "LOAD",
+        Dynamic = 2 => "DYNAMIC",
+        Interp = 3 => "INTERP",
+        Note = 4 => "NOTE",
+        Shlib = 5 => "SHLIB",
+        Phdr = 6 => "PHDR",
+        Tls = 7 => "TLS",
+        GnuEhFrame = 0x6474_e550 => "GNU_EH_FRAME",
+        GnuStack = 0x6474_e551 => "GNU_STACK",
+        GnuRelro = 0x6474_e552 => "GNU_RELRO",
+        GnuProperty = 0x6474_e553 => "GNU_PROPERTY",
+    }
+}
+
+impl SegmentType {
+    /// 与 `readelf -l` 的 Type 列一致的名称，未知类型为 `"<unknown>"`
+    pub fn readelf_name(self) -> &'static str {
+        self.canonical_name().unwrap_or("<unknown>")
+    }
+}
+
+/// 一个 PT_LOAD segment 的加载信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadSegment<'a> {
+    /// 加载的目标虚拟地址
+    pub vaddr: u64,
+    /// 文件中的内容，拷贝到 vaddr 处
+    pub file_data: &'a [u8],
+    /// 在内存中占据的大小，超出 file_data 的部分需要填零
+    pub mem_size: u64,
+    /// p_flags 的原始值
+    pub flags: u32,
+}
+
+impl<'a> LoadSegment<'a> {
+    /// file_data 之后需要填零的字节数（如 .bss）
+    pub fn zero_fill(&self) -> u64 {
+        self.mem_size.saturating_sub(self.file_data.len() as u64)
+    }
+}
+
+/// 按 Program Header 的顺序遍历 PT_LOAD segment。
+///
+/// 文件范围超出 buf 的 segment 会被跳过。
+pub fn load_segments<'a, T: Phdr + 'a>(
+    pht: &'a impl PhdrTab<T>,
+    buf: &'a [u8],
+) -> impl Iterator<Item = LoadSegment<'a>> + 'a {
+    pht.iter()
+        .filter(|phdr| phdr.get::<T::Type>() == SegmentType::Load)
+        .filter_map(move |phdr| {
+            Some(LoadSegment {
+                vaddr: phdr.get::<T::Vaddr>(),
+                file_data: clamped_slice(buf, &phdr.get::<T::SegRange>())?,
+                mem_size: phdr.get::<T::Memsz>(),
+                flags: phdr.get::<T::Flags>(),
+            })
+        })
+}
+
+/// 通过 PT_LOAD segment 将虚拟地址转换为文件偏移，地址不在任何 segment 的文件内容中时返回 None
+pub fn vaddr_to_offset<T: Phdr>(pht: &impl PhdrTab<T>, vaddr: u64) -> Option<u64> {
+    pht.iter()
+        .filter(|phdr| phdr.get::<T::Type>() == SegmentType::Load)
+        .find_map(|phdr| {
+            let delta = vaddr.checked_sub(phdr.get::<T::Vaddr>())?;
+            let range = phdr.get::<T::SegRange>();
+            if delta < range.len() as u64 {
+                Some(range.start as u64 + delta)
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+    use accessor::Setter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn readelf_strings() {
+        assert_eq!(perm_string(PF_R | PF_X), "R E");
+        assert_eq!(perm_string(PF_R | PF_W), "RW ");
+        assert_eq!(perm_string(PF_R | PF_W | PF_X), "RWE");
+        assert_eq!(perm_string(0), "   ");
+        assert_eq!(
+            SegmentType::from(0x6474_e550).readelf_name(),
+            "GNU_EH_FRAME"
+        );
+        assert_eq!(SegmentType::Other(0x7000_0000).readelf_name(), "<unknown>");
+    }
+
+    #[test]
+    fn only_load_in_order() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 56]));
+        let pht = table(&buf, 0..3 * 56, Phdr64);
+        let types = [SegmentType::Load, SegmentType::Note, SegmentType::Load];
+        for (i, ty) in types.iter().enumerate() {
+            pht[i].with::<PType>(*ty);
+            pht[i].with::<PVaddr>(0x1000 * (i as u64 + 1));
+            pht[i].with::<POffset>(i as u64 * 8);
+            pht[i].with::<PFilesz>(4);
+            pht[i].with::<PMemsz>(16);
+        }
+        let file = buf.borrow().clone();
+        let segs: Vec<_> = load_segments(&pht, &file).collect();
+        assert_eq!(segs.len(), 2);
+        assert_eq!(segs[0].vaddr, 0x1000);
+        assert_eq!(segs[1].vaddr, 0x3000);
+        assert_eq!(segs[1].file_data, &file[16..20]);
+        assert_eq!(segs[1].zero_fill(), 12);
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `serde` feature 下为非本 crate 生成的类型提供序列化。
+//!
+//! 只有不含引用的 owned 类型实现 Serialize/Deserialize；
+//! 借用缓冲区的 trait 对象与 view 不参与序列化。
+//! `elf_enum!` 生成的枚举序列化为规范名称（如 `"PROGBITS"`），其中的 `Other` 序列化为原始整数；
+//! 其余枚举序列化为变体名。
+
+use crate::Class;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+impl Serialize for Class {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match self {
+            Class::Elf32 => "Elf32",
+            Class::Elf64 => "Elf64",
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Class {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "Elf32" => Ok(Class::Elf32),
+            "Elf64" => Ok(Class::Elf64),
+            other => Err(D::Error::custom(format!("unknown class {:?}", other))),
+        }
+    }
+}
+
+/// 用于 `#[serde(with = "crate::serde_impl::encode")]`，`Encode` 定义在 accessor 中
+pub(crate) mod encode {
+    use super::*;
+    use accessor::Encode;
+
+    pub(crate) fn serialize<S: Serializer>(
+        data: &Encode,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match data {
+            Encode::Le => "Le",
+            Encode::Be => "Be",
+        })
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Encode, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "Le" => Ok(Encode::Le),
+            "Be" => Ok(Encode::Be),
+            other => Err(D::Error::custom(format!("unknown encoding {:?}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::summary::SectionSummary;
+    use crate::{Class, HeaderSummary, Machine, ObjectType, OsAbi, SectionType, SegmentType};
+    use accessor::Encode;
+
+    #[test]
+    fn header_summary_round_trip() {
+        let summary = HeaderSummary {
+            class: Class::Elf64,
+            data: Encode::Le,
+            os_abi: OsAbi::from(0),
+            abi_version: 0,
+            e_type: ObjectType::Exec,
+            machine: Machine::X86_64,
+            version: 1,
+            entry: 0x40_1000,
+            phoff: 64,
+            shoff: 0x2000,
+            flags: 0,
+            ehsize: 64,
+            phentsize: 56,
+            phnum: 2,
+            shentsize: 64,
+            shnum: 5,
+            shstrndx: 4,
+        };
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(json.contains(r#""class":"Elf64""#));
+        assert!(json.contains(r#""data":"Le""#));
+        assert!(json.contains(r#""machine":"X86_64""#));
+        assert_eq!(
+            serde_json::from_str::<HeaderSummary>(&json).unwrap(),
+            summary
+        );
+
+        let other = HeaderSummary {
+            machine: Machine::Other(0x1234),
+            ..summary
+        };
+        let json = serde_json::to_string(&other).unwrap();
+        assert!(json.contains(r#""machine":4660"#));
+        assert_eq!(serde_json::from_str::<HeaderSummary>(&json).unwrap(), other);
+    }
+
+    #[test]
+    fn machine_name_or_number() {
+        assert_eq!(
+            serde_json::to_string(&Machine::X86_64).unwrap(),
+            r#""X86_64""#
+        );
+        assert_eq!(
+            serde_json::to_string(&Machine::Other(0x1234)).unwrap(),
+            "4660"
+        );
+        assert_eq!(
+            serde_json::from_str::<Machine>("62").unwrap(),
+            Machine::X86_64
+        );
+        assert_eq!(
+            serde_json::from_str::<Machine>("4660").unwrap(),
+            Machine::Other(0x1234)
+        );
+        assert_eq!(serde_json::to_string(&Machine::I386).unwrap(), r#""386""#);
+        assert!(serde_json::from_str::<Machine>(r#""I386""#).is_err());
+        assert!(serde_json::from_str::<Machine>(r#""Vax""#).is_err());
+        assert!(serde_json::from_str::<Machine>("70000").is_err());
+    }
+
+    #[test]
+    fn canonical_type_names() {
+        assert_eq!(
+            serde_json::to_string(&SectionType::Progbits).unwrap(),
+            r#""PROGBITS""#
+        );
+        assert_eq!(
+            serde_json::from_str::<SectionType>(r#""GNU_versym""#).unwrap(),
+            SectionType::GnuVersym
+        );
+        assert_eq!(
+            serde_json::to_string(&SegmentType::GnuEhFrame).unwrap(),
+            r#""GNU_EH_FRAME""#
+        );
+
+        let section = SectionSummary {
+            name: ".bss".to_string(),
+            sh_type: SectionType::Nobits,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&section).unwrap();
+        assert!(json.contains(r#""sh_type":"NOBITS""#));
+        assert_eq!(
+            serde_json::from_str::<SectionSummary>(&json).unwrap(),
+            section
+        );
+    }
+}
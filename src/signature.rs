@@ -0,0 +1,148 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 签名 section 的定位。本模块只负责确定签名与被签名数据的位置，具体的验证算法由调用者提供。
+
+use crate::sections::section_by_name;
+use crate::util::clamped_slice;
+use crate::{Shdr, ShdrTab, Strtab};
+use std::borrow::Cow;
+
+/// 被签名的数据与签名
+#[derive(Debug, Clone)]
+pub struct SignedRegion<'a> {
+    /// 去掉签名 section 数据之后的整个文件，签名 section 位于文件末尾时不做拷贝
+    pub data: Cow<'a, [u8]>,
+    pub signature: &'a [u8],
+}
+
+/// 按名称定位签名 section，默认名称为 `.sig`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureLocator {
+    section: String,
+}
+
+impl Default for SignatureLocator {
+    fn default() -> Self {
+        SignatureLocator::new(".sig")
+    }
+}
+
+impl SignatureLocator {
+    pub fn new(section: &str) -> Self {
+        SignatureLocator {
+            section: section.to_string(),
+        }
+    }
+
+    pub fn section(&self) -> &str {
+        &self.section
+    }
+
+    /// 找不到签名 section、其不占据文件空间（如 SHT_NOBITS）或范围超出 buf 时返回 None
+    pub fn signed_region<'a, T: Shdr>(
+        &self,
+        sht: &impl ShdrTab<T>,
+        shstrtab: &impl Strtab,
+        buf: &'a [u8],
+    ) -> Option<SignedRegion<'a>> {
+        let idx = section_by_name(sht, shstrtab, &self.section)?;
+        let range = sht.get(idx)?.file_range();
+        if range.is_empty() {
+            return None;
+        }
+        let signature = clamped_slice(buf, &range)?;
+        let data = if range.end == buf.len() {
+            Cow::Borrowed(&buf[..range.start])
+        } else {
+            let mut data = buf[..range.start].to_vec();
+            data.extend_from_slice(&buf[range.end..]);
+            Cow::Owned(data)
+        };
+        Some(SignedRegion { data, signature })
+    }
+
+    /// 以 (被签名数据, 签名) 调用 verifier，没有签名 section 时返回 false
+    pub fn verify_with<T: Shdr>(
+        &self,
+        sht: &impl ShdrTab<T>,
+        shstrtab: &impl Strtab,
+        buf: &[u8],
+        verifier: impl Fn(&[u8], &[u8]) -> bool,
+    ) -> bool {
+        self.signed_region(sht, shstrtab, buf)
+            .map(|region| verifier(&region.data, region.signature))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+    use crate::{SectionType, StrtabView, Table};
+    use accessor::Setter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// .text 位于 0..4，两个签名 section 依次位于 4..8 与 8..12
+    fn sht() -> Table<Shdr64> {
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 64]));
+        let sht = table(&buf, 0..3 * 64, Shdr64);
+        for (i, name) in [1, 7, 12].iter().enumerate() {
+            sht[i]
+                .with::<ShName>(*name)
+                .with::<ShType>(SectionType::Progbits)
+                .with::<ShOffset>(i as u64 * 4)
+                .with::<ShSize>(4);
+        }
+        sht
+    }
+
+    #[test]
+    fn region_around_signature() {
+        let shstrtab = StrtabView::new(b"\0.text\0.sig\0.mysig\0");
+        let sht = sht();
+        let buf = b"codeSIG1SIG2";
+
+        // .mysig 位于文件末尾，直接借用前面的数据
+        let custom = SignatureLocator::new(".mysig");
+        let region = custom.signed_region(&sht, &shstrtab, buf).unwrap();
+        assert!(matches!(region.data, Cow::Borrowed(b"codeSIG1")));
+        assert_eq!(region.signature, b"SIG2");
+
+        // .sig 位于中间，需要拼接前后两部分
+        let region = SignatureLocator::default()
+            .signed_region(&sht, &shstrtab, buf)
+            .unwrap();
+        assert!(matches!(region.data, Cow::Owned(_)));
+        assert_eq!(&region.data[..], b"codeSIG2");
+        assert_eq!(region.signature, b"SIG1");
+
+        let verify = |locator: &SignatureLocator| {
+            locator.verify_with(&sht, &shstrtab, buf, |data, sig| {
+                data.starts_with(b"code") && sig.starts_with(b"SIG")
+            })
+        };
+        assert!(verify(&custom));
+        assert!(!verify(&SignatureLocator::new(".missing")));
+
+        sht[2].with::<ShType>(SectionType::Nobits);
+        assert!(custom.signed_region(&sht, &shstrtab, buf).is_none());
+        assert!(!verify(&custom));
+    }
+}
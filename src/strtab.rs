@@ -0,0 +1,254 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::Strtab;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// 在字符串表中查找以 NUL 结尾的 name，允许匹配其他字符串的尾部
+pub(crate) fn find_str(strtab: &[u8], name: &[u8]) -> Option<usize> {
+    let len = name.len() + 1;
+    if strtab.len() < len {
+        return None;
+    }
+    (0..=strtab.len() - len)
+        .find(|&i| &strtab[i..i + name.len()] == name && strtab[i + name.len()] == 0)
+}
+
+/// 读取 offset 处以 NUL 结尾的字符串，越界、缺少 NUL 或不是 UTF-8 时返回 None
+pub(crate) fn str_at(strtab: &[u8], offset: usize) -> Option<&str> {
+    let tail = strtab.get(offset..)?;
+    let end = tail.iter().position(|&b| b == 0)?;
+    std::str::from_utf8(&tail[..end]).ok()
+}
+
+/// 索引操作返回的 String 缓存，只为被索引过的偏移解码并保存一份 String。
+///
+/// 已插入的 String 在缓存被替换前既不会移除也不会移动（保存在 Box 中），
+/// 因此可以在只持有 &self 时返回其引用。
+#[derive(Debug, Clone, Default)]
+struct StrCache {
+    // 返回的是 &String，必须保证 String 本身的地址不随 HashMap 扩容而改变
+    #[allow(clippy::box_collection)]
+    strings: RefCell<HashMap<usize, Box<String>>>,
+}
+
+impl StrCache {
+    fn get(&self, bytes: &[u8], offset: usize) -> &String {
+        let mut strings = self.strings.borrow_mut();
+        let s: &String = strings.entry(offset).or_insert_with(|| {
+            let s =
+                str_at(bytes, offset).unwrap_or_else(|| panic!("无效的字符串表偏移 {}", offset));
+            Box::new(s.to_string())
+        });
+        // SAFETY: Box 中的 String 地址固定，且条目只会随整个缓存一起被替换，
+        // 替换需要 &mut self，因此引用在 &self 的生命周期内有效。
+        unsafe { &*(s as *const String) }
+    }
+}
+
+/// [`Strtab::entries`] 返回的迭代器
+#[derive(Debug, Clone)]
+pub struct StrtabEntries<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> StrtabEntries<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        StrtabEntries { bytes, offset: 0 }
+    }
+}
+
+impl<'a> Iterator for StrtabEntries<'a> {
+    type Item = (usize, &'a str);
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        loop {
+            let offset = self.offset;
+            let tail = self.bytes.get(offset..)?;
+            let end = tail.iter().position(|&b| b == 0)?;
+            self.offset += end + 1;
+            if let Ok(s) = std::str::from_utf8(&tail[..end]) {
+                return Some((offset, s));
+            }
+        }
+    }
+}
+
+/// 构建字符串表，相同的字符串只保存一次。
+///
+/// 与 [`Strtab::offset_of`] 的线性扫描不同，查重通过哈希表完成，适合批量插入。
+#[derive(Debug, Clone)]
+pub struct StrtabBuilder {
+    bytes: Vec<u8>,
+    map: HashMap<String, usize>,
+    cache: StrCache,
+}
+
+impl Default for StrtabBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StrtabBuilder {
+    /// 新建的字符串表只包含偏移 0 处的空字符串
+    pub fn new() -> Self {
+        let mut map = HashMap::new();
+        map.insert(String::new(), 0);
+        StrtabBuilder {
+            bytes: vec![0],
+            map,
+            cache: StrCache::default(),
+        }
+    }
+
+    /// 以已有的字符串表为基础，已有的字符串会参与查重
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut builder = StrtabBuilder {
+            bytes: bytes.to_vec(),
+            map: HashMap::new(),
+            cache: StrCache::default(),
+        };
+        if builder.bytes.last() != Some(&0) {
+            builder.bytes.push(0);
+        }
+        let mut start = 0;
+        for (i, &b) in builder.bytes.iter().enumerate() {
+            if b == 0 {
+                if let Ok(s) = std::str::from_utf8(&builder.bytes[start..i]) {
+                    builder.map.entry(s.to_string()).or_insert(start);
+                }
+                start = i + 1;
+            }
+        }
+        builder
+    }
+
+    /// 插入字符串并返回其偏移，已存在时直接返回原偏移
+    pub fn add(&mut self, s: &str) -> usize {
+        if let Some(&off) = self.map.get(s) {
+            return off;
+        }
+        let off = self.bytes.len();
+        self.bytes.extend_from_slice(s.as_bytes());
+        self.bytes.push(0);
+        self.map.insert(s.to_string(), off);
+        self.cache = StrCache::default();
+        off
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// 偏移无效时 panic，不确定偏移是否有效时应使用 [`Strtab::get`]
+impl std::ops::Index<usize> for StrtabBuilder {
+    type Output = String;
+    fn index(&self, offset: usize) -> &String {
+        self.cache.get(&self.bytes, offset)
+    }
+}
+
+impl Strtab for StrtabBuilder {
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// 直接引用文件数据的只读字符串表，[`Strtab::get`] 直接引用表中的字节，索引操作会缓存解码出的 String
+#[derive(Debug, Clone)]
+pub struct StrtabView<'a> {
+    bytes: &'a [u8],
+    cache: StrCache,
+}
+
+impl<'a> StrtabView<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        StrtabView {
+            bytes,
+            cache: StrCache::default(),
+        }
+    }
+}
+
+/// 偏移无效时 panic，不确定偏移是否有效时应使用 [`Strtab::get`]
+impl<'a> std::ops::Index<usize> for StrtabView<'a> {
+    type Output = String;
+    fn index(&self, offset: usize) -> &String {
+        self.cache.get(self.bytes, offset)
+    }
+}
+
+impl<'a> Strtab for StrtabView<'a> {
+    fn as_bytes(&self) -> &[u8] {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn offset_of_shares_tail() {
+        let mut b = StrtabBuilder::new();
+        let foobar = b.add("foobar");
+        assert_eq!(b.add("foobar"), foobar);
+        assert_eq!(b.offset_of("foobar"), Some(foobar));
+        assert_eq!(b.offset_of("bar"), Some(foobar + 3));
+        assert_eq!(b[foobar + 3], "bar");
+        b.add("baz");
+        assert_eq!(b[foobar], "foobar");
+        assert_eq!(b.offset_of(""), Some(0));
+        assert_eq!(b.offset_of("foo"), None);
+    }
+
+    #[test]
+    fn entries_with_offsets() {
+        let table = StrtabView::new(b"\0main\0printf\0.text\0");
+        let entries: Vec<_> = table.entries().collect();
+        assert_eq!(
+            entries,
+            [(0, ""), (1, "main"), (6, "printf"), (13, ".text")]
+        );
+        for (offset, s) in entries {
+            assert_eq!(table[offset], s);
+            assert_eq!(table.get(offset), Some(s));
+        }
+        assert_eq!(StrtabView::new(b"\0abc").entries().count(), 1);
+    }
+
+    #[test]
+    fn index_caches_only_accessed_offsets() {
+        let table = StrtabView::new(b"\0main\0printf\0");
+        let first: *const String = &table[1];
+        assert_eq!(table[6], "printf");
+        assert!(std::ptr::eq(first, &table[1]));
+        assert_eq!(table.cache.strings.borrow().len(), 2);
+        assert_eq!(table.get(100), None);
+    }
+}
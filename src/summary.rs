@@ -0,0 +1,343 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 解析结果的紧凑二进制序列化，用于跨进程传递（如进程外的符号化服务）。
+//!
+//! 格式为 `b"ESUM"`、1 字节版本号，之后是若干 `tag: u8, len: u32, payload` 记录，所有整数均为小端。
+//! 读取时跳过未知的 tag，并忽略记录中超出已知字段的部分，
+//! 因此新版本可以增加记录类型或在记录末尾追加字段，而不影响旧版本的读取。
+
+use crate::note::{NoteIter, NT_GNU_BUILD_ID};
+use crate::owned::{OwnedEhdr, OwnedElf};
+use crate::{strtab, ElfError, Note, SectionType};
+
+const MAGIC: &[u8; 4] = b"ESUM";
+/// 当前的格式版本
+pub const SUMMARY_VERSION: u8 = 1;
+
+const TAG_HEADER: u8 = 1;
+const TAG_SECTION: u8 = 2;
+const TAG_SYMBOL: u8 = 3;
+const TAG_BUILD_ID: u8 = 4;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SectionSummary {
+    pub name: String,
+    pub sh_type: SectionType,
+    pub sh_flags: u64,
+    pub sh_addr: u64,
+    pub sh_size: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SymbolSummary {
+    pub name: String,
+    pub value: u64,
+    pub size: u64,
+    pub info: u8,
+    pub shndx: u16,
+}
+
+/// 从文件中提取的元数据：header、section 概要、符号与 build-id
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ElfSummary {
+    pub header: OwnedEhdr,
+    pub sections: Vec<SectionSummary>,
+    /// `.symtab` 中的符号，没有 `.symtab` 时为 `.dynsym` 中的符号
+    pub symbols: Vec<SymbolSummary>,
+    pub build_id: Option<Vec<u8>>,
+}
+
+impl ElfSummary {
+    pub fn from_elf(elf: &OwnedElf) -> Self {
+        let sections = elf
+            .shdrs
+            .iter()
+            .enumerate()
+            .map(|(i, shdr)| SectionSummary {
+                name: elf.section_name(i).unwrap_or("").to_string(),
+                sh_type: shdr.sh_type.into(),
+                sh_flags: shdr.sh_flags,
+                sh_addr: shdr.sh_addr,
+                sh_size: shdr.sh_size,
+            })
+            .collect();
+        ElfSummary {
+            header: elf.ehdr,
+            sections,
+            symbols: symbols(elf),
+            build_id: build_id(elf),
+        }
+    }
+
+    /// 序列化为紧凑的二进制格式
+    pub fn serialize_summary(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(SUMMARY_VERSION);
+        let h = &self.header;
+        let mut p = h.ident.to_vec();
+        put_u16(&mut p, h.e_type);
+        put_u16(&mut p, h.e_machine);
+        put_u32(&mut p, h.e_version);
+        put_u64(&mut p, h.e_entry);
+        put_u64(&mut p, h.e_phoff);
+        put_u64(&mut p, h.e_shoff);
+        put_u32(&mut p, h.e_flags);
+        for v in &[
+            h.e_ehsize,
+            h.e_phentsize,
+            h.e_phnum,
+            h.e_shentsize,
+            h.e_shnum,
+            h.e_shstrndx,
+        ] {
+            put_u16(&mut p, *v);
+        }
+        record(&mut out, TAG_HEADER, &p);
+        for sec in &self.sections {
+            let mut p = Vec::new();
+            put_str(&mut p, &sec.name);
+            put_u32(&mut p, sec.sh_type.raw());
+            put_u64(&mut p, sec.sh_flags);
+            put_u64(&mut p, sec.sh_addr);
+            put_u64(&mut p, sec.sh_size);
+            record(&mut out, TAG_SECTION, &p);
+        }
+        for sym in &self.symbols {
+            let mut p = Vec::new();
+            put_str(&mut p, &sym.name);
+            put_u64(&mut p, sym.value);
+            put_u64(&mut p, sym.size);
+            p.push(sym.info);
+            put_u16(&mut p, sym.shndx);
+            record(&mut out, TAG_SYMBOL, &p);
+        }
+        if let Some(id) = &self.build_id {
+            record(&mut out, TAG_BUILD_ID, id);
+        }
+        out
+    }
+
+    /// 反序列化 [`ElfSummary::serialize_summary`] 的结果，未知的记录会被跳过
+    pub fn deserialize_summary(bytes: &[u8]) -> Result<Self, ElfError> {
+        if bytes.len() < 5 || &bytes[..4] != MAGIC {
+            return Err(ElfError::Malformed("bad summary magic"));
+        }
+        if bytes[4] == 0 {
+            return Err(ElfError::Unsupported("summary version 0"));
+        }
+        let mut summary = ElfSummary::default();
+        let mut rest = Cursor(&bytes[5..]);
+        while !rest.0.is_empty() {
+            let tag = rest.u8()?;
+            let len = rest.u32()? as usize;
+            let mut p = Cursor(rest.take(len)?);
+            match tag {
+                TAG_HEADER => {
+                    let h = &mut summary.header;
+                    h.ident.copy_from_slice(p.take(16)?);
+                    h.e_type = p.u16()?;
+                    h.e_machine = p.u16()?;
+                    h.e_version = p.u32()?;
+                    h.e_entry = p.u64()?;
+                    h.e_phoff = p.u64()?;
+                    h.e_shoff = p.u64()?;
+                    h.e_flags = p.u32()?;
+                    h.e_ehsize = p.u16()?;
+                    h.e_phentsize = p.u16()?;
+                    h.e_phnum = p.u16()?;
+                    h.e_shentsize = p.u16()?;
+                    h.e_shnum = p.u16()?;
+                    h.e_shstrndx = p.u16()?;
+                }
+                TAG_SECTION => summary.sections.push(SectionSummary {
+                    name: p.str()?,
+                    sh_type: p.u32()?.into(),
+                    sh_flags: p.u64()?,
+                    sh_addr: p.u64()?,
+                    sh_size: p.u64()?,
+                }),
+                TAG_SYMBOL => summary.symbols.push(SymbolSummary {
+                    name: p.str()?,
+                    value: p.u64()?,
+                    size: p.u64()?,
+                    info: p.u8()?,
+                    shndx: p.u16()?,
+                }),
+                TAG_BUILD_ID => summary.build_id = Some(p.0.to_vec()),
+                _ => {}
+            }
+        }
+        Ok(summary)
+    }
+}
+
+fn symbols(elf: &OwnedElf) -> Vec<SymbolSummary> {
+    let idx = match elf
+        .find_section(SectionType::Symtab.raw())
+        .or_else(|| elf.find_section(SectionType::Dynsym.raw()))
+    {
+        Some(idx) => idx,
+        None => return Vec::new(),
+    };
+    let strtab = elf
+        .section_data(elf.shdrs[idx].sh_link as usize)
+        .unwrap_or(&[]);
+    elf.symbols(idx)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|sym| SymbolSummary {
+            name: strtab::str_at(strtab, sym.st_name as usize)
+                .unwrap_or("")
+                .to_string(),
+            value: sym.st_value,
+            size: sym.st_size,
+            info: sym.st_info,
+            shndx: sym.st_shndx,
+        })
+        .collect()
+}
+
+fn build_id(elf: &OwnedElf) -> Option<Vec<u8>> {
+    let encode = elf.encode().ok()?;
+    elf.shdrs
+        .iter()
+        .enumerate()
+        .filter(|(_, shdr)| shdr.sh_type == SectionType::Note.raw())
+        .filter_map(|(i, shdr)| {
+            let data = elf.section_data(i).ok()?;
+            NoteIter::new(data, encode, shdr.sh_addralign as usize)
+                .find(|n| n.name() == b"GNU" && n.n_type() == NT_GNU_BUILD_ID)
+                .map(|n| n.desc().to_vec())
+        })
+        .next()
+}
+
+fn record(out: &mut Vec<u8>, tag: u8, payload: &[u8]) {
+    out.push(tag);
+    put_u32(out, payload.len() as u32);
+    out.extend_from_slice(payload);
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_u32(out, s.len() as u32);
+    out.extend_from_slice(s.as_bytes());
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ElfError> {
+        if self.0.len() < len {
+            return Err(ElfError::Malformed("truncated summary"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+    fn u8(&mut self) -> Result<u8, ElfError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, ElfError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+    fn u32(&mut self) -> Result<u32, ElfError> {
+        let mut b = [0u8; 4];
+        b.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(b))
+    }
+    fn u64(&mut self) -> Result<u64, ElfError> {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+    fn str(&mut self) -> Result<String, ElfError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| ElfError::Malformed("summary string is not utf-8"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::elf64_ident;
+
+    fn sample() -> ElfSummary {
+        ElfSummary {
+            header: OwnedEhdr {
+                ident: elf64_ident(),
+                e_type: 3,
+                e_machine: 62,
+                e_entry: 0x1040,
+                e_shnum: 2,
+                ..Default::default()
+            },
+            sections: vec![
+                SectionSummary::default(),
+                SectionSummary {
+                    name: ".text".to_string(),
+                    sh_type: SectionType::Progbits,
+                    sh_flags: 6,
+                    sh_addr: 0x1000,
+                    sh_size: 0x40,
+                },
+            ],
+            symbols: vec![SymbolSummary {
+                name: "main".to_string(),
+                value: 0x1040,
+                size: 12,
+                info: 0x12,
+                shndx: 1,
+            }],
+            build_id: Some(vec![0xde, 0xad, 0xbe, 0xef]),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let summary = sample();
+        let bytes = summary.serialize_summary();
+        assert_eq!(ElfSummary::deserialize_summary(&bytes), Ok(summary));
+    }
+
+    #[test]
+    fn skips_unknown_records() {
+        let summary = sample();
+        let mut bytes = summary.serialize_summary();
+        bytes[4] = SUMMARY_VERSION + 1;
+        record(&mut bytes, 0xff, b"from the future");
+        assert_eq!(ElfSummary::deserialize_summary(&bytes), Ok(summary));
+        assert!(ElfSummary::deserialize_summary(&bytes[..bytes.len() - 3]).is_err());
+    }
+}
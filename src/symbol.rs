@@ -0,0 +1,278 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 符号查找
+
+use crate::codec::Codec;
+use crate::hash::{GnuHash, SysvHash};
+use crate::header::SHN_LORESERVE;
+use crate::owned::OwnedSym;
+use crate::util::clamped_slice;
+use crate::{Class, SectionType, Shdr, ShdrTab, Strtab, StrtabView, Sym, Symtab};
+use accessor::Encode;
+use std::fmt;
+
+pub const SHN_UNDEF: u16 = 0;
+pub const SHN_ABS: u16 = 0xfff1;
+pub const SHN_COMMON: u16 = 0xfff2;
+
+pub const STB_LOCAL: u8 = 0;
+pub const STB_GLOBAL: u8 = 1;
+pub const STB_WEAK: u8 = 2;
+
+/// 符号 idx 的名称是否为 name
+fn name_is<T: Sym>(symtab: &impl Symtab<T>, strtab: &impl Strtab, idx: usize, name: &str) -> bool {
+    symtab
+        .get(idx)
+        .and_then(|sym| strtab.get(sym.get::<T::Name>()))
+        == Some(name)
+}
+
+/// 线性扫描符号表，返回第一个名称为 name 的符号索引。
+///
+/// 每次查找都需要解析所有符号的名称，复杂度为 O(n)，
+/// 适用于没有 `.gnu.hash`/`.hash` 的文件（如可重定位文件）。
+pub fn find_symbol<T: Sym>(
+    symtab: &impl Symtab<T>,
+    strtab: &impl Strtab,
+    name: &str,
+) -> Option<usize> {
+    (0..symtab.len()).find(|&idx| name_is(symtab, strtab, idx, name))
+}
+
+/// 按名称查找符号，依次尝试：
+/// 1. `.gnu.hash`（gnu 不为 None 时）；
+/// 2. `.hash`（sysv 不为 None 时）；
+/// 3. [`find_symbol`] 的线性扫描，复杂度为 O(n)。
+///
+/// 哈希表存在时，其结果即为最终结果，不会再退回到线性扫描。
+pub fn lookup_symbol<T: Sym>(
+    symtab: &impl Symtab<T>,
+    strtab: &impl Strtab,
+    gnu: Option<&GnuHash>,
+    sysv: Option<&SysvHash>,
+    name: &str,
+) -> Option<usize> {
+    let matches = |idx| name_is(symtab, strtab, idx, name);
+    match (gnu, sysv) {
+        (Some(gnu), _) => gnu.lookup(name, matches),
+        (None, Some(sysv)) => sysv.lookup(name, matches),
+        (None, None) => find_symbol(symtab, strtab, name),
+    }
+}
+
+/// 直接引用文件数据的符号表，表项在读取时解码为 [`OwnedSym`]
+#[derive(Debug, Clone, Copy)]
+pub struct DynSymView<'a> {
+    data: &'a [u8],
+    class: Class,
+    encode: Encode,
+}
+
+impl<'a> DynSymView<'a> {
+    pub fn new(data: &'a [u8], class: Class, encode: Encode) -> Self {
+        DynSymView {
+            data,
+            class,
+            encode,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.data.len() / OwnedSym::size(self.class)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// 越界时返回 None
+    pub fn get(&self, idx: usize) -> Option<OwnedSym> {
+        if idx >= self.len() {
+            return None;
+        }
+        let c = Codec::new(self.class, self.encode);
+        OwnedSym::parse(c, self.data, idx * OwnedSym::size(self.class)).ok()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = OwnedSym> + 'a {
+        let view = *self;
+        (0..self.len()).filter_map(move |idx| view.get(idx))
+    }
+}
+
+/// 查找 `SHT_DYNSYM` 及其 sh_link 指向的字符串表。
+///
+/// class 为文件的 EI_CLASS。没有 `.dynsym`（如静态链接的文件）、
+/// sh_entsize 与 class 的符号大小不一致或数据超出 buf 时返回 None。
+pub fn dynamic_symbols<'a, T: Shdr>(
+    sht: &impl ShdrTab<T>,
+    buf: &'a [u8],
+    class: Class,
+) -> Option<(DynSymView<'a>, StrtabView<'a>)> {
+    let idx = sht
+        .iter()
+        .position(|shdr| shdr.get::<T::Type>() == SectionType::Dynsym)?;
+    if !sht[idx].entsize_ok(class) {
+        return None;
+    }
+    let symbols = clamped_slice(buf, &sht[idx].get::<T::SecRange>())?;
+    let strtab = clamped_slice(buf, &sht.linked(idx)?.get::<T::SecRange>())?;
+    Some((
+        DynSymView::new(symbols, class, sht.table().encode()),
+        StrtabView::new(strtab),
+    ))
+}
+
+/// 符号所在的 section，对应 `readelf -s` 的 Ndx 列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymSection<'a> {
+    /// SHN_UNDEF，未定义的符号
+    Undef,
+    /// SHN_ABS，不随重定位改变的绝对值
+    Abs,
+    /// SHN_COMMON，尚未分配空间的 common 块
+    Common,
+    /// 普通的 section，值为其名称
+    Named(&'a str),
+}
+
+impl<'a> SymSection<'a> {
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            SymSection::Undef => "UND",
+            SymSection::Abs => "ABS",
+            SymSection::Common => "COMMON",
+            SymSection::Named(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for SymSection<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 根据 st_shndx 得到符号所在的 section。
+///
+/// 其他保留索引（包括需要 `.symtab_shndx` 的 SHN_XINDEX）、索引越界或名称无效时返回 None。
+pub fn symbol_section_name<'a, S: Sym, T: Shdr>(
+    sym: &S,
+    sht: &'a impl ShdrTab<T>,
+    shstrtab: &'a impl Strtab,
+) -> Option<SymSection<'a>> {
+    match sym.get::<S::Shndx>() {
+        SHN_UNDEF => Some(SymSection::Undef),
+        SHN_ABS => Some(SymSection::Abs),
+        SHN_COMMON => Some(SymSection::Common),
+        SHN_LORESERVE..=u16::MAX => None,
+        idx => shstrtab
+            .get(sht.get(idx as usize)?.get::<T::Name>())
+            .map(SymSection::Named),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::*;
+    use accessor::Setter;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn words(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn lookup_precedence() {
+        let strtab = StrtabView::new(b"\0foo\0bar\0");
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 24]));
+        let symtab = table(&buf, 0..3 * 24, Sym64);
+        symtab[1].with::<StName>(1);
+        symtab[2].with::<StName>(5);
+
+        assert_eq!(find_symbol(&symtab, &strtab, "bar"), Some(2));
+        assert_eq!(find_symbol(&symtab, &strtab, ""), Some(0));
+        assert_eq!(find_symbol(&symtab, &strtab, "qux"), None);
+
+        // 没有 bucket 的 .gnu.hash 查不到任何符号，.hash 的链为 2 -> 1 -> 0
+        let gnu = GnuHash::parse(&words(&[0, 1, 1, 6, 0, 0]), Class::Elf64, Encode::Le).unwrap();
+        let sysv = SysvHash::parse(&words(&[1, 3, 2, 0, 0, 1]), Encode::Le).unwrap();
+        let lookup = |gnu, sysv, name| lookup_symbol(&symtab, &strtab, gnu, sysv, name);
+        assert_eq!(lookup(Some(&gnu), Some(&sysv), "foo"), None);
+        assert_eq!(lookup(None, Some(&sysv), "foo"), Some(1));
+        assert_eq!(lookup(None, Some(&sysv), "qux"), None);
+        assert_eq!(lookup(None, None, "foo"), Some(1));
+    }
+
+    #[test]
+    fn dynsym_and_dynstr() {
+        let strtab = b"\0puts\0";
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 64 + 48 + strtab.len()]));
+        buf.borrow_mut()[3 * 64 + 24] = 1;
+        buf.borrow_mut()[3 * 64 + 48..].copy_from_slice(strtab);
+        let sht = table(&buf, 0..3 * 64, Shdr64);
+        sht[1]
+            .with::<ShType>(SectionType::Dynsym)
+            .with::<ShOffset>(3 * 64)
+            .with::<ShSize>(48)
+            .with::<ShEntsize>(24)
+            .with::<ShLink>(2);
+        sht[2]
+            .with::<ShType>(SectionType::Strtab)
+            .with::<ShOffset>(3 * 64 + 48)
+            .with::<ShSize>(strtab.len() as u64);
+
+        let bytes = buf.borrow().clone();
+        let (syms, names) = dynamic_symbols(&sht, &bytes, Class::Elf64).unwrap();
+        assert_eq!(syms.len(), 2);
+        assert_eq!(
+            names.get(syms.get(1).unwrap().st_name as usize),
+            Some("puts")
+        );
+        assert!(syms.get(2).is_none());
+
+        // 24 字节的表项与 ELF32 的符号大小不一致
+        assert!(dynamic_symbols(&sht, &bytes, Class::Elf32).is_none());
+        sht[1].with::<ShEntsize>(16);
+        assert!(dynamic_symbols(&sht, &bytes, Class::Elf64).is_none());
+
+        sht[1].with::<ShType>(SectionType::Progbits);
+        assert!(dynamic_symbols(&sht, &bytes, Class::Elf64).is_none());
+    }
+
+    #[test]
+    fn section_of_symbol() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 2 * 64 + 24]));
+        let sht = table(&buf, 0..2 * 64, Shdr64);
+        sht[1].with::<ShName>(1);
+        let shstrtab = StrtabView::new(b"\0.text\0");
+        let sym = Sym64(Obj {
+            buf: buf.clone(),
+            base: 2 * 64,
+            encode: Encode::Le,
+        });
+        let ndx = |shndx| {
+            sym.with::<StShndx>(shndx);
+            symbol_section_name(&sym, &sht, &shstrtab)
+        };
+        assert_eq!(ndx(1), Some(SymSection::Named(".text")));
+        assert_eq!(ndx(SHN_UNDEF).unwrap().to_string(), "UND");
+        assert_eq!(ndx(SHN_ABS), Some(SymSection::Abs));
+        assert_eq!(ndx(SHN_COMMON).map(|s| s.as_str()), Some("COMMON"));
+        assert_eq!(ndx(2), None);
+        assert_eq!(ndx(0xffff), None);
+    }
+}
@@ -0,0 +1,363 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::read_field;
+use crate::util::clamped_slice;
+use crate::{ElfError, ParseLimits};
+use accessor::*;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use std::ops::Range;
+
+/// 由定长表项组成的表，如 Section Header Table、Program Header Table、符号表等。
+///
+/// 表项的大小由 `T::range()` 决定。构造时只记录每个表项的范围，字段的值在读取时才解码。
+pub struct Table<T> {
+    range: Range<usize>,
+    encode: Encode,
+    entries: Vec<T>,
+}
+
+impl<T: Field + Getter> Table<T> {
+    /// 表项的字节长度
+    pub fn entsize() -> usize {
+        T::range().len()
+    }
+
+    /// `range` 为表在文件中的范围，`f` 根据表项的范围构造表项。
+    /// 末尾不足一个表项的字节会被忽略。
+    ///
+    /// 表项会按 range 的长度全部预先构造，range 只应来自可信的输入；
+    /// range 来自未经检查的文件时应使用 [`Table::with_limits`]。
+    pub fn new<F>(range: Range<usize>, encode: Encode, f: F) -> Self
+    where
+        F: FnMut(Range<usize>, Encode) -> T,
+    {
+        let count = Self::count(&range);
+        Self::build(range, encode, count, f)
+    }
+
+    /// 与 [`Table::new`] 相同，表项数量按 limits 与 limit 限制（见 [`ParseLimits::cap`]）。
+    ///
+    /// 截断时 `range()` 保持不变，表中只包含前 limit 项。
+    pub fn with_limits<F>(
+        range: Range<usize>,
+        encode: Encode,
+        limits: &ParseLimits,
+        limit: usize,
+        f: F,
+    ) -> Result<Self, ElfError>
+    where
+        F: FnMut(Range<usize>, Encode) -> T,
+    {
+        let count = limits.cap("table entries", Self::count(&range), limit)?;
+        Ok(Self::build(range, encode, count, f))
+    }
+
+    fn count(range: &Range<usize>) -> usize {
+        range.len().checked_div(Self::entsize()).unwrap_or(0)
+    }
+
+    fn build<F>(range: Range<usize>, encode: Encode, count: usize, mut f: F) -> Self
+    where
+        F: FnMut(Range<usize>, Encode) -> T,
+    {
+        let entsize = Self::entsize();
+        let entries = (0..count)
+            .map(|i| {
+                let start = range.start + i * entsize;
+                f(start..start + entsize, encode)
+            })
+            .collect();
+        Table {
+            range,
+            encode,
+            entries,
+        }
+    }
+}
+
+impl<T> Table<T> {
+    /// 表在文件中的范围
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+    pub fn encode(&self) -> Encode {
+        self.encode
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// 越界时返回 None
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        self.entries.get(idx)
+    }
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        self.entries.get_mut(idx)
+    }
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.entries.iter_mut()
+    }
+    pub fn iter(&self) -> TableIter<'_, T> {
+        TableIter {
+            inner: self.entries.iter(),
+        }
+    }
+    /// 遍历 range 中的表项，超出表长的部分被忽略
+    pub fn iter_range(&self, range: Range<usize>) -> TableIter<'_, T> {
+        let end = range.end.min(self.entries.len());
+        TableIter {
+            inner: self.entries[range.start.min(end)..end].iter(),
+        }
+    }
+}
+
+impl<T> std::ops::Index<usize> for Table<T> {
+    type Output = T;
+    /// 越界时 panic，不希望 panic 时应使用 [`Table::get`]
+    fn index(&self, idx: usize) -> &T {
+        self.get(idx).expect("表项索引越界")
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Table<T> {
+    type Item = &'a T;
+    type IntoIter = TableIter<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// 按顺序遍历表中的表项
+pub struct TableIter<'a, T> {
+    inner: std::slice::Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for TableIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for TableIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for TableIter<'a, T> {}
+
+/// 直接借用字节切片、表项大小在编译期确定的表，不做任何堆分配，只依赖 `core`。
+///
+/// T 为表项的类型，仅用于在构造时检查 `T::range()` 与 ENTSIZE 一致；
+/// 表项以 [`FixedEntry`] 的形式按值返回，字段在读取时才解码。
+pub struct FixedTable<'a, T, const ENTSIZE: usize> {
+    bytes: &'a [u8],
+    encode: Encode,
+    _entry: PhantomData<T>,
+}
+
+impl<'a, T: Field, const ENTSIZE: usize> FixedTable<'a, T, ENTSIZE> {
+    /// bytes 为整个表的内容，末尾不足一个表项的字节会被忽略。
+    ///
+    /// ENTSIZE 与 `T::range()` 的长度不一致时 panic。
+    pub fn new(bytes: &'a [u8], encode: Encode) -> Self {
+        assert_eq!(T::range().len(), ENTSIZE, "ENTSIZE 与表项大小不一致");
+        FixedTable {
+            bytes,
+            encode,
+            _entry: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, const ENTSIZE: usize> FixedTable<'a, T, ENTSIZE> {
+    pub fn encode(&self) -> Encode {
+        self.encode
+    }
+    pub fn len(&self) -> usize {
+        self.bytes.len() / ENTSIZE
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// 第 idx 个表项的原始字节，越界时返回 None
+    pub fn bytes(&self, idx: usize) -> Option<&'a [u8; ENTSIZE]> {
+        let start = idx.checked_mul(ENTSIZE)?;
+        let bytes = clamped_slice(self.bytes, &(start..start.checked_add(ENTSIZE)?))?;
+        <&[u8; ENTSIZE]>::try_from(bytes).ok()
+    }
+    /// 越界时返回 None
+    pub fn get(&self, idx: usize) -> Option<FixedEntry<'a, ENTSIZE>> {
+        self.bytes(idx).map(|bytes| FixedEntry {
+            bytes,
+            encode: self.encode,
+        })
+    }
+    pub fn iter(&self) -> FixedTableIter<'a, ENTSIZE> {
+        FixedTableIter {
+            inner: self.bytes.chunks_exact(ENTSIZE),
+            encode: self.encode,
+        }
+    }
+}
+
+impl<'a, T, const ENTSIZE: usize> core::ops::Index<usize> for FixedTable<'a, T, ENTSIZE> {
+    type Output = [u8; ENTSIZE];
+    /// 越界时 panic，不希望 panic 时应使用 [`FixedTable::bytes`]
+    fn index(&self, idx: usize) -> &[u8; ENTSIZE] {
+        self.bytes(idx).expect("表项索引越界")
+    }
+}
+
+/// [`FixedTable`] 中的一个表项
+#[derive(Debug, Clone, Copy)]
+pub struct FixedEntry<'a, const ENTSIZE: usize> {
+    bytes: &'a [u8; ENTSIZE],
+    encode: Encode,
+}
+
+impl<'a, const ENTSIZE: usize> FixedEntry<'a, ENTSIZE> {
+    pub fn as_bytes(&self) -> &'a [u8; ENTSIZE] {
+        self.bytes
+    }
+}
+
+impl<'a, const ENTSIZE: usize> Getter for FixedEntry<'a, ENTSIZE> {
+    fn get<F: Field>(&self) -> F::FieldType {
+        read_field::<F>(&self.bytes[F::range()], self.encode)
+    }
+}
+
+/// 按顺序遍历 [`FixedTable`] 中的表项
+pub struct FixedTableIter<'a, const ENTSIZE: usize> {
+    inner: core::slice::ChunksExact<'a, u8>,
+    encode: Encode,
+}
+
+impl<'a, const ENTSIZE: usize> Iterator for FixedTableIter<'a, ENTSIZE> {
+    type Item = FixedEntry<'a, ENTSIZE>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = <&[u8; ENTSIZE]>::try_from(self.inner.next()?).ok()?;
+        Some(FixedEntry {
+            bytes,
+            encode: self.encode,
+        })
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, const ENTSIZE: usize> ExactSizeIterator for FixedTableIter<'a, ENTSIZE> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field::Located;
+    use crate::test_util::{table, Obj, PType, PVaddr, Phdr64};
+    use crate::SegmentType;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn table_entries_in_order() {
+        // 从 8 开始的两个 ELF64 Program Header，末尾多出不足一个表项的 10 字节
+        let mut bytes = vec![0u8; 8 + 2 * 56 + 10];
+        bytes[8] = 3;
+        bytes[8 + 56] = 1;
+        let buf = Rc::new(RefCell::new(bytes));
+        let pht = table(&buf, 8..8 + 2 * 56 + 10, Phdr64);
+        assert_eq!(Table::<Phdr64>::entsize(), 56);
+        assert_eq!(pht.len(), 2);
+        assert_eq!(pht.range(), 8..8 + 2 * 56 + 10);
+
+        let types: Vec<_> = pht.iter().map(|phdr| phdr.get::<PType>()).collect();
+        assert_eq!(types, [SegmentType::Interp, SegmentType::Load]);
+        let bases: Vec<_> = (&pht).into_iter().map(|phdr| phdr.get_range()).collect();
+        assert_eq!(bases, [8..64, 64..120]);
+        assert_eq!(
+            pht.iter().next_back().unwrap().get::<PType>(),
+            SegmentType::Load
+        );
+        assert_eq!(pht.iter_range(1..5).len(), 1);
+
+        assert!(pht.get(1).is_some());
+        assert!(pht.get(2).is_none());
+        assert!(pht.get(usize::MAX).is_none());
+
+        let empty = table(&buf, 8..8 + 55, Phdr64);
+        assert!(empty.is_empty());
+        assert!(empty.get(0).is_none());
+    }
+
+    #[test]
+    fn limited_table() {
+        let buf = Rc::new(RefCell::new(vec![0u8; 3 * 56]));
+        let entry = |r: Range<usize>, encode: Encode| {
+            Phdr64(Obj {
+                buf: buf.clone(),
+                base: r.start,
+                encode,
+            })
+        };
+        // 范围声明了远超缓冲区的表项，构造前就被拒绝
+        let mut limits = ParseLimits::default();
+        let err = Table::with_limits(0..usize::MAX, Encode::Le, &limits, 2, entry).err();
+        assert_eq!(
+            err,
+            Some(ElfError::LimitExceeded {
+                what: "table entries",
+                count: usize::MAX / 56,
+                limit: 2
+            })
+        );
+
+        limits.truncate = true;
+        let pht = Table::with_limits(0..3 * 56, Encode::Le, &limits, 2, entry).unwrap();
+        assert_eq!(pht.len(), 2);
+        assert_eq!(pht.range(), 0..3 * 56);
+        let pht = Table::with_limits(0..56, Encode::Le, &limits, 2, entry).unwrap();
+        assert_eq!(pht.len(), 1);
+    }
+
+    #[test]
+    fn fixed_elf64_phdrs() {
+        let mut bytes = [0u8; 2 * 56 + 3];
+        bytes[56] = 1;
+        bytes[56 + 16..56 + 24].copy_from_slice(&0x40_0000u64.to_le_bytes());
+        let pht: FixedTable<Phdr64, 56> = FixedTable::new(&bytes, Encode::Le);
+        assert_eq!(pht.len(), 2);
+        assert_eq!(pht[1][0], 1);
+        let load = pht.get(1).unwrap();
+        assert_eq!(load.get::<PType>(), SegmentType::Load);
+        assert_eq!(load.get::<PVaddr>(), 0x40_0000);
+        assert!(pht.get(2).is_none());
+        let mut types = [SegmentType::Load; 2];
+        for (ty, phdr) in types.iter_mut().zip(pht.iter()) {
+            *ty = phdr.get::<PType>();
+        }
+        assert_eq!(types, [SegmentType::Null, SegmentType::Load]);
+    }
+}
@@ -0,0 +1,552 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 单元测试共用的 Getter/Setter 实现与字段定义，字段布局均为 ELF64。
+#![allow(dead_code)]
+
+use crate::owned::{OwnedEhdr, OwnedElf, OwnedPhdr, OwnedShdr};
+use crate::{read_field, write_field};
+use accessor::*;
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// 以共享缓冲区中 base 处开始的字节为内容的对象
+#[derive(Clone)]
+pub(crate) struct Obj {
+    pub buf: Rc<RefCell<Vec<u8>>>,
+    pub base: usize,
+    pub encode: Encode,
+}
+
+impl Getter for Obj {
+    fn get<T: Field>(&self) -> T::FieldType {
+        let r = T::range();
+        read_field::<T>(
+            &self.buf.borrow()[self.base + r.start..self.base + r.end],
+            self.encode,
+        )
+    }
+}
+
+impl Setter for Obj {
+    fn with<T: Field + Mutable>(&self, value: T::FieldType) -> &Self {
+        let r = self.base + T::range().start..self.base + T::range().end;
+        WINDOW.with(|w| *w.borrow_mut() = self.buf.borrow()[r.clone()].to_vec());
+        let bytes = write_field::<T>(value, self.encode);
+        WINDOW.with(|w| w.borrow_mut().clear());
+        self.buf.borrow_mut()[r].copy_from_slice(bytes.as_ref());
+        self
+    }
+}
+
+thread_local! {
+    /// Field 的编码函数拿不到原有的字节，[`Obj`] 写入前将字段范围内的原有字节放在这里，
+    /// 跨越其他字段的范围字段（如 p_offset 到 p_filesz）据此只改写属于自己的部分
+    static WINDOW: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 用 WINDOW 中的原有字节填充 out，长度不一致（不是经由 Obj 写入）时保持不变
+fn fill_window(out: &mut [u8]) {
+    WINDOW.with(|w| {
+        let w = w.borrow();
+        if w.len() == out.len() {
+            out.copy_from_slice(&w);
+        }
+    });
+}
+
+/// 定义以整数存储的字段，`$store` 为存储类型，`$ty` 为解码后的类型
+macro_rules! int_field {
+    ($name:ident: $ty:ty = $store:ty, $off:expr) => {
+        pub(crate) struct $name;
+        impl Field for $name {
+            type FieldType = $ty;
+            type BytesType = [u8; std::mem::size_of::<$store>()];
+            fn range() -> Range<usize> {
+                $off..$off + std::mem::size_of::<$store>()
+            }
+            fn from_le_bytes(val: &[u8]) -> $ty {
+                let mut b = [0u8; std::mem::size_of::<$store>()];
+                b.copy_from_slice(val);
+                <$store>::from_le_bytes(b) as $ty
+            }
+            fn from_be_bytes(val: &[u8]) -> $ty {
+                let mut b = [0u8; std::mem::size_of::<$store>()];
+                b.copy_from_slice(val);
+                <$store>::from_be_bytes(b) as $ty
+            }
+            fn to_le_bytes(val: $ty) -> Self::BytesType {
+                (val as $store).to_le_bytes()
+            }
+            fn to_be_bytes(val: $ty) -> Self::BytesType {
+                (val as $store).to_be_bytes()
+            }
+        }
+        impl Mutable for $name {}
+        crate::impl_field_size!($name => std::mem::size_of::<$store>());
+    };
+}
+
+/// 定义存储为整数、通过 From 与 $ty 互相转换的字段
+macro_rules! enum_field {
+    ($name:ident: $ty:ty = $store:ty, $off:expr) => {
+        pub(crate) struct $name;
+        impl Field for $name {
+            type FieldType = $ty;
+            type BytesType = [u8; std::mem::size_of::<$store>()];
+            fn range() -> Range<usize> {
+                $off..$off + std::mem::size_of::<$store>()
+            }
+            fn from_le_bytes(val: &[u8]) -> $ty {
+                let mut b = [0u8; std::mem::size_of::<$store>()];
+                b.copy_from_slice(val);
+                <$store>::from_le_bytes(b).into()
+            }
+            fn from_be_bytes(val: &[u8]) -> $ty {
+                let mut b = [0u8; std::mem::size_of::<$store>()];
+                b.copy_from_slice(val);
+                <$store>::from_be_bytes(b).into()
+            }
+            fn to_le_bytes(val: $ty) -> Self::BytesType {
+                <$store>::from(val).to_le_bytes()
+            }
+            fn to_be_bytes(val: $ty) -> Self::BytesType {
+                <$store>::from(val).to_be_bytes()
+            }
+        }
+        impl Mutable for $name {}
+        crate::impl_field_size!($name => std::mem::size_of::<$store>());
+    };
+}
+
+/// 定义由 offset 与 size 两个 u64 组成的范围字段，$off 与 $size 为两者在对象中的偏移，
+/// 写入时只改写这两个字段
+macro_rules! range_field {
+    ($name:ident, $off:expr, $size:expr) => {
+        pub(crate) struct $name;
+        impl $name {
+            fn decode(val: &[u8], encode: Encode) -> Range<usize> {
+                let c = crate::codec::Codec::new(crate::Class::Elf64, encode);
+                let start = c.uint(&val[..8]) as usize;
+                let size = c.uint(&val[$size - $off..$size - $off + 8]) as usize;
+                start..start + size
+            }
+            fn encode(val: Range<usize>, encode: Encode) -> [u8; $size - $off + 8] {
+                let c = crate::codec::Codec::new(crate::Class::Elf64, encode);
+                let mut out = [0u8; $size - $off + 8];
+                fill_window(&mut out);
+                c.put_uint(&mut out[..8], val.start as u64);
+                c.put_uint(&mut out[$size - $off..], val.len() as u64);
+                out
+            }
+        }
+        impl Field for $name {
+            type FieldType = Range<usize>;
+            type BytesType = [u8; $size - $off + 8];
+            fn range() -> Range<usize> {
+                $off..$size + 8
+            }
+            fn from_le_bytes(val: &[u8]) -> Range<usize> {
+                Self::decode(val, Encode::Le)
+            }
+            fn from_be_bytes(val: &[u8]) -> Range<usize> {
+                Self::decode(val, Encode::Be)
+            }
+            fn to_le_bytes(val: Range<usize>) -> Self::BytesType {
+                Self::encode(val, Encode::Le)
+            }
+            fn to_be_bytes(val: Range<usize>) -> Self::BytesType {
+                Self::encode(val, Encode::Be)
+            }
+        }
+        impl Mutable for $name {}
+        crate::impl_field_size!($name => $size - $off + 8);
+    };
+}
+
+/// 定义占 $size 字节的表项类型
+macro_rules! entry {
+    ($name:ident, $size:expr) => {
+        #[derive(Clone)]
+        pub(crate) struct $name(pub Obj);
+        impl Field for $name {
+            type FieldType = ();
+            type BytesType = [u8; 0];
+            fn range() -> Range<usize> {
+                0..$size
+            }
+            fn from_le_bytes(_: &[u8]) {}
+            fn from_be_bytes(_: &[u8]) {}
+            fn to_le_bytes(_: ()) -> [u8; 0] {
+                []
+            }
+            fn to_be_bytes(_: ()) -> [u8; 0] {
+                []
+            }
+        }
+        impl Getter for $name {
+            fn get<T: Field>(&self) -> T::FieldType {
+                self.0.get::<T>()
+            }
+        }
+        impl Setter for $name {
+            fn with<T: Field + Mutable>(&self, value: T::FieldType) -> &Self {
+                self.0.with::<T>(value);
+                self
+            }
+        }
+        impl crate::field::Located for $name {
+            fn get_range(&self) -> Range<usize> {
+                self.0.base..self.0.base + $size
+            }
+        }
+    };
+}
+
+/// 定义原样保存 $len 字节的字段
+macro_rules! bytes_field {
+    ($name:ident: $len:expr, $off:expr) => {
+        pub(crate) struct $name;
+        impl Field for $name {
+            type FieldType = [u8; $len];
+            type BytesType = [u8; $len];
+            fn range() -> Range<usize> {
+                $off..$off + $len
+            }
+            fn from_le_bytes(val: &[u8]) -> [u8; $len] {
+                let mut b = [0u8; $len];
+                b.copy_from_slice(val);
+                b
+            }
+            fn from_be_bytes(val: &[u8]) -> [u8; $len] {
+                Self::from_le_bytes(val)
+            }
+            fn to_le_bytes(val: [u8; $len]) -> [u8; $len] {
+                val
+            }
+            fn to_be_bytes(val: [u8; $len]) -> [u8; $len] {
+                val
+            }
+        }
+        impl Mutable for $name {}
+        crate::impl_field_size!($name => $len);
+    };
+}
+
+/// 由 e_phoff/e_shoff、表项大小与表项数三个字段组成的表范围，$off、$entsize 与 $num 为三者在 header 中的偏移。
+/// 写入时表项大小为 0 则取 $default，表项数为范围长度除以表项大小
+macro_rules! header_table_field {
+    ($name:ident, $off:expr, $entsize:expr, $num:expr, $default:expr) => {
+        pub(crate) struct $name;
+        impl $name {
+            fn decode(val: &[u8], encode: Encode) -> Range<usize> {
+                let c = crate::codec::Codec::new(crate::Class::Elf64, encode);
+                let at = |off: usize, len: usize| c.uint(&val[off - 32..off - 32 + len]) as usize;
+                let start = at($off, 8);
+                start..start + at($entsize, 2) * at($num, 2)
+            }
+            fn encode(val: Range<usize>, encode: Encode) -> [u8; 32] {
+                let c = crate::codec::Codec::new(crate::Class::Elf64, encode);
+                let at = |off: usize, len: usize| off - 32..off - 32 + len;
+                let mut out = [0u8; 32];
+                fill_window(&mut out);
+                let entsize = match c.uint(&out[at($entsize, 2)]) {
+                    0 => $default,
+                    size => size,
+                };
+                c.put_uint(&mut out[at($off, 8)], val.start as u64);
+                c.put_uint(&mut out[at($entsize, 2)], entsize);
+                c.put_uint(&mut out[at($num, 2)], val.len() as u64 / entsize);
+                out
+            }
+        }
+        impl Field for $name {
+            type FieldType = Range<usize>;
+            type BytesType = [u8; 32];
+            fn range() -> Range<usize> {
+                32..64
+            }
+            fn from_le_bytes(val: &[u8]) -> Range<usize> {
+                Self::decode(val, Encode::Le)
+            }
+            fn from_be_bytes(val: &[u8]) -> Range<usize> {
+                Self::decode(val, Encode::Be)
+            }
+            fn to_le_bytes(val: Range<usize>) -> [u8; 32] {
+                Self::encode(val, Encode::Le)
+            }
+            fn to_be_bytes(val: Range<usize>) -> [u8; 32] {
+                Self::encode(val, Encode::Be)
+            }
+        }
+        impl Mutable for $name {}
+        crate::impl_field_size!($name => 32);
+    };
+}
+
+pub(crate) struct EiData;
+impl Field for EiData {
+    type FieldType = Encode;
+    type BytesType = [u8; 1];
+    fn range() -> Range<usize> {
+        5..6
+    }
+    fn from_le_bytes(val: &[u8]) -> Encode {
+        if val[0] == 2 {
+            Encode::Be
+        } else {
+            Encode::Le
+        }
+    }
+    fn from_be_bytes(val: &[u8]) -> Encode {
+        Self::from_le_bytes(val)
+    }
+    fn to_le_bytes(val: Encode) -> [u8; 1] {
+        match val {
+            Encode::Le => [1],
+            Encode::Be => [2],
+        }
+    }
+    fn to_be_bytes(val: Encode) -> [u8; 1] {
+        Self::to_le_bytes(val)
+    }
+}
+impl Mutable for EiData {}
+
+bytes_field!(EiMagic: 4, 0);
+int_field!(EiClass: u8 = u8, 4);
+int_field!(EiDataRaw: u8 = u8, 5);
+int_field!(EiVersion: u8 = u8, 6);
+impl crate::field::FieldValidate for EiVersion {
+    fn validate(value: &u8) -> Result<(), crate::ElfError> {
+        crate::field::check_version(*value as u32)
+    }
+}
+int_field!(EiOsAbi: u8 = u8, 7);
+int_field!(EiAbiVersion: u8 = u8, 8);
+bytes_field!(EiPad: 7, 9);
+
+enum_field!(EType: crate::ObjectType = u16, 16);
+impl crate::field::FieldValidate for EType {
+    fn validate(value: &crate::ObjectType) -> Result<(), crate::ElfError> {
+        crate::field::check_object_type(*value)
+    }
+}
+enum_field!(EMachine: crate::Machine = u16, 18);
+int_field!(EVersion: u32 = u32, 20);
+int_field!(EEntry: u64 = u64, 24);
+int_field!(EPhoff: u64 = u64, 32);
+int_field!(EShoff: u64 = u64, 40);
+int_field!(EFlags: u32 = u32, 48);
+int_field!(EEhsize: u16 = u16, 52);
+int_field!(EPhentsize: u16 = u16, 54);
+int_field!(EPhnum: u16 = u16, 56);
+int_field!(EShentsize: u16 = u16, 58);
+int_field!(EShnum: u16 = u16, 60);
+int_field!(EShstrndx: usize = u16, 62);
+header_table_field!(EPhtRange, 32, 54, 56, 56);
+header_table_field!(EShtRange, 40, 58, 60, 64);
+
+entry!(Ehdr64, 64);
+
+impl crate::Ident for Ehdr64 {
+    type Encode = EiData;
+    type Data = EiDataRaw;
+    type Magic = EiMagic;
+    type Class = EiClass;
+    type Version = EiVersion;
+    type OsAbi = EiOsAbi;
+    type AbiVersion = EiAbiVersion;
+    type Pad = EiPad;
+}
+
+impl crate::Ehdr for Ehdr64 {
+    type PhtRange = EPhtRange;
+    type ShtRange = EShtRange;
+    type Shstrndx = EShstrndx;
+    type Machine = EMachine;
+    type EFlags = EFlags;
+    type Type = EType;
+    type Version = EVersion;
+    type Entry = EEntry;
+    type Ehsize = EEhsize;
+    type Phentsize = EPhentsize;
+    type Phnum = EPhnum;
+    type Shentsize = EShentsize;
+    type Shnum = EShnum;
+}
+
+int_field!(ShName: usize = u32, 0);
+enum_field!(ShType: crate::SectionType = u32, 4);
+int_field!(ShFlags: u64 = u64, 8);
+int_field!(ShOffset: u64 = u64, 24);
+int_field!(ShEntsize: u64 = u64, 56);
+range_field!(ShRange, 24, 32);
+int_field!(ShSize: u64 = u64, 32);
+int_field!(ShLink: usize = u32, 40);
+int_field!(ShInfo: usize = u32, 44);
+int_field!(ShAddralign: u64 = u64, 48);
+impl crate::field::FieldValidate for ShAddralign {
+    fn validate(value: &u64) -> Result<(), crate::ElfError> {
+        crate::field::check_alignment(*value)
+    }
+}
+enum_field!(ShFlagsField: crate::SectionFlags = u64, 8);
+
+entry!(Shdr64, 64);
+
+impl crate::Shdr for Shdr64 {
+    type SecRange = ShRange;
+    type Name = ShName;
+    type Offset = ShOffset;
+    type Type = ShType;
+    type Flags = ShFlags;
+    type Entsize = ShEntsize;
+    type Link = ShLink;
+    type Info = ShInfo;
+    type Addralign = ShAddralign;
+}
+
+enum_field!(PType: crate::SegmentType = u32, 0);
+int_field!(PFlags: u32 = u32, 4);
+enum_field!(PFlagsField: crate::segments::SegmentFlags = u32, 4);
+int_field!(POffset: u64 = u64, 8);
+int_field!(PVaddr: u64 = u64, 16);
+range_field!(PRange, 8, 32);
+int_field!(PFilesz: u64 = u64, 32);
+int_field!(PMemsz: u64 = u64, 40);
+
+entry!(Phdr64, 56);
+
+impl crate::Phdr for Phdr64 {
+    type SegRange = PRange;
+    type Offset = POffset;
+    type Type = PType;
+    type Flags = PFlags;
+    type Vaddr = PVaddr;
+    type Memsz = PMemsz;
+}
+
+int_field!(StName: usize = u32, 0);
+int_field!(StInfo: u8 = u8, 4);
+int_field!(StShndx: u16 = u16, 6);
+
+entry!(Sym64, 24);
+
+impl crate::Sym for Sym64 {
+    type Name = StName;
+    type Shndx = StShndx;
+    type Info = StInfo;
+}
+
+int_field!(ROffset: u64 = u64, 0);
+int_field!(RInfo: u64 = u64, 8);
+
+/// ELF64 r_info 的高 32 位
+pub(crate) struct RSym;
+impl Field for RSym {
+    type FieldType = u32;
+    type BytesType = [u8; 8];
+    fn range() -> Range<usize> {
+        8..16
+    }
+    fn from_le_bytes(val: &[u8]) -> u32 {
+        (RInfo::from_le_bytes(val) >> 32) as u32
+    }
+    fn from_be_bytes(val: &[u8]) -> u32 {
+        (RInfo::from_be_bytes(val) >> 32) as u32
+    }
+    /// 保留 r_info 低 32 位的类型
+    fn to_le_bytes(val: u32) -> [u8; 8] {
+        let mut old = [0u8; 8];
+        fill_window(&mut old);
+        RInfo::to_le_bytes(u64::from(val) << 32 | RInfo::from_le_bytes(&old) & 0xffff_ffff)
+    }
+    fn to_be_bytes(val: u32) -> [u8; 8] {
+        let mut old = [0u8; 8];
+        fill_window(&mut old);
+        RInfo::to_be_bytes(u64::from(val) << 32 | RInfo::from_be_bytes(&old) & 0xffff_ffff)
+    }
+}
+impl Mutable for RSym {}
+
+entry!(Rela64, 24);
+
+impl crate::Rel for Rela64 {
+    type Offset = ROffset;
+    type SymIdx = RSym;
+}
+
+int_field!(DTag: i64 = i64, 0);
+int_field!(DVal: u64 = u64, 8);
+
+entry!(Dyn64, 16);
+
+impl crate::Dyn for Dyn64 {
+    type Tag = DTag;
+    type Val = DVal;
+}
+
+/// 在 buf 的 range 上构造表
+pub(crate) fn table<T, F>(buf: &Rc<RefCell<Vec<u8>>>, range: Range<usize>, f: F) -> crate::Table<T>
+where
+    T: Field + Getter,
+    F: Fn(Obj) -> T,
+{
+    crate::Table::new(range, Encode::Le, |r, encode| {
+        f(Obj {
+            buf: buf.clone(),
+            base: r.start,
+            encode,
+        })
+    })
+}
+
+/// 小端 ELF64 的 ident
+pub(crate) fn elf64_ident() -> [u8; 16] {
+    let mut ident = [0u8; 16];
+    ident[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    ident
+}
+
+/// 带一个 PT_LOAD 与三个 section header 的 ELF64 文件，
+/// Program Header Table 位于 64，Section Header Table 位于 0x100
+pub(crate) fn sample_elf64() -> Vec<u8> {
+    let shdr = |sh_name| OwnedShdr {
+        sh_name,
+        ..Default::default()
+    };
+    OwnedElf {
+        ehdr: OwnedEhdr {
+            ident: elf64_ident(),
+            e_phoff: 64,
+            e_phnum: 1,
+            e_shoff: 0x100,
+            e_shnum: 3,
+            ..Default::default()
+        },
+        phdrs: vec![OwnedPhdr {
+            p_type: 1,
+            ..Default::default()
+        }],
+        shdrs: vec![shdr(0), shdr(1), shdr(2)],
+        data: Vec::new(),
+    }
+    .to_bytes()
+    .unwrap()
+}
@@ -0,0 +1,119 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 偏移计算中常用的辅助函数。
+//!
+//! 对齐值为 0 或 1 时表示不需要对齐；其他对齐值必须为 2 的幂，debug 构建下会检查。
+
+use crate::ElfError;
+use std::ops::Range;
+
+/// 文件中对齐字段（如 sh_addralign）允许的最大值，更大的对齐值按格式错误处理，避免补齐时分配过多内存
+pub const MAX_FIELD_ALIGN: u64 = 1 << 21;
+
+fn mask(align: usize) -> Option<usize> {
+    if align <= 1 {
+        return None;
+    }
+    debug_assert!(align.is_power_of_two(), "对齐值 {} 不是 2 的幂", align);
+    Some(align - 1)
+}
+
+/// 将 value 向上对齐到 align 的整数倍，溢出时返回 None
+pub fn checked_align_up(value: usize, align: usize) -> Option<usize> {
+    match mask(align) {
+        Some(mask) => value.checked_add(mask).map(|v| v & !mask),
+        None => Some(value),
+    }
+}
+
+/// 将 value 向上对齐到 align 的整数倍，溢出时 panic，不希望 panic 时应使用 [`checked_align_up`]
+pub fn align_up(value: usize, align: usize) -> usize {
+    checked_align_up(value, align).expect("对齐后的值溢出")
+}
+
+/// 将 value 向上对齐到来自文件的对齐值 align。
+///
+/// align 必须为 0、1 或不超过 [`MAX_FIELD_ALIGN`] 的 2 的幂，否则或对齐后溢出时返回 `ElfError::Malformed`。
+pub fn field_align_up(value: usize, align: u64) -> Result<usize, ElfError> {
+    if align > 1 && (!align.is_power_of_two() || align > MAX_FIELD_ALIGN) {
+        return Err(ElfError::Malformed("invalid alignment"));
+    }
+    checked_align_up(value, align as usize).ok_or(ElfError::Malformed("alignment overflows"))
+}
+
+/// 将 value 向下对齐到 align 的整数倍，不会溢出
+pub fn align_down(value: usize, align: usize) -> usize {
+    match mask(align) {
+        Some(mask) => value & !mask,
+        None => value,
+    }
+}
+
+/// buf 中 range 所指的部分，range 超出 buf 或起点大于终点时返回 None。
+///
+/// 按文件中的偏移与长度切片时都应通过该函数，而不是直接索引，避免格式错误的文件导致 panic。
+/// 除 `[u8]` 外也用于 view 借用的 `[Cell<u8>]`。
+pub fn clamped_slice<'a, T>(buf: &'a [T], range: &Range<usize>) -> Option<&'a [T]> {
+    if range.start > range.end || range.end > buf.len() {
+        return None;
+    }
+    Some(&buf[range.clone()])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn align_boundary() {
+        assert_eq!(align_up(13, 0), 13);
+        assert_eq!(align_up(13, 1), 13);
+        assert_eq!(align_up(13, 8), 16);
+        assert_eq!(align_up(16, 8), 16);
+        assert_eq!(align_down(13, 8), 8);
+        assert_eq!(align_down(usize::MAX, 8), usize::MAX - 7);
+
+        assert_eq!(checked_align_up(usize::MAX - 7, 8), Some(usize::MAX - 7));
+        assert_eq!(checked_align_up(usize::MAX - 6, 8), None);
+        assert_eq!(checked_align_up(usize::MAX, 1), Some(usize::MAX));
+    }
+
+    #[test]
+    fn field_alignment() {
+        assert_eq!(field_align_up(13, 0), Ok(13));
+        assert_eq!(field_align_up(13, 16), Ok(16));
+        assert_eq!(
+            field_align_up(13, MAX_FIELD_ALIGN),
+            Ok(MAX_FIELD_ALIGN as usize)
+        );
+        assert!(field_align_up(13, 24).is_err());
+        assert!(field_align_up(13, MAX_FIELD_ALIGN << 1).is_err());
+        assert!(field_align_up(usize::MAX, 8).is_err());
+    }
+
+    #[test]
+    fn clamped_ranges() {
+        let buf = [1u8, 2, 3, 4];
+        assert_eq!(clamped_slice(&buf, &(1..3)), Some(&buf[1..3]));
+        assert_eq!(clamped_slice(&buf, &(4..4)), Some(&[][..]));
+        assert_eq!(clamped_slice(&buf, &(0..5)), None);
+        assert_eq!(clamped_slice(&buf, &(usize::MAX..usize::MAX)), None);
+        let (start, end) = (3, 1);
+        assert_eq!(clamped_slice(&buf, &(start..end)), None);
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! GNU 符号版本：`.gnu.version`、`.gnu.version_r` 与 `.gnu.version_d`。
+//!
+//! 三个 section 的布局在 ELF32 与 ELF64 中相同，各 trait 只暴露按版本索引查找名称所需的操作，
+//! [`VersymView`]、[`VerneedView`] 与 [`VerdefView`] 为直接引用 section 数据的实现。
+
+use crate::codec::Codec;
+use crate::{Class, Strtab};
+use accessor::Encode;
+
+/// 版本索引的隐藏位，置位时符号只能通过带版本的名称引用
+pub const VERSYM_HIDDEN: u16 = 0x8000;
+/// 局部符号，没有版本
+pub const VER_NDX_LOCAL: u16 = 0;
+/// 全局符号，使用基础版本
+pub const VER_NDX_GLOBAL: u16 = 1;
+
+/// `.gnu.version`：与动态符号表一一对应的版本索引数组
+pub trait VerSym {
+    /// 第 sym_idx 个符号的版本索引原始值，包含隐藏位
+    fn version_index(&self, sym_idx: usize) -> Option<u16>;
+}
+
+/// `.gnu.version_r`：从其他文件导入的版本
+pub trait VerNeed {
+    /// vna_other 为 ndx 的 Vernaux 项的 vna_name，即版本名称在 `.dynstr` 中的偏移
+    fn name_of(&self, ndx: u16) -> Option<usize>;
+}
+
+/// `.gnu.version_d`：本文件定义的版本
+pub trait VerDef {
+    /// vd_ndx 为 ndx 的 Verdef 项的第一个 Verdaux 的 vda_name
+    fn name_of(&self, ndx: u16) -> Option<usize>;
+}
+
+/// 直接引用 `.gnu.version` 数据的 [`VerSym`]
+#[derive(Debug, Clone, Copy)]
+pub struct VersymView<'a> {
+    data: &'a [u8],
+    codec: Codec,
+}
+
+impl<'a> VersymView<'a> {
+    pub fn new(data: &'a [u8], encode: Encode) -> Self {
+        VersymView {
+            data,
+            codec: Codec::new(Class::Elf32, encode),
+        }
+    }
+}
+
+impl<'a> VerSym for VersymView<'a> {
+    fn version_index(&self, sym_idx: usize) -> Option<u16> {
+        let off = sym_idx.checked_mul(2)?;
+        self.codec.read(self.data, off, 2).ok().map(|v| v as u16)
+    }
+}
+
+/// 按 `*_next` 字段遍历链表，返回每项的起始偏移；
+/// 链表项数不超过 data 所能容纳的数量，防止构造的循环链导致死循环
+fn chain(
+    c: Codec,
+    data: &[u8],
+    start: usize,
+    next_at: usize,
+    entsize: usize,
+) -> impl Iterator<Item = usize> + '_ {
+    let mut off = Some(start);
+    (0..data.len() / entsize).map_while(move |_| {
+        let cur = off?;
+        data.get(cur..cur.checked_add(entsize)?)?;
+        let next = c.read(data, cur + next_at, 4).ok()? as usize;
+        off = if next == 0 {
+            None
+        } else {
+            cur.checked_add(next)
+        };
+        Some(cur)
+    })
+}
+
+/// 直接引用 `.gnu.version_r` 数据的 [`VerNeed`]
+#[derive(Debug, Clone, Copy)]
+pub struct VerneedView<'a> {
+    data: &'a [u8],
+    codec: Codec,
+}
+
+impl<'a> VerneedView<'a> {
+    pub fn new(data: &'a [u8], encode: Encode) -> Self {
+        VerneedView {
+            data,
+            codec: Codec::new(Class::Elf32, encode),
+        }
+    }
+}
+
+impl<'a> VerNeed for VerneedView<'a> {
+    fn name_of(&self, ndx: u16) -> Option<usize> {
+        let (c, data) = (self.codec, self.data);
+        // Elf_Verneed：vn_version, vn_cnt, vn_file, vn_aux, vn_next
+        // Elf_Vernaux：vna_hash, vna_flags, vna_other, vna_name, vna_next
+        chain(c, data, 0, 12, 16).find_map(|need| {
+            let aux = need.checked_add(c.read(data, need + 8, 4).ok()? as usize)?;
+            chain(c, data, aux, 12, 16)
+                .find(|&aux| c.read(data, aux + 6, 2).ok() == Some(ndx as u64))
+                .and_then(|aux| c.read(data, aux + 8, 4).ok())
+                .map(|name| name as usize)
+        })
+    }
+}
+
+/// 直接引用 `.gnu.version_d` 数据的 [`VerDef`]
+#[derive(Debug, Clone, Copy)]
+pub struct VerdefView<'a> {
+    data: &'a [u8],
+    codec: Codec,
+}
+
+impl<'a> VerdefView<'a> {
+    pub fn new(data: &'a [u8], encode: Encode) -> Self {
+        VerdefView {
+            data,
+            codec: Codec::new(Class::Elf32, encode),
+        }
+    }
+}
+
+impl<'a> VerDef for VerdefView<'a> {
+    fn name_of(&self, ndx: u16) -> Option<usize> {
+        let (c, data) = (self.codec, self.data);
+        // Elf_Verdef：vd_version, vd_flags, vd_ndx, vd_cnt, vd_hash, vd_aux, vd_next
+        // Elf_Verdaux：vda_name, vda_next
+        let def = chain(c, data, 0, 16, 20)
+            .find(|&def| c.read(data, def + 4, 2).ok() == Some(ndx as u64))?;
+        let aux = def.checked_add(c.read(data, def + 12, 4).ok()? as usize)?;
+        c.read(data, aux, 4).ok().map(|name| name as usize)
+    }
+}
+
+/// 第 sym_idx 个动态符号所绑定的版本名称，如导入的 `printf` 得到 `"GLIBC_2.2.5"`。
+///
+/// 版本索引去掉隐藏位后先在 verneed（导入的版本）中查找，再在 verdef（本文件定义的版本）中查找。
+/// [`VER_NDX_LOCAL`] 与 [`VER_NDX_GLOBAL`] 表示符号没有特定版本，返回 None；
+/// 文件没有 `.gnu.version_r` 或 `.gnu.version_d` 时可以传入空数据构造的 view。
+pub fn symbol_version<'a>(
+    versym: &impl VerSym,
+    verneed: &impl VerNeed,
+    verdef: &impl VerDef,
+    dynstr: &'a impl Strtab,
+    sym_idx: usize,
+) -> Option<&'a str> {
+    let ndx = versym.version_index(sym_idx)? & !VERSYM_HIDDEN;
+    if ndx == VER_NDX_LOCAL || ndx == VER_NDX_GLOBAL {
+        return None;
+    }
+    let name = verneed.name_of(ndx).or_else(|| verdef.name_of(ndx))?;
+    dynstr.get(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::StrtabView;
+
+    fn put(out: &mut Vec<u8>, words: &[(u32, usize)]) {
+        for &(val, size) in words {
+            out.extend_from_slice(&val.to_le_bytes()[..size]);
+        }
+    }
+
+    #[test]
+    fn imported_version() {
+        let dynstr = StrtabView::new(b"\0libc.so.6\0GLIBC_2.2.5\0GLIBC_2.3\0LIBFOO_1.0\0");
+        let mut versym = Vec::new();
+        put(
+            &mut versym,
+            &[(0, 2), (1, 2), (2, 2), (3 | 0x8000, 2), (4, 2)],
+        );
+
+        // 一个 Verneed，两个 Vernaux
+        let mut verneed = Vec::new();
+        put(&mut verneed, &[(1, 2), (2, 2), (1, 4), (16, 4), (0, 4)]);
+        put(&mut verneed, &[(0, 4), (0, 2), (2, 2), (11, 4), (16, 4)]);
+        put(&mut verneed, &[(0, 4), (0, 2), (3, 2), (23, 4), (0, 4)]);
+
+        // 基础版本与 LIBFOO_1.0 两个 Verdef
+        let mut verdef = Vec::new();
+        put(
+            &mut verdef,
+            &[(1, 2), (1, 2), (1, 2), (1, 2), (0, 4), (20, 4), (28, 4)],
+        );
+        put(&mut verdef, &[(1, 4), (0, 4)]);
+        put(
+            &mut verdef,
+            &[(1, 2), (0, 2), (4, 2), (1, 2), (0, 4), (20, 4), (0, 4)],
+        );
+        put(&mut verdef, &[(33, 4), (0, 4)]);
+
+        let versym = VersymView::new(&versym, Encode::Le);
+        let verneed = VerneedView::new(&verneed, Encode::Le);
+        let verdef = VerdefView::new(&verdef, Encode::Le);
+        let version = |idx| symbol_version(&versym, &verneed, &verdef, &dynstr, idx);
+        assert_eq!(version(0), None);
+        assert_eq!(version(1), None);
+        assert_eq!(version(2), Some("GLIBC_2.2.5"));
+        assert_eq!(version(3), Some("GLIBC_2.3"));
+        assert_eq!(version(4), Some("LIBFOO_1.0"));
+        assert_eq!(version(5), None);
+    }
+}
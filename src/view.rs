@@ -0,0 +1,240 @@
+// Copyright (C) 2020 sanfusu@foxmail.com
+//
+// This file is part of accessor.
+//
+// accessor is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// accessor is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with accessor.  If not, see <http://www.gnu.org/licenses/>.
+
+//! 直接借用 `&mut [u8]` 的 header 对象，同时实现 Getter 与 Setter。
+//!
+//! `Setter::with` 接收 `&self`，因此缓冲区以 `&[Cell<u8>]` 的形式保存。
+//! `Cell<u8>` 与 `u8` 布局相同，既不需要 `Rc<RefCell<..>>` 也没有运行时借用检查；
+//! 生命周期 `'a` 保证 view 存在期间原缓冲区不会被其他代码访问。
+
+use crate::class_field::ClassField;
+use crate::codec::Codec;
+use crate::util::clamped_slice;
+use crate::{read_field, write_field, Class, ElfError};
+use accessor::*;
+use std::cell::Cell;
+use std::ops::Range;
+
+/// 将 cells 拷贝到栈上后调用 f，字段通常不超过 16 字节
+pub(crate) fn read_cells<R>(cells: &[Cell<u8>], f: impl FnOnce(&[u8]) -> R) -> R {
+    let mut tmp = [0u8; 32];
+    if cells.len() <= tmp.len() {
+        for (t, c) in tmp.iter_mut().zip(cells) {
+            *t = c.get();
+        }
+        f(&tmp[..cells.len()])
+    } else {
+        f(&cells.iter().map(Cell::get).collect::<Vec<_>>())
+    }
+}
+
+/// cells 中 range 所指的部分，超出时返回 `ElfError::OutOfBounds`
+pub(crate) fn checked_cells(
+    cells: &[Cell<u8>],
+    range: Range<usize>,
+) -> Result<&[Cell<u8>], ElfError> {
+    clamped_slice(cells, &range).ok_or(ElfError::OutOfBounds(range))
+}
+
+/// 按 encode 将字段 T 的值写入 cells
+pub(crate) fn write_cells<T: Field>(cells: &[Cell<u8>], value: T::FieldType, encode: Encode) {
+    let bytes = write_field::<T>(value, encode);
+    for (c, b) in cells.iter().zip(bytes.as_ref()) {
+        c.set(*b);
+    }
+}
+
+macro_rules! header_view {
+    ($(#[$doc:meta])* $name:ident, $size:ident) => {
+        $(#[$doc])*
+        pub struct $name<'a> {
+            bytes: &'a [Cell<u8>],
+            class: Class,
+            encode: Encode,
+        }
+
+        impl<'a> $name<'a> {
+            /// buf 从 header 开始，长度不足一个 header 时返回 `ElfError::OutOfBounds`，
+            /// 超出的部分不属于该 view
+            pub fn new(buf: &'a mut [u8], class: Class, encode: Encode) -> Result<Self, ElfError> {
+                let size = class.$size();
+                if buf.len() < size {
+                    return Err(ElfError::OutOfBounds(0..size));
+                }
+                Ok($name {
+                    bytes: Cell::from_mut(&mut buf[..size]).as_slice_of_cells(),
+                    class,
+                    encode,
+                })
+            }
+
+            pub fn class(&self) -> Class {
+                self.class
+            }
+
+            pub fn encode(&self) -> Encode {
+                self.encode
+            }
+
+            /// 按 view 的 Class 读取字段 F，字段超出 header 时 panic
+            pub fn get_class<F: ClassField>(&self) -> u64 {
+                self.try_get_class::<F>().expect("字段超出 header")
+            }
+
+            /// 与 [`get_class`](Self::get_class) 相同，字段超出 header 时返回 `ElfError::OutOfBounds`
+            pub fn try_get_class<F: ClassField>(&self) -> Result<u64, ElfError> {
+                let c = Codec::new(self.class, self.encode);
+                let cells = checked_cells(self.bytes, F::range_of(self.class))?;
+                Ok(read_cells(cells, |b| c.uint(b)))
+            }
+
+            /// 按 view 的 Class 以正确的宽度写入字段 F，
+            /// value 超出字段宽度（如 ELF32 中超过 u32 的偏移）时返回 `ElfError::Overflow`，
+            /// 字段超出 header 时返回 `ElfError::OutOfBounds`，两种情况都不做修改
+            pub fn with_class<F: ClassField>(&self, value: u64) -> Result<&Self, ElfError> {
+                let cells = checked_cells(self.bytes, F::range_of(self.class))?;
+                if cells.len() < 8 && value >> (cells.len() * 8) != 0 {
+                    return Err(ElfError::Overflow);
+                }
+                let mut tmp = [0u8; 8];
+                let tmp = &mut tmp[..cells.len()];
+                Codec::new(self.class, self.encode).put_uint(tmp, value);
+                for (c, b) in cells.iter().zip(tmp.iter()) {
+                    c.set(*b);
+                }
+                Ok(self)
+            }
+
+            /// header 的字节长度
+            pub fn len(&self) -> usize {
+                self.bytes.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.bytes.is_empty()
+            }
+
+            /// 拷贝出 header 当前的内容
+            pub fn to_vec(&self) -> Vec<u8> {
+                self.bytes.iter().map(Cell::get).collect()
+            }
+
+            /// 与 `Getter::get` 相同，字段超出 header 时返回 `ElfError::OutOfBounds`
+            pub fn try_get<T: Field>(&self) -> Result<T::FieldType, ElfError> {
+                let cells = checked_cells(self.bytes, T::range())?;
+                Ok(read_cells(cells, |b| read_field::<T>(b, self.encode)))
+            }
+
+            /// 与 `Setter::with` 相同，字段超出 header 时返回 `ElfError::OutOfBounds` 且不做修改
+            pub fn try_with<T: Field + Mutable>(&self, value: T::FieldType) -> Result<&Self, ElfError> {
+                write_cells::<T>(checked_cells(self.bytes, T::range())?, value, self.encode);
+                Ok(self)
+            }
+        }
+
+        impl<'a> Getter for $name<'a> {
+            /// 字段超出 header 时 panic，不希望 panic 时应使用 `try_get`
+            fn get<T: Field>(&self) -> T::FieldType {
+                self.try_get::<T>().expect("字段超出 header")
+            }
+        }
+
+        impl<'a> Setter for $name<'a> {
+            /// 字段超出 header 时 panic，不希望 panic 时应使用 `try_with`
+            fn with<T: Field + Mutable>(&self, value: T::FieldType) -> &Self {
+                self.try_with::<T>(value).expect("字段超出 header")
+            }
+        }
+    };
+}
+
+header_view!(
+    /// 借用缓冲区的 Elf Header
+    EhdrView,
+    ehdr_size
+);
+header_view!(
+    /// 借用缓冲区的 Section Header
+    ShdrView,
+    shdr_size
+);
+header_view!(
+    /// 借用缓冲区的 Program Header
+    PhdrView,
+    phdr_size
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{EEntry, ShEntsize, ShOffset};
+
+    #[test]
+    fn edit_in_place() {
+        let mut buf = vec![0u8; 128];
+        {
+            let ehdr = EhdrView::new(&mut buf, Class::Elf64, Encode::Le).unwrap();
+            ehdr.with::<EEntry>(0x401000);
+            assert_eq!(ehdr.get::<EEntry>(), 0x401000);
+        }
+        assert_eq!(&buf[24..32], &0x401000u64.to_le_bytes());
+
+        let shdr = ShdrView::new(&mut buf[64..], Class::Elf64, Encode::Be).unwrap();
+        shdr.with::<ShOffset>(0x1234);
+        assert_eq!(shdr.len(), 64);
+        assert_eq!(shdr.to_vec()[24..32], 0x1234u64.to_be_bytes());
+
+        assert!(PhdrView::new(&mut buf[100..], Class::Elf64, Encode::Le).is_err());
+
+        // sh_entsize 位于 56..64，超出 56 字节的 Program Header
+        let phdr = PhdrView::new(&mut buf[..56], Class::Elf64, Encode::Le).unwrap();
+        assert_eq!(
+            phdr.try_get::<ShEntsize>(),
+            Err(ElfError::OutOfBounds(56..64))
+        );
+        assert!(phdr.try_with::<ShEntsize>(1).is_err());
+    }
+
+    #[test]
+    fn class_correct_widths() {
+        use crate::class_field::{EShnum, EShoff};
+
+        let mut buf = vec![0xffu8; 64];
+        {
+            let ehdr = EhdrView::new(&mut buf, Class::Elf64, Encode::Le).unwrap();
+            ehdr.with_class::<EShoff>(0x1_0000_2000).unwrap();
+            ehdr.with_class::<EShnum>(7).unwrap();
+            assert_eq!(ehdr.get_class::<EShoff>(), 0x1_0000_2000);
+        }
+        assert_eq!(&buf[40..48], &0x1_0000_2000u64.to_le_bytes());
+        assert_eq!(&buf[60..62], &[7, 0]);
+
+        let mut buf = vec![0xffu8; 52];
+        {
+            let ehdr = EhdrView::new(&mut buf, Class::Elf32, Encode::Be).unwrap();
+            ehdr.with_class::<EShoff>(0x2000).unwrap();
+            assert_eq!(ehdr.get_class::<EShoff>(), 0x2000);
+            assert_eq!(
+                ehdr.with_class::<EShoff>(0x1_0000_0000).err(),
+                Some(ElfError::Overflow)
+            );
+            assert_eq!(ehdr.get_class::<EShoff>(), 0x2000);
+        }
+        assert_eq!(&buf[32..36], &0x2000u32.to_be_bytes());
+        assert_eq!(&buf[36..40], &[0xff; 4]);
+    }
+}